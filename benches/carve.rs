@@ -0,0 +1,164 @@
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use image::{DynamicImage, Rgba, RgbaImage};
+
+use rmr::carve::{Carver, Direction};
+use rmr::ResizeOptions;
+
+/// Square image sizes every phase is measured at.
+const SIZES: [u32; 3] = [64, 256, 512];
+
+/// A deterministic image with smooth gradients and some noise, so seams have
+/// structure to follow and the numbers are comparable across commits.
+fn make_image(size: u32) -> DynamicImage {
+    let mut state = 0x2545_f491u32;
+    DynamicImage::ImageRgba8(RgbaImage::from_fn(size, size, |x, y| {
+        // xorshift32
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        let noise = (state % 32) as u8;
+        let r = ((x * 255) / size) as u8;
+        let g = ((y * 255) / size) as u8;
+        let b = (((x + y) * 127) / size) as u8;
+        Rgba([
+            r.wrapping_add(noise),
+            g.wrapping_add(noise),
+            b.wrapping_add(noise),
+            255,
+        ])
+    }))
+}
+
+/// A carver with its pixel energies calculated.
+fn carver_with_energy(image: &DynamicImage) -> Carver {
+    let mut carver = Carver::new(image).unwrap();
+    carver.bench_calculate_all_pixel_energy();
+    carver
+}
+
+/// A carver with its path costs calculated, ready to find a seam.
+fn carver_with_path_costs(image: &DynamicImage) -> Carver {
+    let mut carver = carver_with_energy(image);
+    carver.bench_calculate_energy();
+    carver
+}
+
+fn bench_set_direction(c: &mut Criterion) {
+    let mut group = c.benchmark_group("set_direction");
+    for &size in &SIZES {
+        let mut carver = Carver::new(&make_image(size)).unwrap();
+        group.bench_function(BenchmarkId::from_parameter(size), |b| {
+            b.iter(|| {
+                carver.bench_set_direction(Direction::Horizontal);
+                carver.bench_set_direction(Direction::Vertical);
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_calculate_all_pixel_energy(c: &mut Criterion) {
+    let mut group = c.benchmark_group("calculate_all_pixel_energy");
+    for &size in &SIZES {
+        let mut carver = Carver::new(&make_image(size)).unwrap();
+        group.bench_function(BenchmarkId::from_parameter(size), |b| {
+            b.iter(|| carver.bench_calculate_all_pixel_energy())
+        });
+    }
+    group.finish();
+}
+
+fn bench_calculate_energy(c: &mut Criterion) {
+    let mut group = c.benchmark_group("calculate_energy");
+    for &size in &SIZES {
+        let carver = carver_with_path_costs(&make_image(size));
+        // Only the pixels next to the removed seam need their energy updated,
+        // but every path cost is recalculated.
+        group.bench_function(BenchmarkId::from_parameter(size), |b| {
+            b.iter_batched(
+                || {
+                    let mut carver = carver.clone();
+                    carver.bench_find_path();
+                    carver.bench_remove_path();
+                    carver
+                },
+                |mut carver| carver.bench_calculate_energy(),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn bench_find_path(c: &mut Criterion) {
+    let mut group = c.benchmark_group("find_path");
+    for &size in &SIZES {
+        let mut carver = carver_with_path_costs(&make_image(size));
+        group.bench_function(BenchmarkId::from_parameter(size), |b| {
+            b.iter(|| carver.bench_find_path())
+        });
+    }
+    group.finish();
+}
+
+fn bench_remove_path(c: &mut Criterion) {
+    let mut group = c.benchmark_group("remove_path");
+    for &size in &SIZES {
+        let mut carver = carver_with_path_costs(&make_image(size));
+        carver.bench_find_path();
+        group.bench_function(BenchmarkId::from_parameter(size), |b| {
+            b.iter_batched(
+                || carver.clone(),
+                |mut carver| carver.bench_remove_path(),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn bench_grow_distance(c: &mut Criterion) {
+    let mut group = c.benchmark_group("grow_distance");
+    group.sample_size(10);
+    for &size in &SIZES {
+        let carver = carver_with_energy(&make_image(size));
+        let distance = size as usize / 10;
+        group.bench_function(BenchmarkId::from_parameter(size), |b| {
+            b.iter_batched(
+                || carver.clone(),
+                |mut carver| carver.bench_grow_distance(distance),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn bench_resize(c: &mut Criterion) {
+    let mut group = c.benchmark_group("resize");
+    group.sample_size(10);
+    for &size in &SIZES {
+        let image = make_image(size);
+        let target = size as usize * 9 / 10;
+        group.bench_function(BenchmarkId::new("shrink", size), |b| {
+            b.iter(|| rmr::resize(&image, ResizeOptions::new(target, target)).unwrap())
+        });
+        let target = size as usize * 11 / 10;
+        group.bench_function(BenchmarkId::new("grow", size), |b| {
+            b.iter(|| rmr::resize(&image, ResizeOptions::new(target, target)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_set_direction,
+    bench_calculate_all_pixel_energy,
+    bench_calculate_energy,
+    bench_find_path,
+    bench_remove_path,
+    bench_grow_distance,
+    bench_resize
+);
+criterion_main!(benches);
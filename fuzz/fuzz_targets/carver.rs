@@ -0,0 +1,75 @@
+#![no_main]
+use image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+use libfuzzer_sys::arbitrary::{self, Arbitrary};
+use libfuzzer_sys::fuzz_target;
+
+use rmr::carve::{Carver, Direction};
+use rmr::progress::{Control, Progress};
+
+/// The largest image generated along each side.
+const MAX_SIZE: u8 = 32;
+
+#[derive(Arbitrary, Debug)]
+struct Pass {
+    horizontal: bool,
+    target: u8,
+    /// Cancels the pass after this many progress reports.
+    cancel_after: Option<u8>,
+}
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    width: u8,
+    height: u8,
+    passes: Vec<Pass>,
+    pixels: Vec<u8>,
+}
+
+// Drives a `Carver` directly through a series of passes, any of which may
+// target zero or be cancelled partway, carrying on from wherever the last
+// one left off. Only empty images may fail to make a carver.
+fuzz_target!(|input: Input| {
+    let width = u32::from(input.width % (MAX_SIZE + 1));
+    let height = u32::from(input.height % (MAX_SIZE + 1));
+    let byte = |i: u32| match input.pixels.len() {
+        0 => 0,
+        len => input.pixels[i as usize % len],
+    };
+    let image = RgbaImage::from_fn(width, height, |x, y| {
+        let i = 4 * (y * width + x);
+        Rgba([byte(i), byte(i + 1), byte(i + 2), byte(i + 3)])
+    });
+    let mut carver = match Carver::new(&DynamicImage::ImageRgba8(image)) {
+        Ok(carver) => carver,
+        Err(_) => {
+            assert!(width == 0 || height == 0);
+            return;
+        }
+    };
+
+    for pass in input.passes.iter().take(4) {
+        let direction = if pass.horizontal {
+            Direction::Horizontal
+        } else {
+            Direction::Vertical
+        };
+        let target = usize::from(pass.target % (2 * MAX_SIZE));
+        let mut reports = 0;
+        let mut observer = |_: &Progress| {
+            reports += 1;
+            match pass.cancel_after {
+                Some(limit) if reports > usize::from(limit) => Control::Cancel,
+                _ => Control::Continue,
+            }
+        };
+
+        if carver.carve(direction, target, &mut observer).is_ok() {
+            let (width, height) = carver.image().dimensions();
+            let across = match direction {
+                Direction::Vertical => width,
+                Direction::Horizontal => height,
+            };
+            assert_eq!(target, across as usize);
+        }
+    }
+});
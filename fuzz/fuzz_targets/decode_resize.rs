@@ -0,0 +1,37 @@
+#![no_main]
+use std::io::Cursor;
+
+use image::io::Reader;
+use libfuzzer_sys::fuzz_target;
+
+use rmr::ResizeOptions;
+
+/// Bigger images slow every run down without reaching new code.
+const MAX_PIXELS: u64 = 64 * 64;
+
+// The first two bytes pick the target size and the rest are decoded as an
+// image in whatever format they look like.
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 2 {
+        return;
+    }
+    let (target, bytes) = data.split_at(2);
+
+    // Decoders allocate whatever the header asks for, so the size is checked
+    // before decoding.
+    let reader = || Reader::new(Cursor::new(bytes)).with_guessed_format();
+    let (width, height) = match reader().map(Reader::into_dimensions) {
+        Ok(Ok(dimensions)) => dimensions,
+        _ => return,
+    };
+    if u64::from(width) * u64::from(height) > MAX_PIXELS {
+        return;
+    }
+    let image = match reader().map(Reader::decode) {
+        Ok(Ok(image)) => image,
+        _ => return,
+    };
+
+    let options = ResizeOptions::new(usize::from(target[0]), usize::from(target[1]));
+    let _ = rmr::resize(&image, options);
+});
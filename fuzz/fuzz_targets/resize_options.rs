@@ -0,0 +1,87 @@
+#![no_main]
+use image::{DynamicImage, GrayImage, Luma, Rgba, RgbaImage};
+use libfuzzer_sys::arbitrary::{self, Arbitrary};
+use libfuzzer_sys::fuzz_target;
+
+use rmr::carve::{Interpolation, ResizeMode, SeamConstraint, SeamOrder, Strategy};
+use rmr::ResizeOptions;
+
+/// The largest image generated along each side.
+const MAX_SIZE: u8 = 32;
+
+#[derive(Arbitrary, Debug)]
+struct Input {
+    width: u8,
+    height: u8,
+    target_width: u8,
+    target_height: u8,
+    mode: u8,
+    strategy: u8,
+    strategy_size: u8,
+    seam_window: u8,
+    diagonal_penalty: u16,
+    height_first: bool,
+    duplicate: bool,
+    threads: u8,
+    protect: Vec<bool>,
+    pixels: Vec<u8>,
+}
+
+// Resizes a generated image, which may be empty, with every option chosen by
+// the fuzzer.
+fuzz_target!(|input: Input| {
+    let width = u32::from(input.width % (MAX_SIZE + 1));
+    let height = u32::from(input.height % (MAX_SIZE + 1));
+    let byte = |i: u32| match input.pixels.len() {
+        0 => 0,
+        len => input.pixels[i as usize % len],
+    };
+    let image = RgbaImage::from_fn(width, height, |x, y| {
+        let i = 4 * (y * width + x);
+        Rgba([byte(i), byte(i + 1), byte(i + 2), byte(i + 3)])
+    });
+    let mask = GrayImage::from_fn(width, height, |x, y| {
+        let i = (y * width + x) as usize;
+        let protected = !input.protect.is_empty() && input.protect[i % input.protect.len()];
+        Luma([if protected { 255 } else { 0 }])
+    });
+
+    let mode = match input.mode % 3 {
+        0 => ResizeMode::Carve,
+        1 => ResizeMode::Crop,
+        _ => ResizeMode::Auto,
+    };
+    let size = usize::from(input.strategy_size % 8);
+    let strategy = match input.strategy % 3 {
+        0 => Strategy::FullResolution,
+        1 => Strategy::pyramid(size),
+        _ => Strategy::Disjoint { seams: size },
+    };
+    let seam_constraint = SeamConstraint {
+        window: usize::from(input.seam_window % 4),
+        diagonal_penalty: u32::from(input.diagonal_penalty),
+    };
+    let seam_order = if input.height_first {
+        SeamOrder::HeightFirst
+    } else {
+        SeamOrder::WidthFirst
+    };
+    let interpolation = if input.duplicate {
+        Interpolation::Duplicate
+    } else {
+        Interpolation::Average
+    };
+
+    let options = ResizeOptions::new(
+        usize::from(input.target_width),
+        usize::from(input.target_height),
+    )
+    .mode(mode)
+    .strategy(strategy)
+    .seam_constraint(seam_constraint)
+    .seam_order(seam_order)
+    .interpolation(interpolation)
+    .threads(usize::from(input.threads % 4))
+    .protect_mask(mask);
+    let _ = rmr::resize(&DynamicImage::ImageRgba8(image), options);
+});
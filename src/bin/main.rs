@@ -1,84 +1,423 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, IsTerminal, Read, Write};
 use std::path::Path;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
-use image::{DynamicImage, GenericImage, GenericImageView, Rgba};
+use image::{DynamicImage, GenericImageView, GrayImage, ImageFormat};
 
-use rmr::carve::Carver;
-use rmr::config::{parse_args, Config};
-use rmr::BoxResult;
+use rmr::carve::Roi;
+use rmr::cascade::{Cascade, DetectOptions};
+use rmr::config::{get_format, parse_args, Config, STDIO_PATH};
+use rmr::debug::create_debug_image_with_lines;
+use rmr::encode::{save_image, write_image};
+use rmr::metadata::{apply_orientation, Metadata, ORIENTATION_NORMAL};
+use rmr::progress::{Control, Observer, Phase, Progress};
+use rmr::protect::{face_mask, merge_masks, skin_mask};
+use rmr::stats::Report;
+use rmr::tiff::TiledTiffReader;
+use rmr::tiled::StripSource;
+use rmr::{BoxResult, ResizeOptions, ResizeStats, TileOptions};
 
 fn main() {
     parse_args().and_then(run).unwrap()
 }
 
 fn run(mut config: Config) -> BoxResult<()> {
-    let mut image = image::open(&config.input_path)?;
-    let mut carver = Carver::new(&image);
+    if let Some(tiles) = config.tiles {
+        return run_tiled(&mut config, &tiles);
+    }
+
+    let to_stdout = config.output_is_stdout();
+    let stats_to_stdout = config
+        .stats_path
+        .as_ref()
+        .is_some_and(|path| path == Path::new(STDIO_PATH));
+    if to_stdout && stats_to_stdout {
+        return Err("The stats report and the image can't both be written to stdout".into());
+    }
+
+    let decode_start = Instant::now();
+    let Input {
+        image,
+        format: input_format,
+        metadata,
+    } = load_input(&config)?;
+    let decode = decode_start.elapsed();
+
+    let (width, height) = get_target_dimensions(image.dimensions(), &config)?;
+    let mut options = resize_options(&config, width, height);
+
+    if let Some(mask) = protection_mask(&image, &config, metadata.orientation)? {
+        options = options.protect_mask(mask);
+    }
+
+    if let Some(symmetry) = config.symmetry {
+        options = options.symmetry(symmetry);
+    }
 
-    let (width, height) = get_target_dimensions(&image, &config);
+    if let Some(ref roi) = config.roi {
+        options = options.roi(roi.clone());
+    } else if let Some(ref roi_path) = config.roi_path {
+        options = options.roi(Roi::Mask(load_mask(roi_path, metadata.orientation)?));
+    }
+
+    let output = rmr::resize(&image, options)?;
 
-    let time_start = if config.time {
-        Some(Instant::now())
+    if config.time {
+        print_time(output.stats.elapsed, to_stdout);
+    }
+
+    let encode_start = Instant::now();
+    let metadata = if config.strip_metadata {
+        Metadata::empty()
     } else {
+        metadata.updated_for(output.image.width(), output.image.height())
+    };
+    save_output(&output.image, &mut config, input_format, &metadata)?;
+    let encode = encode_start.elapsed();
+
+    if let Some(ref stats_path) = config.stats_path {
+        let report = Report {
+            decode,
+            encode,
+            resize: &output.stats,
+        };
+        write_stats(&report, stats_path)?;
+    }
+
+    if let Some(ref debug_path) = config.debug_path {
+        let debug_image = create_debug_image_with_lines(
+            &image,
+            &output.seams,
+            &output.lines,
+            &config.debug_options,
+        );
+        let format = get_format(debug_path)?;
+        save_image(&debug_image, debug_path, format, &config.encode_options)?;
+    }
+
+    Ok(())
+}
+
+/// Carves the input a strip at a time, streaming the result to a tiled
+/// TIFF. Tiled TIFF input is read a strip at a time too, while other input
+/// is decoded whole first, with a warning saying why.
+fn run_tiled(config: &mut Config, tiles: &TileOptions) -> BoxResult<()> {
+    if config.output_is_stdout() {
+        return Err("Tiled output can't be written to stdout".into());
+    }
+    let output_path = config.get_output_path().to_path_buf();
+    if get_format(&output_path) != Ok(ImageFormat::Tiff) {
+        return Err("Tiled output must be a .tif or .tiff file".into());
+    }
+
+    let tiff = if config.input_is_stdin() {
+        warn(
+            config,
+            "Input from stdin is decoded whole before it's carved in strips",
+        );
         None
+    } else {
+        match TiledTiffReader::open(&config.input_path) {
+            Ok(reader) => Some(reader),
+            Err(e) => {
+                warn(
+                    config,
+                    &format!(
+                        "{} can't be read in strips, so it's decoded whole: {}",
+                        config.input_path.display(),
+                        e
+                    ),
+                );
+                None
+            }
+        }
     };
+    let stats = match tiff {
+        Some(mut source) => {
+            resize_strips(&mut source, config, tiles, &output_path, ORIENTATION_NORMAL)?
+        }
+        None => {
+            let Input {
+                mut image,
+                metadata,
+                ..
+            } = load_input(config)?;
+            resize_strips(
+                &mut image,
+                config,
+                tiles,
+                &output_path,
+                metadata.orientation,
+            )?
+        }
+    };
+
+    if config.time {
+        print_time(stats.elapsed, false);
+    }
+    Ok(())
+}
 
-    let scaled_image = carver.resize(width, height);
+fn resize_strips<S: StripSource>(
+    source: &mut S,
+    config: &Config,
+    tiles: &TileOptions,
+    output_path: &Path,
+    orientation: u16,
+) -> BoxResult<ResizeStats> {
+    let (width, height) = get_target_dimensions(source.size(), config)?;
+    let mut options = resize_options(config, width, height);
+    if let Some(ref protect_path) = config.protect_path {
+        warn(config, "The protection mask is loaded whole, not in strips");
+        options = options.protect_mask(load_mask(protect_path, orientation)?);
+    }
+
+    let output = BufWriter::new(File::create(output_path)?);
+    rmr::resize_tiled(source, options, tiles, output)
+}
 
-    if let Some(time_start) = time_start {
-        let duration = time_start.elapsed();
-        let secs = duration.as_secs();
-        let nanos = duration.subsec_nanos();
-        println!("Resizing image took: {}.{}", secs, nanos);
+/// The options set by the flags that apply to both whole and tiled resizes.
+fn resize_options(config: &Config, width: usize, height: usize) -> ResizeOptions<'static> {
+    let mut options = ResizeOptions::new(width, height)
+        .mode(config.mode)
+        .strategy(config.strategy)
+        .energy_function(config.energy_function)
+        .boundary(config.boundary)
+        .seam_order(config.seam_order)
+        .interpolation(config.interpolation)
+        .seam_constraint(config.seam_constraint)
+        .threads(config.threads)
+        .low_memory(config.low_memory)
+        // The seams are only needed to draw the debug image.
+        .seam_log(config.debug_path.is_some());
+
+    if let Some(text) = config.text {
+        options = options.text(text);
     }
 
-    save_image_to_path(&scaled_image, config.get_output_path())?;
+    if let Some(lines) = config.lines {
+        options = options.lines(lines);
+    }
 
-    if let Some(debug_path) = config.debug_path {
-        let debug_image = create_debug_image(&mut image, &carver.get_removed_points());
-        save_image_to_path(&debug_image, debug_path)?;
+    if !config.quiet && io::stderr().is_terminal() {
+        options = options.observer(ProgressBar::default());
     }
+    options
+}
 
+/// Prints a warning to stderr unless `--quiet` was given.
+fn warn(config: &Config, message: &str) {
+    if !config.quiet {
+        eprintln!("Warning: {}", message);
+    }
+}
+
+fn print_time(duration: Duration, to_stdout: bool) {
+    let secs = duration.as_secs();
+    let nanos = duration.subsec_nanos();
+    // Keep stdout clean when the image itself is written there.
+    if to_stdout {
+        eprintln!("Resizing image took: {}.{:09}", secs, nanos);
+    } else {
+        println!("Resizing image took: {}.{:09}", secs, nanos);
+    }
+}
+
+struct Input {
+    image: DynamicImage,
+    format: Option<ImageFormat>,
+    metadata: Metadata,
+}
+
+fn load_input(config: &Config) -> BoxResult<Input> {
+    let (bytes, format) = if config.input_is_stdin() {
+        let mut bytes = vec![];
+        io::stdin().read_to_end(&mut bytes)?;
+        let format = image::guess_format(&bytes).ok();
+        (bytes, format)
+    } else {
+        let bytes = fs::read(&config.input_path)?;
+        let format = get_format(&config.input_path).ok();
+        (bytes, format)
+    };
+
+    let image = match format {
+        Some(format) => image::load_from_memory_with_format(&bytes, format)?,
+        None => image::load_from_memory(&bytes)?,
+    };
+
+    // Carving happens on the image as it's meant to be displayed.
+    let metadata = Metadata::read(&bytes);
+    let image = apply_orientation(image, metadata.orientation);
+
+    Ok(Input {
+        image,
+        format,
+        metadata,
+    })
+}
+
+fn save_output(
+    image: &DynamicImage,
+    config: &mut Config,
+    input_format: Option<ImageFormat>,
+    metadata: &Metadata,
+) -> BoxResult<()> {
+    let format = if config.output_is_stdout() {
+        config
+            .format
+            .or(input_format)
+            .ok_or("An output --format is required when writing to stdout")?
+    } else {
+        match config.format {
+            Some(format) => format,
+            None => get_format(config.get_output_path())?,
+        }
+    };
+
+    let mut bytes = vec![];
+    write_image(image, &mut bytes, format, &config.encode_options)?;
+    let bytes = metadata.embed(bytes)?;
+
+    if config.output_is_stdout() {
+        let mut stdout = io::stdout();
+        stdout.write_all(&bytes)?;
+        stdout.flush()?;
+    } else {
+        fs::write(config.get_output_path(), bytes)?;
+    }
     Ok(())
 }
 
-fn get_target_dimensions(image: &DynamicImage, config: &Config) -> (usize, usize) {
+fn get_target_dimensions(
+    (width, height): (u32, u32),
+    config: &Config,
+) -> BoxResult<(usize, usize)> {
     if let Some(dimensions) = config.dimensions {
-        return dimensions;
+        return Ok(dimensions);
     }
 
-    let (width, height) = image.dimensions();
-    let (mut width, mut height) = (width as usize, height as usize);
+    let width = apply_delta(width as usize, config.width, "width")?;
+    let height = apply_delta(height as usize, config.height, "height")?;
+    Ok((width, height))
+}
 
-    if let Some(delta_width) = config.width {
-        if delta_width >= 0 {
-            width += delta_width as usize;
-        } else {
-            width -= delta_width.abs() as usize;
-        }
+fn apply_delta(size: usize, delta: Option<isize>, name: &str) -> BoxResult<usize> {
+    let delta = match delta {
+        Some(delta) => delta,
+        None => return Ok(size),
+    };
+    let target = if delta >= 0 {
+        size.checked_add(delta as usize)
+    } else {
+        size.checked_sub(delta.unsigned_abs())
+    };
+    match target {
+        Some(target) if target > 0 => Ok(target),
+        _ => Err(format!("Can't change the {} of {} pixels by {}", name, size, delta).into()),
     }
+}
 
-    if let Some(delta_height) = config.height {
-        if delta_height >= 0 {
-            height += delta_height as usize;
-        } else {
-            height -= delta_height.abs() as usize;
+fn write_stats(report: &Report, path: &Path) -> BoxResult<()> {
+    let json = report.to_json();
+    if path == Path::new(STDIO_PATH) {
+        io::stdout().write_all(json.as_bytes())?;
+    } else {
+        fs::write(path, json)?;
+    }
+    Ok(())
+}
+
+/// Loads a mask drawn over the input, turning it the same way as the input
+/// so the two still line up.
+fn load_mask(path: &Path, orientation: u16) -> BoxResult<GrayImage> {
+    Ok(apply_orientation(image::open(path)?, orientation).to_luma8())
+}
+
+/// Combines the mask from `--protect` with any found faces and skin, saving
+/// the exported masks along the way.
+fn protection_mask(
+    image: &DynamicImage,
+    config: &Config,
+    orientation: u16,
+) -> BoxResult<Option<GrayImage>> {
+    let mut masks = vec![];
+    if let Some(ref protect_path) = config.protect_path {
+        masks.push(load_mask(protect_path, orientation)?);
+    }
+    if config.protect_faces {
+        let cascade = match config.face_cascade_path {
+            Some(ref path) => Cascade::load(path)?,
+            None => Cascade::frontal_face(),
+        };
+        let faces = cascade.detect(image, &DetectOptions::default());
+        let mask = face_mask(image.width(), image.height(), &faces);
+        if let Some(ref path) = config.face_mask_path {
+            mask.save(path)?;
         }
+        masks.push(mask);
+    }
+    if config.protect_skin {
+        let mask = skin_mask(image);
+        if let Some(ref path) = config.skin_mask_path {
+            mask.save(path)?;
+        }
+        masks.push(mask);
     }
 
-    (width, height)
+    let mut masks = masks.into_iter();
+    let first = match masks.next() {
+        Some(first) => first,
+        None => return Ok(None),
+    };
+    masks
+        .try_fold(first, |merged, mask| merge_masks(&merged, &mask))
+        .map(Some)
 }
 
-fn save_image_to_path<P: AsRef<Path>>(image: &DynamicImage, path: P) -> BoxResult<()> {
-    image.save(path)?;
-    Ok(())
+const PROGRESS_BAR_WIDTH: usize = 40;
+
+#[derive(Default)]
+struct ProgressBar {
+    last_filled: Option<usize>,
 }
 
-fn create_debug_image(image: &mut DynamicImage, points: &[(usize, usize)]) -> DynamicImage {
-    let red_pixel = Rgba([255, 0, 0, 255]);
-    let mut image = image.clone();
-    for &(x, y) in points {
-        image.put_pixel(x as u32, y as u32, red_pixel);
+impl Observer for ProgressBar {
+    fn on_progress(&mut self, progress: &Progress) -> Control {
+        let filled = (progress.completed * PROGRESS_BAR_WIDTH)
+            .checked_div(progress.total)
+            .unwrap_or(PROGRESS_BAR_WIDTH);
+
+        let finished = progress.completed == progress.total;
+        if self.last_filled == Some(filled) && !finished {
+            return Control::Continue;
+        }
+        self.last_filled = if finished { None } else { Some(filled) };
+
+        let label = match progress.phase {
+            Phase::Energy => "Energy",
+            Phase::GrowPrecompute => "Seam search",
+            Phase::Carve => "Carving",
+        };
+        let direction = format!("{:?}", progress.direction).to_lowercase();
+
+        let mut stderr = io::stderr();
+        let _ = write!(
+            stderr,
+            "\r{:>11} ({:<10}) [{}{}] {}/{}",
+            label,
+            direction,
+            "#".repeat(filled),
+            "-".repeat(PROGRESS_BAR_WIDTH - filled),
+            progress.completed,
+            progress.total
+        );
+        if finished {
+            let _ = writeln!(stderr);
+        }
+        let _ = stderr.flush();
+
+        Control::Continue
     }
-    image
 }
@@ -0,0 +1,54 @@
+/// A fixed number of flags packed one bit each.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Bitmap {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl Bitmap {
+    /// `len` flags, all cleared.
+    pub fn new(len: usize) -> Self {
+        Bitmap {
+            words: vec![0; len.div_ceil(64)],
+            len,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Whether flag `index` is set.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn get(&self, index: usize) -> bool {
+        assert!(index < self.len, "Bitmap index out of bounds");
+        self.words[index / 64] & (1 << (index % 64)) != 0
+    }
+
+    /// Sets or clears flag `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    pub fn set(&mut self, index: usize, value: bool) {
+        assert!(index < self.len, "Bitmap index out of bounds");
+        let bit = 1 << (index % 64);
+        if value {
+            self.words[index / 64] |= bit;
+        } else {
+            self.words[index / 64] &= !bit;
+        }
+    }
+
+    /// Heap memory held by the flags, in bytes.
+    pub fn memory_footprint(&self) -> usize {
+        self.words.capacity() * 8
+    }
+}
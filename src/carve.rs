@@ -1,98 +1,1406 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
 use std::mem;
+use std::thread;
+use std::time::Instant;
 
-use image::{DynamicImage, GenericImage};
+use image::{DynamicImage, GenericImage, GenericImageView, GrayImage, Rgba, RgbaImage};
 
-use crate::energy::PixelEnergyPoint;
+use crate::bitmap::Bitmap;
+use crate::crop::{self, CropWindow};
+use crate::energy::{
+    self, Boundary, EnergyFunction, PixelEnergyPoint, TextOptions, PROTECTED_ENERGY,
+};
 use crate::grid::{Grid, Token};
+use crate::layer::{CarvedLayer, DynamicLayer, Layer};
+use crate::lines::{self, LineOptions, LineSegment};
+use crate::progress::{self, Cancelled, Control, Observer, Phase, Progress};
+use crate::stats::PassStats;
+use crate::BoxResult;
+
+/// The axis a seam runs along. Vertical seams change the width of the image,
+/// horizontal seams change the height.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Vertical,
+    Horizontal,
+}
+
+/// Whether a seam was carved out of the image or inserted into it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SeamKind {
+    Removed,
+    Inserted,
+}
+
+/// A single pixel of a seam, positioned relative to the input image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SeamPoint {
+    pub x: usize,
+    pub y: usize,
+    pub direction: Direction,
+    pub kind: SeamKind,
+}
+
+/// The number of seams removed and inserted in each direction.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SeamCounts {
+    pub vertical_removed: usize,
+    pub vertical_inserted: usize,
+    pub horizontal_removed: usize,
+    pub horizontal_inserted: usize,
+}
+
+impl SeamCounts {
+    fn add(&mut self, direction: Direction, kind: SeamKind, count: usize) {
+        let counter = match (direction, kind) {
+            (Direction::Vertical, SeamKind::Removed) => &mut self.vertical_removed,
+            (Direction::Vertical, SeamKind::Inserted) => &mut self.vertical_inserted,
+            (Direction::Horizontal, SeamKind::Removed) => &mut self.horizontal_removed,
+            (Direction::Horizontal, SeamKind::Inserted) => &mut self.horizontal_inserted,
+        };
+        *counter += count;
+    }
+}
+
+/// Which dimension is carved first when both the width and height change.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SeamOrder {
+    #[default]
+    WidthFirst,
+    HeightFirst,
+}
+
+impl SeamOrder {
+    pub(crate) fn directions(self) -> [Direction; 2] {
+        match self {
+            SeamOrder::WidthFirst => [Direction::Vertical, Direction::Horizontal],
+            SeamOrder::HeightFirst => [Direction::Horizontal, Direction::Vertical],
+        }
+    }
+}
+
+/// How the image is made smaller in each direction. Enlarging always
+/// inserts seams.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ResizeMode {
+    /// Remove the seams with the least energy.
+    #[default]
+    Carve,
+    /// Crop to the window that keeps the most energy.
+    Crop,
+    /// Carve or crop, whichever removes less energy in each pass.
+    Auto,
+}
+
+/// How seams are searched for.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Strategy {
+    /// Search the whole image for every seam.
+    #[default]
+    FullResolution,
+    /// Find seams on a copy of the image downscaled by `factor`, then refine
+    /// each one at full resolution within `band` pixels of the proxy seam.
+    /// Every proxy seam guides `factor` full resolution seams.
+    Pyramid { factor: usize, band: usize },
+    /// Take up to `seams` non-overlapping seams from each table of path
+    /// costs instead of recalculating it after every seam. Later seams in a
+    /// batch are found greedily, so they may cost more than the best seams.
+    Disjoint { seams: usize },
+}
+
+impl Strategy {
+    /// A pyramid strategy with a band two proxy pixels wide.
+    pub fn pyramid(factor: usize) -> Self {
+        Strategy::Pyramid {
+            factor,
+            band: 2 * factor,
+        }
+    }
+}
+
+/// How the pixels of an inserted seam are filled in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Interpolation {
+    /// Blend the pixels on either side of the seam.
+    #[default]
+    Average,
+    /// Repeat the pixel to the left of (or above) the seam.
+    Duplicate,
+}
+
+/// Limits on how a seam may wander between neighbouring rows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SeamConstraint {
+    /// How many pixels a seam may step sideways from one row to the next.
+    /// The classic algorithm uses 1; 0 only allows straight seams.
+    pub window: usize,
+    /// Added to a seam's cost for every pixel it steps sideways, favouring
+    /// smoother seams.
+    pub diagonal_penalty: u32,
+}
+
+impl SeamConstraint {
+    /// Seams that run straight through the image, for comparison with
+    /// content-aware seams.
+    pub fn straight() -> Self {
+        SeamConstraint {
+            window: 0,
+            diagonal_penalty: 0,
+        }
+    }
+
+    fn step_cost(self, from: usize, to: usize) -> u32 {
+        let step = from.abs_diff(to) as u32;
+        self.diagonal_penalty.saturating_mul(step)
+    }
+}
+
+impl Default for SeamConstraint {
+    fn default() -> Self {
+        SeamConstraint {
+            window: 1,
+            diagonal_penalty: 0,
+        }
+    }
+}
+
+/// The part of the image seams may pass through. Pixels outside it are never
+/// removed, so the image only shrinks within the region, and seams are only
+/// inserted next to pixels inside it.
+///
+/// Every seam crosses the image from edge to edge, so vertical seams need a
+/// region spanning the full height and horizontal seams one spanning the
+/// full width.
+#[derive(Clone, Debug)]
+pub enum Roi {
+    /// A `width` by `height` rectangle with its top left corner at `(x, y)`.
+    Rect {
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    },
+    /// Every pixel where the mask is non-zero. Must be the same size as the
+    /// image.
+    Mask(GrayImage),
+}
+
+impl Roi {
+    /// Whether the pixel at `(x, y)` is inside the region.
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        match *self {
+            Roi::Rect {
+                x: left,
+                y: top,
+                width,
+                height,
+            } => x >= left && x - left < width && y >= top && y - top < height,
+            Roi::Mask(ref mask) => mask.get_pixel(x, y)[0] != 0,
+        }
+    }
+}
+
+/// Keeps the image symmetric by removing seams in mirrored pairs, each pair
+/// chosen by the joint energy of both seams.
+///
+/// A distance that's odd leaves one seam over. It runs straight down the
+/// axis when the axis is on a column, and is carved as usual otherwise, as
+/// are any seams left once no mirrored pair fits. Enlarging inserts seams as
+/// usual.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Symmetry {
+    /// The direction of the axis and of the seams paired about it. A
+    /// vertical axis keeps the left and right of the image mirror images as
+    /// the width shrinks.
+    pub direction: Direction,
+    /// The axis in pixels from the left or top edge of the input image, or
+    /// `None` to find the best axis automatically. The middle of a 10 pixel
+    /// wide image is at 5.
+    pub axis: Option<f64>,
+}
+
+impl Symmetry {
+    /// Symmetry about an axis found automatically.
+    pub fn detect(direction: Direction) -> Self {
+        Symmetry {
+            direction,
+            axis: None,
+        }
+    }
+
+    /// Symmetry about the axis `axis` pixels from the left or top edge.
+    pub fn about(direction: Direction, axis: f64) -> Self {
+        Symmetry {
+            direction,
+            axis: Some(axis),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct CarverOptions {
+    pub mode: ResizeMode,
+    pub strategy: Strategy,
+    pub energy_function: EnergyFunction,
+    pub boundary: Boundary,
+    pub seam_order: SeamOrder,
+    pub interpolation: Interpolation,
+    pub seam_constraint: SeamConstraint,
+    /// Seams avoid every pixel where the mask is non-zero. Must be the same
+    /// size as the image.
+    pub protect_mask: Option<GrayImage>,
+    /// Finds rows of text in the image and protects them like the mask.
+    pub text: Option<TextOptions>,
+    /// Restricts seams to a region of the image. Cropping can't keep to a
+    /// region, so with one every mode carves.
+    pub roi: Option<Roi>,
+    /// Finds straight lines in the image and adds energy along them, so
+    /// seams avoid crossing and bending them.
+    pub lines: Option<LineOptions>,
+    /// Removes seams in mirrored pairs. Crops are rarely symmetric, so with
+    /// symmetry every mode carves in its direction.
+    pub symmetry: Option<Symmetry>,
+    /// Number of threads used when calculating the energy of the whole grid.
+    pub threads: usize,
+    /// Plans enlargements by carving the image itself and putting the seams
+    /// back afterwards, instead of carving a copy of it. Keeps the memory
+    /// held by `resize` in carve mode under `LOW_MEMORY_BYTES_PER_PIXEL`.
+    pub low_memory: bool,
+    /// Keeps a `SeamPoint` for every pixel carved, for `get_seam_points`.
+    /// Turning it off saves 24 bytes per pixel carved.
+    pub seam_log: bool,
+}
+
+impl Default for CarverOptions {
+    fn default() -> Self {
+        CarverOptions {
+            mode: ResizeMode::default(),
+            strategy: Strategy::default(),
+            energy_function: EnergyFunction::default(),
+            boundary: Boundary::default(),
+            seam_order: SeamOrder::default(),
+            interpolation: Interpolation::default(),
+            seam_constraint: SeamConstraint::default(),
+            protect_mask: None,
+            text: None,
+            roi: None,
+            lines: None,
+            symmetry: None,
+            threads: 1,
+            low_memory: false,
+            seam_log: true,
+        }
+    }
+}
+
+/// How many times the cost of the best seam in a batch the other seams in
+/// it may cost.
+const MAX_DISJOINT_COST_RATIO: u32 = 2;
+
+/// The path cost of pixels no seam may reach. Real path costs saturate just
+/// below it.
+const UNREACHABLE: u32 = u32::MAX;
+
+/// The most memory `resize` holds at once with `low_memory` and without
+/// `seam_log` in carve mode, in bytes per pixel of the largest the image
+/// gets, not counting the input image or any layers. A 100 megapixel
+/// panorama stays under 3.2GB.
+///
+/// Each pixel takes 16 bytes in the grid, and planning an enlargement
+/// renumbers the pixels and saves their energy for another 8. The seams
+/// planned for each round are held with the pixels they took until they're
+/// inserted. Protection and the region of interest take a bit per pixel,
+/// and only the few pixels beside the last seam are tracked with tokens.
+pub const LOW_MEMORY_BYTES_PER_PIXEL: usize = 32;
+
+/// Why `Carver::carve` stopped short of its target.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CarveError {
+    /// The observer cancelled the resize.
+    Cancelled,
+    /// The target was zero pixels across.
+    EmptyTarget,
+    /// The region of interest ran out of seams before the target was
+    /// reached.
+    NoSeamInRoi,
+}
+
+impl fmt::Display for CarveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CarveError::Cancelled => Cancelled.fmt(f),
+            CarveError::EmptyTarget => write!(f, "Can't carve an image down to nothing"),
+            CarveError::NoSeamInRoi => write!(f, "No seam runs through the region of interest"),
+        }
+    }
+}
+
+impl Error for CarveError {}
+
+impl From<Cancelled> for CarveError {
+    fn from(_: Cancelled) -> Self {
+        CarveError::Cancelled
+    }
+}
+
+/// What the carver knows about the input pixels beyond their color, looked
+/// up by `original_index` so the points in the grid stay small.
+#[derive(Clone, Default)]
+struct Attributes {
+    /// Pixels seams avoid whenever they can.
+    protected: Option<Bitmap>,
+    /// Pixels seams may pass through, when there's a region of interest.
+    roi: Option<Bitmap>,
+    /// Energy added on top of the gradient of a few pixels, such as those on
+    /// a line that should stay straight.
+    bias: HashMap<u32, u32>,
+    /// Set while an enlargement is planned with the points renumbered, to
+    /// the `original_index` each of them had before.
+    sources: Option<Vec<u32>>,
+}
+
+impl Attributes {
+    /// The input pixel a point with `index` as its `original_index` came
+    /// from.
+    fn source(&self, index: u32) -> u32 {
+        match self.sources {
+            Some(ref sources) => sources[index as usize],
+            None => index,
+        }
+    }
+
+    /// The energy of a point with gradient `energy`, plus its bias and raised
+    /// if it's protected.
+    fn energy(&self, index: u32, energy: u32) -> u32 {
+        let source = self.source(index);
+        let energy = energy.saturating_add(self.bias.get(&source).copied().unwrap_or(0));
+        match self.protected {
+            Some(ref protected) if protected.get(source as usize) => {
+                energy.saturating_add(PROTECTED_ENERGY)
+            }
+            _ => energy,
+        }
+    }
+
+    /// Whether seams may pass through a point.
+    fn carvable(&self, index: u32) -> bool {
+        self.roi
+            .as_ref()
+            .is_none_or(|roi| roi.get(self.source(index) as usize))
+    }
+
+    fn memory_footprint(&self) -> usize {
+        self.protected.as_ref().map_or(0, Bitmap::memory_footprint)
+            + self.roi.as_ref().map_or(0, Bitmap::memory_footprint)
+            + self.bias.capacity() * (mem::size_of::<(u32, u32)>() + 1)
+            + self
+                .sources
+                .as_ref()
+                .map_or(0, |sources| sources.capacity() * mem::size_of::<u32>())
+    }
+}
 
 #[derive(Clone)]
 pub struct Carver {
     grid: Grid<PixelEnergyPoint>,
-    removed_points: Vec<(usize, usize)>,
+    attributes: Attributes,
+    layers: Vec<Box<dyn CarvedLayer>>,
+    lines: Vec<LineSegment>,
+    removed_points: Vec<SeamPoint>,
     dirty_points: Vec<Token>,
     path: Vec<(usize, usize)>,
+    seam_counts: SeamCounts,
+    passes: Vec<PassStats>,
+    peak_memory_bytes: usize,
+    mode: ResizeMode,
+    strategy: Strategy,
+    energy_function: EnergyFunction,
+    boundary: Boundary,
+    seam_order: SeamOrder,
+    interpolation: Interpolation,
+    seam_constraint: SeamConstraint,
+    restricted: bool,
+    symmetry: Option<Direction>,
+    /// The sum of the positions of every pair of mirrored pixels, or `None`
+    /// to find the axis when shrinking.
+    mirror_sum: Option<usize>,
+    /// Set while path costs are calculated for mirrored pairs of seams.
+    pairing: Option<usize>,
+    /// The width of the image `original_index` counts in.
+    index_width: usize,
+    low_memory: bool,
+    seam_log: bool,
+    /// Set while an enlargement is planned in place, collecting each seam
+    /// removed with the pixels it took so they can be put back.
+    undo: Option<Vec<(Vec<usize>, Vec<PixelEnergyPoint>)>>,
+    threads: usize,
 }
 
 impl Carver {
-    pub fn new(image: &DynamicImage) -> Self {
-        let grid = image.into();
-        Self {
+    /// A carver for `image` with the default options. Fails if `image` is
+    /// empty or has 2^32 pixels or more.
+    pub fn new(image: &DynamicImage) -> BoxResult<Self> {
+        Self::with_options(image, CarverOptions::default())
+    }
+
+    pub fn with_options(image: &DynamicImage, options: CarverOptions) -> BoxResult<Self> {
+        if image.width() == 0 || image.height() == 0 {
+            return Err("Image must be at least one pixel wide and high".into());
+        }
+        if u64::from(image.width()) * u64::from(image.height()) > u64::from(u32::MAX) {
+            return Err("Image must have fewer than 2^32 pixels".into());
+        }
+
+        let grid: Grid<PixelEnergyPoint> = image.into();
+        let mut attributes = Attributes::default();
+        // Attributes are kept by `original_index`, which counts across the
+        // rows of the input.
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        let pixels = width * height;
+
+        if let Some(mask) = options.protect_mask {
+            if mask.dimensions() != image.dimensions() {
+                return Err("Protection mask must be the same size as the image".into());
+            }
+            let mut protected = Bitmap::new(pixels);
+            for (x, y, pixel) in mask.enumerate_pixels() {
+                protected.set(y as usize * width + x as usize, pixel[0] != 0);
+            }
+            attributes.protected = Some(protected);
+        }
+
+        if let Some(text_options) = options.text {
+            let mask = energy::text_mask(image, &text_options);
+            let protected = attributes
+                .protected
+                .get_or_insert_with(|| Bitmap::new(pixels));
+            for (x, y, pixel) in mask.enumerate_pixels() {
+                if pixel[0] != 0 {
+                    protected.set(y as usize * width + x as usize, true);
+                }
+            }
+        }
+
+        let mirror_sum = match options.symmetry.and_then(|symmetry| symmetry.axis) {
+            Some(axis) => {
+                let len = match options.symmetry.map(|symmetry| symmetry.direction) {
+                    Some(Direction::Horizontal) => image.height(),
+                    _ => image.width(),
+                };
+                let doubled = (axis * 2.0).round();
+                if !(doubled >= 1.0 && doubled < f64::from(len) * 2.0) {
+                    return Err("Symmetry axis must lie within the image".into());
+                }
+                Some(doubled as usize - 1)
+            }
+            None => None,
+        };
+
+        let lines = match options.lines {
+            Some(line_options) => {
+                let lines = lines::detect_lines(image, &line_options);
+                // Lines are two pixels thick so diagonal seams can't slip
+                // between their pixels.
+                for line in &lines {
+                    for (x, y) in line.points() {
+                        let (x, y) = (x as usize, y as usize);
+                        for index in [
+                            y * width + x,
+                            y * width + (x + 1).min(width - 1),
+                            (y + 1).min(height - 1) * width + x,
+                        ] {
+                            attributes.bias.insert(index as u32, line_options.weight);
+                        }
+                    }
+                }
+                lines
+            }
+            None => vec![],
+        };
+
+        let restricted = options.roi.is_some();
+        if let Some(roi) = options.roi {
+            match roi {
+                Roi::Rect {
+                    x,
+                    y,
+                    width,
+                    height,
+                } => {
+                    let (image_width, image_height) = image.dimensions();
+                    if width == 0
+                        || height == 0
+                        || x.saturating_add(width) > image_width
+                        || y.saturating_add(height) > image_height
+                    {
+                        return Err("Region of interest must be a non-empty rectangle within \
+                                    the image"
+                            .into());
+                    }
+                }
+                Roi::Mask(ref mask) => {
+                    if mask.dimensions() != image.dimensions() {
+                        return Err(
+                            "Region of interest mask must be the same size as the image".into()
+                        );
+                    }
+                }
+            }
+            let mut carvable = Bitmap::new(pixels);
+            for index in 0..pixels {
+                let (x, y) = (index % width, index / width);
+                carvable.set(index, roi.contains(x as u32, y as u32));
+            }
+            attributes.roi = Some(carvable);
+        }
+
+        let mut carver = Self {
             grid,
+            attributes,
+            layers: vec![],
+            lines,
             removed_points: vec![],
             dirty_points: vec![],
             path: vec![],
+            seam_counts: SeamCounts::default(),
+            passes: vec![],
+            peak_memory_bytes: 0,
+            mode: options.mode,
+            strategy: options.strategy,
+            energy_function: options.energy_function,
+            boundary: options.boundary,
+            seam_order: options.seam_order,
+            interpolation: options.interpolation,
+            seam_constraint: options.seam_constraint,
+            restricted,
+            symmetry: options.symmetry.map(|symmetry| symmetry.direction),
+            mirror_sum,
+            pairing: None,
+            index_width: image.width() as usize,
+            low_memory: options.low_memory,
+            seam_log: options.seam_log,
+            undo: None,
+            threads: options.threads.max(1),
+        };
+        carver.track_memory(0);
+        Ok(carver)
+    }
+
+    /// Adds a layer that will be carved along with the image, returning its
+    /// index. The layer must be the same size as the image.
+    pub fn add_layer<T: Clone + 'static>(&mut self, layer: Layer<T>) -> BoxResult<usize> {
+        self.push_layer(Box::new(layer))
+    }
+
+    /// Adds an image layer, which keeps its pixel type, returning its index.
+    pub fn add_image_layer(
+        &mut self,
+        image: &DynamicImage,
+        interpolation: Interpolation,
+    ) -> BoxResult<usize> {
+        self.push_layer(Box::new(DynamicLayer::new(image, interpolation)))
+    }
+
+    /// The layer at `index` as carved so far, or `None` if there's no such
+    /// layer or it doesn't hold values of type `T`.
+    pub fn layer<T: 'static>(&self, index: usize) -> Option<&Layer<T>> {
+        self.layers.get(index)?.as_any().downcast_ref()
+    }
+
+    /// The image layer at `index` as carved so far, or `None` if there's no
+    /// such layer or it was added with `add_layer`.
+    pub fn image_layer(&self, index: usize) -> Option<DynamicImage> {
+        self.layers
+            .get(index)?
+            .as_any()
+            .downcast_ref::<DynamicLayer>()
+            .map(DynamicLayer::to_image)
+    }
+
+    /// Resizes the image to `width` by `height` pixels. Fails if `width` or
+    /// `height` is zero or the region of interest runs out of seams.
+    pub fn resize(&mut self, width: usize, height: usize) -> Result<DynamicImage, CarveError> {
+        let mut observer = |_: &Progress| Control::Continue;
+        self.resize_with_observer(width, height, &mut observer)
+    }
+
+    /// Resizes like `resize`, reporting progress to `observer` after every
+    /// seam. The resize stops early if the observer returns `Control::Cancel`,
+    /// and fails wherever `carve` does.
+    pub fn resize_with_observer(
+        &mut self,
+        width: usize,
+        height: usize,
+        observer: &mut dyn Observer,
+    ) -> Result<DynamicImage, CarveError> {
+        for &direction in &self.seam_order.directions() {
+            let target = match direction {
+                Direction::Vertical => width,
+                Direction::Horizontal => height,
+            };
+            self.carve(direction, target, observer)?;
         }
+
+        Ok(self.image())
     }
 
-    pub fn resize(&mut self, width: usize, height: usize) -> DynamicImage {
-        let initial_width = self.grid.width();
-        let initial_height = self.grid.height();
+    /// Removes or inserts seams running in `direction` until the image is
+    /// `target` pixels across them, so vertical seams set the width and
+    /// horizontal seams the height. Fails if `target` is zero or the region
+    /// of interest runs out of seams, which `check_roi` catches up front for
+    /// most regions.
+    pub fn carve(
+        &mut self,
+        direction: Direction,
+        target: usize,
+        observer: &mut dyn Observer,
+    ) -> Result<(), CarveError> {
+        if target == 0 {
+            return Err(CarveError::EmptyTarget);
+        }
+        self.grid.set_direction(direction);
+        for layer in &mut self.layers {
+            layer.set_direction(direction);
+        }
+        let current = self.grid.width();
+        if current == target {
+            return Ok(());
+        }
+        // Every pixel carved is logged, so the log is sized up front rather
+        // than left to double past it.
+        if self.seam_log {
+            self.removed_points
+                .reserve_exact(current.abs_diff(target) * self.grid.height());
+        }
+
+        let mut pass = PassStats::new(direction);
+        let energy_start = Instant::now();
+        self.calculate_all_pixel_energy_observed(observer)?;
+        pass.energy = energy_start.elapsed();
 
-        self.calculate_all_pixel_energy();
-        let mut width_changed = false;
+        if target > current {
+            self.grow_distance(target - current, observer, &mut pass)?;
+        } else {
+            let carve_start = Instant::now();
+            self.shrink_pass(current - target, observer, &mut pass)?;
+            pass.seams_removed = current - target;
+            pass.carve = carve_start.elapsed();
+        }
+
+        self.passes.push(pass);
+        Ok(())
+    }
 
-        if width > initial_width {
-            self.grow_distance(width - initial_width);
-            width_changed = true;
-        } else if width < initial_width {
-            self.shrink_distance(initial_width - width);
-            width_changed = true;
+    /// Checks that the region of interest can take `carve` in `direction` to
+    /// `target`. Every row of the region has to be at least as wide as the
+    /// seams removed, and at least one seam has to run through it from edge
+    /// to edge. Regions that narrow sharply from row to row can still run out
+    /// of seams partway, which `carve` reports as `CarveError::NoSeamInRoi`.
+    pub fn check_roi(&self, direction: Direction, target: usize) -> BoxResult<()> {
+        if !self.restricted {
+            return Ok(());
         }
 
-        if width_changed && height != initial_height {
-            self.calculate_all_pixel_energy();
+        // The grid's view may not match `direction`, so this works from image
+        // coordinates.
+        let (width, height) = self.grid.dimensions();
+        let (across, along) = match direction {
+            Direction::Vertical => (width, height),
+            Direction::Horizontal => (height, width),
+        };
+        if target == across {
+            return Ok(());
+        }
+        let mut carvable = vec![false; across * along];
+        for (x, y, pep) in self.grid.coord_iter() {
+            let (column, row) = match direction {
+                Direction::Vertical => (x, y),
+                Direction::Horizontal => (y, x),
+            };
+            carvable[row * across + column] = self.attributes.carvable(pep.original_index);
+        }
+        let rows: Vec<&[bool]> = carvable.chunks(across).collect();
+
+        let narrowest = rows
+            .iter()
+            .map(|row| row.iter().filter(|&&carvable| carvable).count())
+            .min()
+            .unwrap_or(0);
+        let distance = across.saturating_sub(target);
+        if narrowest < distance.max(1) {
+            let name = match direction {
+                Direction::Vertical => "width",
+                Direction::Horizontal => "height",
+            };
+            return Err(format!(
+                "The region of interest is {} pixels across at its narrowest, so it can't \
+                 change the {} from {} to {}",
+                narrowest, name, across, target
+            )
+            .into());
         }
 
-        if height > initial_height {
-            self.grid.rotate();
-            self.grow_distance(height - initial_height);
-            self.grid.rotate();
-        } else if height < initial_height {
-            self.grid.rotate();
-            self.shrink_distance(initial_height - height);
-            self.grid.rotate();
+        let window = self.seam_constraint.window;
+        let mut reachable = rows[0].to_vec();
+        for row in &rows[1..] {
+            reachable = (0..across)
+                .map(|x| {
+                    let first = x.saturating_sub(window);
+                    let last = (x + window).min(across - 1);
+                    row[x] && reachable[first..=last].iter().any(|&reachable| reachable)
+                })
+                .collect();
+        }
+        if !reachable.contains(&true) {
+            return Err(CarveError::NoSeamInRoi.into());
         }
+        Ok(())
+    }
 
-        self.rebuild_image()
+    /// The image as carved so far.
+    pub fn image(&self) -> DynamicImage {
+        let (width, height) = self.grid.dimensions();
+        let mut image = DynamicImage::new_rgba8(width as u32, height as u32);
+        for (x, y, pep) in self.grid.coord_iter() {
+            image.put_pixel(x as u32, y as u32, pep.pixel);
+        }
+        image
     }
 
+    /// Positions of every pixel carved out of the image, in input image
+    /// coordinates. Inserted seams are not included, and nothing is if
+    /// `seam_log` was off.
     pub fn get_removed_points(self) -> Vec<(usize, usize)> {
         self.removed_points
+            .into_iter()
+            .filter(|point| point.kind == SeamKind::Removed)
+            .map(|point| (point.x, point.y))
+            .collect()
+    }
+
+    /// The straight lines found in the input image when `lines` is set.
+    pub fn lines(&self) -> &[LineSegment] {
+        &self.lines
+    }
+
+    pub fn seam_counts(&self) -> SeamCounts {
+        self.seam_counts
     }
 
-    fn shrink_distance(&mut self, distance: usize) {
-        for _ in 0..distance {
+    /// Timings and seam energy for each pass made by `resize`.
+    pub fn pass_stats(&self) -> &[PassStats] {
+        &self.passes
+    }
+
+    /// An estimate of the most memory held at once while resizing, including
+    /// the copy made to plan enlargements.
+    pub fn peak_memory_bytes(&self) -> usize {
+        self.peak_memory_bytes
+    }
+
+    /// Every seam pixel touched by `resize`, in the order they were processed.
+    /// Empty if `seam_log` was off.
+    pub fn get_seam_points(self) -> Vec<SeamPoint> {
+        self.removed_points
+    }
+
+    /// Removes `distance` seams, returning the sum of their path costs.
+    fn shrink_distance(
+        &mut self,
+        distance: usize,
+        observer: &mut dyn Observer,
+        phase: Phase,
+    ) -> Result<u64, CarveError> {
+        match self.strategy {
+            Strategy::Pyramid { factor, band } => {
+                let (width, height) = self.grid.dimensions();
+                if factor > 1 && width / factor > 1 && height / factor > 1 {
+                    return self.shrink_distance_guided(distance, observer, phase, factor, band);
+                }
+            }
+            Strategy::Disjoint { seams } if seams > 1 => {
+                return self.shrink_distance_disjoint(distance, observer, phase, seams);
+            }
+            _ => {}
+        }
+
+        let direction = self.direction();
+        let mut seam_energy = 0;
+        progress::report(observer, phase, direction, 0, distance)?;
+        for i in 0..distance {
+            seam_energy += u64::from(self.remove_min_seam()?);
+            self.seam_counts.add(direction, SeamKind::Removed, 1);
+            progress::report(observer, phase, direction, i + 1, distance)?;
+        }
+        self.track_memory(0);
+        Ok(seam_energy)
+    }
+
+    /// Removes the seam with the lowest path cost in the whole grid,
+    /// returning its cost.
+    fn remove_min_seam(&mut self) -> Result<u32, CarveError> {
+        self.calculate_energy();
+        let (start_x, start_y) = self.get_path_start();
+        let cost = self.grid.get(start_x, start_y).path_cost;
+        if cost == UNREACHABLE {
+            return Err(CarveError::NoSeamInRoi);
+        }
+        self.find_path(start_x, start_y);
+        self.remove_path();
+        Ok(cost)
+    }
+
+    /// Like `shrink_distance`, but removes up to `batch` seams for every
+    /// calculation of the path costs.
+    fn shrink_distance_disjoint(
+        &mut self,
+        distance: usize,
+        observer: &mut dyn Observer,
+        phase: Phase,
+        batch: usize,
+    ) -> Result<u64, CarveError> {
+        let direction = self.direction();
+        let mut seam_energy = 0;
+        let mut removed = 0;
+        progress::report(observer, phase, direction, 0, distance)?;
+
+        while removed < distance {
             self.calculate_energy();
+            let seams = self.find_disjoint_paths(batch.min(distance - removed));
+            if seams.is_empty() {
+                return Err(CarveError::NoSeamInRoi);
+            }
+
+            // Each seam is found in the grid as it was before the batch, so it
+            // has to move left past the pixels already taken out of each row.
+            for i in 0..seams.len() {
+                let (earlier, rest) = seams.split_at(i);
+                let (path, cost) = &rest[0];
+                self.path = path
+                    .iter()
+                    .map(|&(x, y)| {
+                        let shift = earlier
+                            .iter()
+                            .filter(|(other, _)| other[other.len() - 1 - y].0 < x)
+                            .count();
+                        (x - shift, y)
+                    })
+                    .collect();
+                self.remove_path();
+
+                seam_energy += u64::from(*cost);
+                removed += 1;
+                self.seam_counts.add(direction, SeamKind::Removed, 1);
+                progress::report(observer, phase, direction, removed, distance)?;
+            }
+        }
+
+        self.track_memory(0);
+        Ok(seam_energy)
+    }
+
+    /// Greedily finds up to `count` seams that share no pixels, starting from
+    /// the cheapest ends in the bottom row. Each seam is returned bottom to
+    /// top with the sum of its energy. The cheapest seam is always found.
+    fn find_disjoint_paths(&self, count: usize) -> Vec<(Vec<(usize, usize)>, u32)> {
+        let width = self.grid.width();
+        let height = self.grid.height();
+        let bottom = height - 1;
+
+        // Neighbouring ends tend to lead up the same valley, so only local
+        // minima are tried.
+        let cost = |x: usize| self.grid.get(x, bottom).path_cost;
+        let mut starts: Vec<usize> = (0..width)
+            .filter(|&x| {
+                cost(x) != UNREACHABLE
+                    && (x == 0 || cost(x - 1) > cost(x))
+                    && (x == width - 1 || cost(x + 1) >= cost(x))
+            })
+            .collect();
+        starts.sort_by_key(|&x| cost(x));
+
+        let mut used = vec![false; width * height];
+        let mut seams: Vec<(Vec<(usize, usize)>, u32)> = vec![];
+        for start in starts {
+            if seams.len() == count {
+                break;
+            }
+            let seam = match self.find_unused_path(start, &used) {
+                Some(seam) => seam,
+                None => continue,
+            };
+            // Seams far worse than the best are left for the next batch.
+            if let Some((_, best)) = seams.first() {
+                if seam.1 > best.saturating_mul(MAX_DISJOINT_COST_RATIO) {
+                    break;
+                }
+            }
+            for &(x, y) in &seam.0 {
+                used[y * width + x] = true;
+            }
+            seams.push(seam);
+        }
+        seams
+    }
+
+    /// Follows the cheapest parents up from `(start_x, bottom)` without
+    /// touching any pixel marked in `used`.
+    fn find_unused_path(
+        &self,
+        start_x: usize,
+        used: &[bool],
+    ) -> Option<(Vec<(usize, usize)>, u32)> {
+        let width = self.grid.width();
+        let bottom = self.grid.height() - 1;
+        if used[bottom * width + start_x] {
+            return None;
+        }
+
+        let mut path = vec![(start_x, bottom)];
+        let mut cost = self.grid.get(start_x, bottom).energy;
+        loop {
+            let (x, y) = path[path.len() - 1];
+            if y == 0 {
+                return Some((path, cost));
+            }
+            let ((parent_x, parent_y), _) = self
+                .iter_parent_costs(x, y)
+                .filter(|&((parent_x, parent_y), _)| !used[parent_y * width + parent_x])
+                .min_by_key(|&(_, cost)| cost)?;
+            cost = cost
+                .saturating_add(self.seam_constraint.step_cost(x, parent_x))
+                .saturating_add(self.grid.get(parent_x, parent_y).energy);
+            path.push((parent_x, parent_y));
+        }
+    }
+
+    /// Like `shrink_distance`, but each seam is only searched for near a seam
+    /// found on a downscaled proxy of the grid.
+    fn shrink_distance_guided(
+        &mut self,
+        distance: usize,
+        observer: &mut dyn Observer,
+        phase: Phase,
+        factor: usize,
+        band: usize,
+    ) -> Result<u64, CarveError> {
+        let direction = self.direction();
+        let mut proxy = self.proxy(factor);
+        let mut seam_energy = 0;
+        let mut removed = 0;
+        progress::report(observer, phase, direction, 0, distance)?;
+
+        while removed < distance {
+            let guide = if proxy.grid.width() > 1 {
+                // The proxy keeps the energy it was given rather than
+                // recalculating it from its blurred pixels.
+                proxy.dirty_points.clear();
+                proxy.remove_min_seam()?;
+                Some(proxy.upsample_path(factor, self.grid.width(), self.grid.height()))
+            } else {
+                None
+            };
+
+            for _ in 0..factor.min(distance - removed) {
+                self.update_dirty_energy();
+                let band_cost = guide
+                    .as_ref()
+                    .and_then(|guide| self.find_path_in_band(guide, band));
+                let cost = match band_cost {
+                    Some(cost) => {
+                        self.remove_path();
+                        cost
+                    }
+                    // The band can be cut off entirely by protected pixels.
+                    None => self.remove_min_seam()?,
+                };
+
+                seam_energy += u64::from(cost);
+                removed += 1;
+                self.seam_counts.add(direction, SeamKind::Removed, 1);
+                progress::report(observer, phase, direction, removed, distance)?;
+            }
+        }
+
+        self.track_memory(proxy.memory_footprint());
+        Ok(seam_energy)
+    }
+
+    /// A carver for a copy of the grid downscaled by `factor`, looking in the
+    /// same direction. Each proxy pixel takes the average color and energy of
+    /// the pixels it covers, so fine detail still steers seams away.
+    fn proxy(&self, factor: usize) -> Carver {
+        let (width, height) = self.grid.dimensions();
+        let proxy_width = width.div_ceil(factor);
+        let proxy_height = height.div_ceil(factor);
+
+        let mut pixels = vec![[0u32; 4]; proxy_width * proxy_height];
+        let mut energies = vec![0u64; proxy_width * proxy_height];
+        let mut counts = vec![0u32; proxy_width * proxy_height];
+        for (x, y, pep) in self.grid.coord_iter() {
+            let index = (y / factor) * proxy_width + x / factor;
+            for (sum, &channel) in pixels[index].iter_mut().zip(pep.pixel.0.iter()) {
+                *sum += u32::from(channel);
+            }
+            energies[index] += u64::from(pep.energy);
+            counts[index] += 1;
+        }
+
+        let image = RgbaImage::from_fn(proxy_width as u32, proxy_height as u32, |x, y| {
+            let index = y as usize * proxy_width + x as usize;
+            let mut pixel = [0; 4];
+            for (channel, &sum) in pixel.iter_mut().zip(pixels[index].iter()) {
+                *channel = (sum / counts[index]) as u8;
+            }
+            Rgba(pixel)
+        });
+
+        let options = CarverOptions {
+            energy_function: self.energy_function,
+            boundary: self.boundary,
+            seam_constraint: self.seam_constraint,
+            ..CarverOptions::default()
+        };
+        let mut proxy = Carver::with_options(&DynamicImage::ImageRgba8(image), options)
+            .expect("proxy options are always valid");
+        for (x, y, pep) in proxy.grid.coord_iter_mut() {
+            let index = y * proxy_width + x;
+            pep.energy = (energies[index] / u64::from(counts[index])) as u32;
+        }
+        proxy.grid.set_direction(self.direction());
+        proxy
+    }
+
+    /// Scales the last path found up by `factor` to a guide with one column
+    /// per row of a `width` by `height` view, interpolating between rows.
+    fn upsample_path(&self, factor: usize, width: usize, height: usize) -> Vec<usize> {
+        let proxy_height = self.grid.height();
+        let mut proxy_x = vec![0; proxy_height];
+        for &(x, y) in &self.path {
+            proxy_x[y] = x;
+        }
+
+        let factor = factor as f64;
+        let last_row = (proxy_height - 1) as f64;
+        (0..height)
+            .map(|y| {
+                let t = ((y as f64 + 0.5) / factor - 0.5).clamp(0.0, last_row);
+                let row = t.floor() as usize;
+                let next = (row + 1).min(proxy_height - 1);
+                let fraction = t - row as f64;
+                let x = proxy_x[row] as f64 * (1.0 - fraction) + proxy_x[next] as f64 * fraction;
+                let x = ((x + 0.5) * factor - 0.5).round().max(0.0) as usize;
+                x.min(width - 1)
+            })
+            .collect()
+    }
+
+    /// Finds the cheapest seam that stays within `band` columns of `guide`,
+    /// leaving it in `self.path` and returning its cost. Returns `None` if no
+    /// seam fits in the band.
+    fn find_path_in_band(&mut self, guide: &[usize], band: usize) -> Option<u32> {
+        let width = self.grid.width();
+        let height = self.grid.height();
+        let constraint = self.seam_constraint;
+        let ranges: Vec<(usize, usize)> = guide
+            .iter()
+            .map(|&x| {
+                let x = x.min(width - 1);
+                (x.saturating_sub(band), (x + band).min(width - 1))
+            })
+            .collect();
+
+        // The path cost of every pixel in the band and the column of its
+        // parent, or `None` where no seam in the band can reach it.
+        let mut rows: Vec<Vec<Option<(u32, usize)>>> = Vec::with_capacity(height);
+        for (y, &(first, last)) in ranges.iter().enumerate() {
+            let mut row = Vec::with_capacity(last - first + 1);
+            for x in first..=last {
+                let pep = self.grid.get(x, y);
+                let energy = pep.energy;
+                let cell = if !self.attributes.carvable(pep.original_index) {
+                    None
+                } else if y == 0 {
+                    Some((energy, x))
+                } else {
+                    let (parent_first, parent_last) = ranges[y - 1];
+                    let parents = &rows[y - 1];
+                    (x.saturating_sub(constraint.window).max(parent_first)
+                        ..=(x + constraint.window).min(parent_last))
+                        .filter_map(|parent_x| {
+                            parents[parent_x - parent_first].map(|(cost, _)| {
+                                (
+                                    cost.saturating_add(constraint.step_cost(x, parent_x)),
+                                    parent_x,
+                                )
+                            })
+                        })
+                        .min_by_key(|&(cost, _)| cost)
+                        .map(|(cost, parent_x)| (cost.saturating_add(energy), parent_x))
+                };
+                row.push(cell);
+            }
+            rows.push(row);
+        }
+
+        let (start, cost) = rows[height - 1]
+            .iter()
+            .enumerate()
+            .filter_map(|(offset, cell)| cell.map(|(cost, _)| (offset, cost)))
+            .min_by_key(|&(_, cost)| cost)?;
+
+        self.path.clear();
+        let mut x = ranges[height - 1].0 + start;
+        for y in (0..height).rev() {
+            self.path.push((x, y));
+            let (_, parent_x) = rows[y][x - ranges[y].0].expect("the path stays reachable");
+            x = parent_x;
+        }
+        Some(cost)
+    }
+
+    /// Removes `distance` columns by carving or cropping, depending on the
+    /// mode.
+    fn shrink_pass(
+        &mut self,
+        distance: usize,
+        observer: &mut dyn Observer,
+        pass: &mut PassStats,
+    ) -> Result<(), CarveError> {
+        if self.symmetry == Some(self.direction()) {
+            pass.seam_energy = self.shrink_distance_symmetric(distance, observer)?;
+            return Ok(());
+        }
+        if self.mode == ResizeMode::Carve || self.restricted {
+            pass.seam_energy = self.shrink_distance(distance, observer, Phase::Carve)?;
+            return Ok(());
+        }
+
+        let window = crop::find_crop_window(
+            &crop::column_energy(&self.grid),
+            self.grid.width() - distance,
+        );
+
+        if self.mode == ResizeMode::Auto {
+            // Carving is tried on a copy, which is kept if it removes less
+            // energy than the crop.
+            let mut carved = self.clone();
+            let seam_energy = carved.shrink_distance(distance, observer, Phase::Carve)?;
+            self.track_memory(carved.memory_footprint());
+            if seam_energy <= window.removed_energy {
+                *self = carved;
+                pass.seam_energy = seam_energy;
+                return Ok(());
+            }
+        }
+
+        self.crop(window);
+        pass.seam_energy = window.removed_energy;
+        pass.cropped = true;
+        progress::report(observer, Phase::Carve, self.direction(), distance, distance)?;
+        Ok(())
+    }
+
+    /// Removes `distance` seams in mirrored pairs about the symmetry axis,
+    /// returning the sum of their path costs.
+    fn shrink_distance_symmetric(
+        &mut self,
+        distance: usize,
+        observer: &mut dyn Observer,
+    ) -> Result<u64, CarveError> {
+        let direction = self.direction();
+        let mut sum = match self.mirror_sum {
+            Some(sum) => sum,
+            None => self.detect_mirror_sum(),
+        };
+        let mut seam_energy = 0;
+        let mut removed = 0;
+        progress::report(observer, Phase::Carve, direction, 0, distance)?;
+
+        while distance - removed >= 2 {
+            self.pairing = Some(sum);
+            self.calculate_energy();
+            self.pairing = None;
             let (start_x, start_y) = self.get_path_start();
+            let cost = self.grid.get(start_x, start_y).path_cost;
+            if cost == UNREACHABLE {
+                break;
+            }
+
+            // The mirrored seam goes first, so the pixels of the seam left of
+            // the axis stay where they are.
             self.find_path(start_x, start_y);
+            let path = mem::take(&mut self.path);
+            self.path = path.iter().map(|&(x, y)| (sum - x, y)).collect();
             self.remove_path();
+            self.path = path;
+            self.remove_path();
+
+            // Every pixel right of both seams moved two pixels closer.
+            sum -= 2;
+            seam_energy += u64::from(cost);
+            removed += 2;
+            self.seam_counts.add(direction, SeamKind::Removed, 2);
+            progress::report(observer, Phase::Carve, direction, removed, distance)?;
+        }
+
+        let axis = sum / 2;
+        let on_column = sum % 2 == 0 && axis < self.grid.width();
+        if distance - removed == 1
+            && on_column
+            && (0..self.grid.height()).all(|y| {
+                self.attributes
+                    .carvable(self.grid.get(axis, y).original_index)
+            })
+        {
+            self.update_dirty_energy();
+            self.path = (0..self.grid.height()).rev().map(|y| (axis, y)).collect();
+            seam_energy += self
+                .path
+                .iter()
+                .map(|&(x, y)| u64::from(self.grid.get(x, y).energy))
+                .sum::<u64>();
+            self.remove_path();
+            sum -= 1;
+            removed += 1;
+            self.seam_counts.add(direction, SeamKind::Removed, 1);
+            progress::report(observer, Phase::Carve, direction, removed, distance)?;
+        }
+
+        while removed < distance {
+            seam_energy += u64::from(self.remove_min_seam()?);
+            removed += 1;
+            self.seam_counts.add(direction, SeamKind::Removed, 1);
+            progress::report(observer, Phase::Carve, direction, removed, distance)?;
         }
+
+        if self.mirror_sum.is_some() {
+            self.mirror_sum = Some(sum);
+        }
+        self.track_memory(0);
+        Ok(seam_energy)
     }
 
-    fn grow_distance(&mut self, distance: usize) {
-        let points = self.get_points_removed_by_shrink(distance);
+    /// The sum of the positions of mirrored pixels about the axis the view
+    /// is most symmetric about. Only axes that leave at least half of each
+    /// row with a mirror image are tried, and ties go to the axis nearest the
+    /// middle.
+    fn detect_mirror_sum(&self) -> usize {
+        let width = self.grid.width();
+        let middle = width - 1;
+
+        (0..=2 * middle)
+            .filter(|&sum| 2 * (sum.min(2 * middle - sum) + 1) >= width)
+            .map(|sum| (sum, self.mirror_score(sum)))
+            .min_by(|&(a, score_a), &(b, score_b)| {
+                score_a
+                    .total_cmp(&score_b)
+                    .then(a.abs_diff(middle).cmp(&b.abs_diff(middle)))
+            })
+            .map_or(middle, |(sum, _)| sum)
+    }
 
-        for _ in 0..distance {
-            self.grid.add_last_column();
+    /// The mean difference between mirrored pixels about `sum`.
+    fn mirror_score(&self, sum: usize) -> f64 {
+        let width = self.grid.width();
+        let first = sum.saturating_sub(width - 1);
+        let mut total = 0u64;
+        let mut count = 0u64;
+        for y in 0..self.grid.height() {
+            for x in first..sum.div_ceil(2) {
+                let (a, b) = (self.grid.get(x, y), self.grid.get(sum - x, y));
+                total += u64::from(a.square_gradient(b));
+                count += 1;
+            }
         }
+        if count == 0 {
+            0.0
+        } else {
+            total as f64 / count as f64
+        }
+    }
 
-        for (x, y) in points {
-            let pep = {
-                let left = self.grid.get(x, y);
-                let right = self.grid.get(x + 1, y);
-                left.average(right)
-            };
-            self.add_point(x, y, pep)
+    /// Crops the view to `window`, logging every discarded column as a seam.
+    fn crop(&mut self, window: CropWindow) {
+        let direction = self.direction();
+        let end = window.start + window.len;
+        let discarded: Vec<usize> = (0..window.start).chain(end..self.grid.width()).collect();
+
+        for &x in &discarded {
+            for y in 0..self.grid.height() {
+                let index = self.grid.get(x, y).original_index;
+                self.log_seam_point(index, SeamKind::Removed);
+            }
         }
+        self.seam_counts
+            .add(direction, SeamKind::Removed, discarded.len());
+
+        self.grid.crop_columns(window.start, window.len);
+        for layer in &mut self.layers {
+            layer.crop_columns(window.start, window.len);
+        }
+        self.dirty_points.clear();
+        self.track_memory(0);
     }
 
-    fn calculate_energy(&mut self) {
-        let mut dirty_points = vec![];
-        mem::swap(&mut dirty_points, &mut self.dirty_points);
+    fn grow_distance(
+        &mut self,
+        distance: usize,
+        observer: &mut dyn Observer,
+        pass: &mut PassStats,
+    ) -> Result<(), CarveError> {
+        let direction = self.direction();
+        let mut inserted = 0;
 
-        for token in dirty_points {
-            self.calculate_pixel_energy_from_token(token);
+        // Each pixel can only have one seam inserted after it per round, so
+        // growing by more than the width takes several rounds.
+        while inserted < distance {
+            let round = (distance - inserted).min(self.carvable_width());
+            if round == 0 {
+                return Err(CarveError::NoSeamInRoi);
+            }
+
+            let precompute_start = Instant::now();
+            let (points, seam_energy) = self.get_points_removed_by_shrink(round, observer)?;
+            pass.grow_precompute += precompute_start.elapsed();
+            pass.seam_energy += seam_energy;
+
+            let carve_start = Instant::now();
+            if inserted == 0 {
+                progress::report(observer, Phase::Carve, direction, 0, distance)?;
+            }
+
+            // Every removed seam took one pixel from each row. Inserting from
+            // the right of each row first keeps the earlier positions valid.
+            let mut rows = vec![vec![]; self.grid.height()];
+            for (x, y) in points {
+                rows[y].push(x);
+            }
+            for row in &mut rows {
+                row.sort_unstable_by_key(|&x| Reverse(x));
+            }
+
+            self.grid.reserve_columns(round);
+            self.track_memory(0);
+            for i in 0..round {
+                let seam: Vec<usize> = rows.iter().map(|row| row[i]).collect();
+                self.insert_seam_after(&seam);
+                self.seam_counts.add(direction, SeamKind::Inserted, 1);
+                inserted += 1;
+                pass.seams_inserted = inserted;
+                progress::report(observer, Phase::Carve, direction, inserted, distance)?;
+            }
+            pass.carve += carve_start.elapsed();
         }
+        self.track_memory(0);
+        Ok(())
+    }
+
+    fn calculate_energy(&mut self) {
+        self.update_dirty_energy();
 
         for y in 0..self.grid.height() {
             for x in 0..self.grid.width() {
@@ -101,7 +1409,21 @@ impl Carver {
         }
     }
 
+    fn update_dirty_energy(&mut self) {
+        let mut dirty_points = vec![];
+        mem::swap(&mut dirty_points, &mut self.dirty_points);
+
+        for token in dirty_points {
+            self.calculate_pixel_energy_from_token(token);
+        }
+    }
+
     fn calculate_all_pixel_energy(&mut self) {
+        if self.threads > 1 {
+            self.calculate_all_pixel_energy_parallel();
+            return;
+        }
+
         for y in 0..self.grid.height() {
             for x in 0..self.grid.width() {
                 self.calculate_pixel_energy(x, y);
@@ -109,21 +1431,100 @@ impl Carver {
         }
     }
 
+    // The grid itself can't be shared across threads because of its tokens,
+    // so the pixels are copied out and the energies written back afterwards.
+    fn calculate_all_pixel_energy_parallel(&mut self) {
+        // The energy is the same whichever way the seams run, so this works in
+        // image coordinates to match the order of `iter`.
+        let (width, height) = self.grid.dimensions();
+        let pixels: Vec<Rgba<u8>> = self.grid.iter().map(|pep| pep.pixel).collect();
+        let energy_function = self.energy_function;
+        let boundary = self.boundary;
+
+        let mut energies = vec![0; width * height];
+        let chunk_len = height.div_ceil(self.threads) * width;
+        thread::scope(|scope| {
+            for (i, chunk) in energies.chunks_mut(chunk_len).enumerate() {
+                let pixels = &pixels;
+                scope.spawn(move || {
+                    for (offset, energy) in chunk.iter_mut().enumerate() {
+                        let index = i * chunk_len + offset;
+                        let (x, y) = (index % width, index / width);
+                        let (x_left, x_right) = boundary.neighbors(x, width);
+                        let (y_up, y_down) = boundary.neighbors(y, height);
+                        let left = pixels[y * width + x_left];
+                        let right = pixels[y * width + x_right];
+                        let up = pixels[y_up * width + x];
+                        let down = pixels[y_down * width + x];
+                        *energy = energy_function.gradient(&left, &right)
+                            + energy_function.gradient(&up, &down)
+                            + boundary.border_energy(x, y, width, height);
+                    }
+                });
+            }
+        });
+
+        let attributes = &self.attributes;
+        for (pep, energy) in self.grid.iter_mut().zip(energies) {
+            pep.energy = attributes.energy(pep.original_index, energy);
+        }
+    }
+
+    fn calculate_all_pixel_energy_observed(
+        &mut self,
+        observer: &mut dyn Observer,
+    ) -> Result<(), CarveError> {
+        let direction = self.direction();
+        progress::report(observer, Phase::Energy, direction, 0, 1)?;
+        self.calculate_all_pixel_energy();
+        progress::report(observer, Phase::Energy, direction, 1, 1)?;
+        Ok(())
+    }
+
     fn calculate_pixel_energy(&mut self, x: usize, y: usize) {
         let energy = {
-            let (left, right, up, down) = self.grid.get_adjacent(x, y);
-            let horizontal_square_gradient = left.square_gradient(right);
-            let vertical_square_gradient = up.square_gradient(down);
-            horizontal_square_gradient + vertical_square_gradient
+            let (left, right, up, down) = self.grid.get_adjacent_within(x, y, self.boundary);
+            self.energy_function.energy(left, right, up, down)
         };
+        let (width, height) = (self.grid.width(), self.grid.height());
+        let energy = energy + self.boundary.border_energy(x, y, width, height);
 
-        self.grid.get_mut(x, y).energy = energy;
+        let pep = self.grid.get_mut(x, y);
+        pep.energy = self.attributes.energy(pep.original_index, energy);
     }
 
     fn calculate_path_cost(&mut self, x: usize, y: usize) {
-        let min_parent_path_cost = self.get_min_parent_path_cost(x, y);
-        let energy = self.grid.get(x, y).energy;
-        self.grid.get_mut(x, y).path_cost = min_parent_path_cost + energy;
+        let path_cost = match self.seam_energy(x, y) {
+            None => UNREACHABLE,
+            Some(energy) if y == 0 => energy.min(UNREACHABLE - 1),
+            Some(energy) => match self.get_min_parent_path_cost(x, y) {
+                Some(cost) => cost.saturating_add(energy).min(UNREACHABLE - 1),
+                None => UNREACHABLE,
+            },
+        };
+        self.grid.get_mut(x, y).path_cost = path_cost;
+    }
+
+    /// The energy a seam pays to pass through `(x, y)`, or `None` if it may
+    /// not. While pairing, only pixels left of the axis with a mirror image
+    /// are open, and they cost as much as both pixels together.
+    fn seam_energy(&self, x: usize, y: usize) -> Option<u32> {
+        let pep = self.grid.get(x, y);
+        if !self.attributes.carvable(pep.original_index) {
+            return None;
+        }
+        match self.pairing {
+            None => Some(pep.energy),
+            Some(sum) if 2 * x < sum && sum - x < self.grid.width() => {
+                let mirror = self.grid.get(sum - x, y);
+                if self.attributes.carvable(mirror.original_index) {
+                    Some(pep.energy.saturating_add(mirror.energy))
+                } else {
+                    None
+                }
+            }
+            Some(_) => None,
+        }
     }
 
     fn get_path_start(&self) -> (usize, usize) {
@@ -150,85 +1551,328 @@ impl Carver {
         }
     }
 
-    fn get_points_removed_by_shrink(&self, distance: usize) -> Vec<(usize, usize)> {
+    fn get_points_removed_by_shrink(
+        &mut self,
+        distance: usize,
+        observer: &mut dyn Observer,
+    ) -> Result<(Vec<(usize, usize)>, u64), CarveError> {
+        if self.low_memory {
+            return self.get_points_removed_in_place(distance, observer);
+        }
         let mut shrinker = self.clone();
 
+        // Only the positions of the removed pixels are needed, which are
+        // read back from the log.
+        shrinker.layers.clear();
         shrinker.removed_points.clear();
+        shrinker.seam_log = true;
         shrinker.reset_positions();
 
-        shrinker.shrink_distance(distance);
-        let mut points = shrinker.get_removed_points();
+        // The grid can't be carved away entirely, so when every column is
+        // wanted the last one is taken as it stands.
+        let whole_grid = distance == self.grid.width();
+        let mut seam_energy = shrinker.shrink_distance(
+            distance - usize::from(whole_grid),
+            observer,
+            Phase::GrowPrecompute,
+        )?;
+        if whole_grid {
+            let last: Vec<_> = shrinker.grid.iter().cloned().collect();
+            for pep in last {
+                seam_energy += u64::from(pep.energy);
+                shrinker.log_seam_point(pep.original_index, SeamKind::Removed);
+            }
+        }
+        self.track_memory(shrinker.memory_footprint());
+        // The positions are in image coordinates but seams are inserted
+        // through the grid's view.
+        let direction = self.direction();
+        let points = shrinker
+            .get_removed_points()
+            .into_iter()
+            .map(|(x, y)| match direction {
+                Direction::Vertical => (x, y),
+                Direction::Horizontal => (y, x),
+            })
+            .collect();
+
+        Ok((points, seam_energy))
+    }
+
+    /// Like `get_points_removed_by_shrink`, but carves the grid itself and
+    /// puts every seam back afterwards, so no copy of the grid is needed.
+    fn get_points_removed_in_place(
+        &mut self,
+        distance: usize,
+        observer: &mut dyn Observer,
+    ) -> Result<(Vec<(usize, usize)>, u64), CarveError> {
+        // Removing seams changes the energy of the pixels beside them, so it's
+        // restored along with the positions.
+        let energies: Vec<u32> = self.grid.iter().map(|pep| pep.energy).collect();
+        let (index_width, seam_counts) = (self.index_width, self.seam_counts);
+        self.reset_positions();
+        self.undo = Some(vec![]);
+
+        let whole_grid = distance == self.grid.width();
+        let shrunk = self.shrink_distance(
+            distance - usize::from(whole_grid),
+            observer,
+            Phase::GrowPrecompute,
+        );
+        let undo = self.undo.take().expect("undo is set while planning");
 
-        // Reverse sort by x values
-        points.sort_by(|a, b| b.0.cmp(&a.0));
+        let mut seam_energy = 0;
+        let mut indices = Vec::with_capacity(distance * self.grid.height());
+        indices.extend(
+            undo.iter()
+                .flat_map(|(_, removed)| removed.iter().map(|pep| pep.original_index)),
+        );
+        if whole_grid {
+            for pep in self.grid.iter() {
+                seam_energy += u64::from(pep.energy);
+                indices.push(pep.original_index);
+            }
+        }
+        let undo_bytes = undo
+            .iter()
+            .map(|(seam, removed)| {
+                seam.capacity() * mem::size_of::<usize>()
+                    + removed.capacity() * mem::size_of::<PixelEnergyPoint>()
+            })
+            .sum::<usize>();
+        self.track_memory(
+            undo_bytes
+                + energies.capacity() * mem::size_of::<u32>()
+                + indices.capacity() * mem::size_of::<u32>(),
+        );
 
-        points
+        // Everything is put back before the positions are worked out, so the
+        // planned seams and the positions aren't held at once.
+        for (seam, removed) in undo.into_iter().rev() {
+            self.grid.insert_seam(&seam, removed);
+        }
+        let sources = self
+            .attributes
+            .sources
+            .take()
+            .expect("sources are set while planning");
+        for (pep, &energy) in self.grid.iter_mut().zip(&energies) {
+            pep.original_index = sources[pep.original_index as usize];
+            pep.energy = energy;
+        }
+        drop(sources);
+        drop(energies);
+
+        let direction = self.direction();
+        let points = indices
+            .into_iter()
+            .map(|index| match self.original_position(index) {
+                (x, y) if direction == Direction::Vertical => (x, y),
+                (x, y) => (y, x),
+            })
+            .collect();
+        self.index_width = index_width;
+        self.seam_counts = seam_counts;
+        self.dirty_points.clear();
+
+        let seam_energy = seam_energy + shrunk?;
+        Ok((points, seam_energy))
     }
 
+    /// Numbers the points by where they are now, so the seams planned can be
+    /// traced back to the grid as it stands. Their attributes are still found
+    /// through the `original_index` they had before.
     fn reset_positions(&mut self) {
-        let is_rotated = self.grid.is_rotated();
+        let (width, height) = self.grid.dimensions();
+        let mut sources = vec![0; width * height];
+        let attributes = &self.attributes;
         for (x, y, pep) in self.grid.coord_iter_mut() {
-            pep.original_position = if !is_rotated { (x, y) } else { (y, x) }
+            let index = y * width + x;
+            sources[index] = attributes.source(pep.original_index);
+            pep.original_index = index as u32;
         }
+        self.attributes.sources = Some(sources);
+        self.index_width = width;
     }
 
-    fn calculate_pixel_energy_from_token(&mut self, token: Token) {
-        let energy = match self.grid.get_token_adjacent(&token) {
-            None => return,
-            Some((left, right, up, down)) => {
-                let horizontal_square_gradient = left.square_gradient(right);
-                let vertical_square_gradient = up.square_gradient(down);
-                horizontal_square_gradient + vertical_square_gradient
-            }
-        };
+    /// The image coordinates of an `original_index`.
+    fn original_position(&self, index: u32) -> (usize, usize) {
+        let index = index as usize;
+        (index % self.index_width, index / self.index_width)
+    }
 
-        self.grid
-            .trade_mut(token)
-            .expect("token should still be valid")
-            .energy = energy;
+    fn calculate_pixel_energy_from_token(&mut self, token: Token) {
+        if let Some((x, y)) = self.grid.get_token_point(&token) {
+            self.calculate_pixel_energy(x, y);
+        }
     }
 
-    fn get_min_parent_path_cost(&self, x: usize, y: usize) -> u32 {
-        self.grid
-            .iter_parents(x, y)
-            .map(|pep| pep.path_cost)
-            .min()
-            .unwrap_or(0)
+    fn get_min_parent_path_cost(&self, x: usize, y: usize) -> Option<u32> {
+        self.iter_parent_costs(x, y).map(|(_, cost)| cost).min()
     }
 
     fn get_parent_with_min_path_cost(&self, x: usize, y: usize) -> Option<(usize, usize)> {
+        self.iter_parent_costs(x, y)
+            .min_by_key(|&(_, cost)| cost)
+            .map(|(parent, _)| parent)
+    }
+
+    /// The parents a seam through `(x, y)` may come from, with the cost of
+    /// reaching each of them. Parents no seam can reach are skipped.
+    fn iter_parent_costs(
+        &self,
+        x: usize,
+        y: usize,
+    ) -> impl Iterator<Item = ((usize, usize), u32)> + '_ {
+        let constraint = self.seam_constraint;
         self.grid
-            .iter_parents_with_coords(x, y)
-            .min_by_key(|&(_, _, pep)| pep.path_cost)
-            .map(|(x, y, _)| (x, y))
+            .iter_parents_with_coords_within(x, y, constraint.window)
+            .filter(|(_, _, pep)| pep.path_cost != UNREACHABLE)
+            .map(move |(parent_x, parent_y, pep)| {
+                let cost = pep
+                    .path_cost
+                    .saturating_add(constraint.step_cost(x, parent_x));
+                ((parent_x, parent_y), cost)
+            })
     }
 
-    fn add_point(&mut self, x: usize, y: usize, pep: PixelEnergyPoint) {
-        self.removed_points
-            .push(self.grid.get(x, y).original_position);
-        self.grid.shift_row_right_from_point(x, y);
-        *self.grid.get_mut(x + 1, y) = pep;
+    /// Inserts a new pixel after `seam[y]` in every row `y`.
+    fn insert_seam_after(&mut self, seam: &[usize]) {
+        let width = self.grid.width();
+        let values = seam
+            .iter()
+            .enumerate()
+            .map(|(y, &x)| {
+                let left = self.grid.get(x, y);
+                let right = self.grid.get((x + 1).min(width - 1), y);
+                match self.interpolation {
+                    Interpolation::Average => left.average(right),
+                    Interpolation::Duplicate => left.clone(),
+                }
+            })
+            .collect();
+
+        for (y, &x) in seam.iter().enumerate() {
+            let index = self.grid.get(x, y).original_index;
+            self.log_seam_point(index, SeamKind::Inserted);
+        }
+
+        for layer in &mut self.layers {
+            layer.insert_seam_after(seam);
+        }
+
+        let after: Vec<usize> = seam.iter().map(|&x| x + 1).collect();
+        self.grid.insert_seam(&after, values);
     }
 
     fn remove_path(&mut self) {
+        let mut seam = vec![0; self.grid.height()];
         for &(x, y) in &self.path {
+            seam[y] = x;
             let adjacent_tokens = self.grid.make_adjacent_tokens(x, y);
             self.dirty_points.extend_from_slice(&adjacent_tokens);
+        }
+        // A seam stepping more than one column sideways also gives every
+        // pixel it steps past a new neighbour above or below. Wrapping makes
+        // the last row the neighbour of the first, however far apart the
+        // seam is there.
+        let last_row = seam.len() - 1;
+        let mut rows: Vec<(usize, usize)> = (1..=last_row).map(|y| (y - 1, y)).collect();
+        if self.boundary == Boundary::Wrap && last_row > 0 {
+            rows.push((last_row, 0));
+        }
+        for (above, below) in rows {
+            let (first, last) = (seam[above].min(seam[below]), seam[above].max(seam[below]));
+            if last - first > 1 {
+                for x in first..=last {
+                    let tokens = [
+                        self.grid.make_token(x, above),
+                        self.grid.make_token(x, below),
+                    ];
+                    self.dirty_points.extend_from_slice(&tokens);
+                }
+            }
+        }
+
+        let removed = self.grid.remove_seam(&seam);
+        if let Some(ref mut undo) = self.undo {
+            // The seam is only being planned and will be put back, so the
+            // layers and the log are left alone.
+            undo.push((seam, removed));
+            return;
+        }
+        for layer in &mut self.layers {
+            layer.remove_seam(&seam);
+        }
+        let path = mem::take(&mut self.path);
+        for &(_, y) in &path {
+            self.log_seam_point(removed[y].original_index, SeamKind::Removed);
+        }
+        self.path = path;
+    }
 
-            let original_position = self.grid.get(x, y).original_position;
-            self.removed_points.push(original_position);
-            self.grid.shift_row_left_from_point(x, y);
+    fn push_layer(&mut self, mut layer: Box<dyn CarvedLayer>) -> BoxResult<usize> {
+        if layer.dimensions() != self.grid.dimensions() {
+            return Err("Layers must be the same size as the image".into());
         }
-        self.grid.remove_last_column();
+        layer.set_direction(self.direction());
+        self.layers.push(layer);
+        self.track_memory(0);
+        Ok(self.layers.len() - 1)
     }
 
-    fn rebuild_image(&self) -> DynamicImage {
-        let mut image =
-            DynamicImage::new_rgba8(self.grid.width() as u32, self.grid.height() as u32);
-        for (x, y, pep) in self.grid.coord_iter() {
-            image.put_pixel(x as u32, y as u32, pep.pixel);
+    /// The fewest pixels seams may pass through in any row of the view.
+    fn carvable_width(&self) -> usize {
+        if !self.restricted {
+            return self.grid.width();
         }
-        image
+        (0..self.grid.height())
+            .map(|y| {
+                self.grid
+                    .get_row(y)
+                    .into_iter()
+                    .filter(|pep| self.attributes.carvable(pep.original_index))
+                    .count()
+            })
+            .min()
+            .unwrap_or(0)
+    }
+
+    fn direction(&self) -> Direction {
+        self.grid.direction()
+    }
+
+    fn log_seam_point(&mut self, index: u32, kind: SeamKind) {
+        if !self.seam_log {
+            return;
+        }
+        let (x, y) = self.original_position(index);
+        let direction = self.direction();
+        self.removed_points.push(SeamPoint {
+            x,
+            y,
+            direction,
+            kind,
+        });
+    }
+
+    fn memory_footprint(&self) -> usize {
+        self.grid.memory_footprint()
+            + self.attributes.memory_footprint()
+            + self
+                .layers
+                .iter()
+                .map(|layer| layer.memory_footprint())
+                .sum::<usize>()
+            + self.removed_points.capacity() * mem::size_of::<SeamPoint>()
+            + self.dirty_points.capacity() * mem::size_of::<Token>()
+            + self.path.capacity() * mem::size_of::<(usize, usize)>()
+    }
+
+    /// Records the current footprint plus `extra` bytes held elsewhere.
+    fn track_memory(&mut self, extra: usize) {
+        let current = self.memory_footprint() + extra;
+        self.peak_memory_bytes = self.peak_memory_bytes.max(current);
     }
 
     #[cfg(test)]
@@ -258,15 +1902,55 @@ impl Carver {
     }
 }
 
+// The carving phases are private, so the benchmarks in `benches/` reach them
+// through these. They're only built with the `bench` feature and aren't part
+// of the public API.
+#[cfg(feature = "bench")]
+impl Carver {
+    #[doc(hidden)]
+    pub fn bench_set_direction(&mut self, direction: Direction) {
+        self.grid.set_direction(direction);
+    }
+
+    #[doc(hidden)]
+    pub fn bench_calculate_all_pixel_energy(&mut self) {
+        self.calculate_all_pixel_energy();
+    }
+
+    #[doc(hidden)]
+    pub fn bench_calculate_energy(&mut self) {
+        self.calculate_energy();
+    }
+
+    #[doc(hidden)]
+    pub fn bench_find_path(&mut self) {
+        let (start_x, start_y) = self.get_path_start();
+        self.find_path(start_x, start_y);
+    }
+
+    #[doc(hidden)]
+    pub fn bench_remove_path(&mut self) {
+        self.remove_path();
+    }
+
+    #[doc(hidden)]
+    pub fn bench_grow_distance(&mut self, distance: usize) {
+        let mut observer = |_: &Progress| Control::Continue;
+        let mut pass = PassStats::new(self.direction());
+        self.grow_distance(distance, &mut observer, &mut pass)
+            .expect("grow without an observer can't be cancelled");
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::Carver;
+    use super::{Carver, CarverOptions, SeamConstraint};
     use image;
 
     macro_rules! setup_carver {
         ( $bytes:expr ) => {{
             let input = image::load_from_memory($bytes).unwrap();
-            let mut carver = Carver::new(&input);
+            let mut carver = Carver::new(&input).unwrap();
             carver.calculate_all_pixel_energy();
             carver.calculate_energy();
             carver
@@ -329,8 +2013,42 @@ mod tests {
         assert_eq!(get_medium_path(), carver.path);
     }
 
-    static SMALL: &'static [u8; 173] = include_bytes!("../tests/images/small_energy.png");
-    static MEDIUM: &'static [u8; 244] = include_bytes!("../tests/images/medium_energy.png");
+    #[test]
+    fn carver_seam_window_energy_test() {
+        let input = image::load_from_memory(INPUT).unwrap();
+
+        for &window in &[1, 3] {
+            let options = CarverOptions {
+                seam_constraint: SeamConstraint {
+                    window,
+                    ..SeamConstraint::default()
+                },
+                ..CarverOptions::default()
+            };
+            let mut carver = Carver::with_options(&input, options).unwrap();
+            carver.calculate_all_pixel_energy();
+
+            for _ in 0..10 {
+                carver.remove_min_seam().unwrap();
+            }
+            carver.update_dirty_energy();
+
+            // The energy updated around each seam matches energy calculated
+            // from scratch.
+            let mut recalculated = carver.clone();
+            recalculated.calculate_all_pixel_energy();
+            assert_eq!(
+                recalculated.get_pixel_energy(),
+                carver.get_pixel_energy(),
+                "window {}",
+                window
+            );
+        }
+    }
+
+    static SMALL: &[u8; 173] = include_bytes!("../tests/images/small_energy.png");
+    static MEDIUM: &[u8; 244] = include_bytes!("../tests/images/medium_energy.png");
+    static INPUT: &[u8; 7256] = include_bytes!("../tests/images/input.png");
 
     fn get_small_pixel_energy() -> Vec<Vec<u32>> {
         vec![
@@ -0,0 +1,540 @@
+use std::fs;
+use std::path::Path;
+
+use image::{DynamicImage, GrayImage};
+
+use crate::BoxResult;
+
+/// A coarse frontal face cascade in OpenCV's format, written by hand rather
+/// than trained, so it's only a rough heuristic. Its four stages check that
+/// the eyes are darker than the forehead and cheeks, the bridge of the nose
+/// is lighter than the eyes and the mouth is darker than the chin. A trained
+/// cascade from OpenCV finds faces far more reliably.
+static FRONTAL_FACE: &str = include_str!("../data/frontal_face.xml");
+
+/// A rectangle of an image, in pixels.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Settings for scanning an image with a cascade.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DetectOptions {
+    /// How much larger the window gets at each scale.
+    pub scale_factor: f64,
+    /// How many overlapping windows a detection needs besides its own to be
+    /// kept. Lone windows are usually false positives.
+    pub min_neighbors: usize,
+}
+
+impl Default for DetectOptions {
+    fn default() -> Self {
+        DetectOptions {
+            scale_factor: 1.25,
+            min_neighbors: 3,
+        }
+    }
+}
+
+/// A Viola-Jones cascade of boosted Haar feature stumps, as trained by
+/// OpenCV. Only upright features and single split weak classifiers are
+/// supported, which covers the stock frontal face cascades.
+#[derive(Clone, Debug)]
+pub struct Cascade {
+    width: u32,
+    height: u32,
+    stages: Vec<Stage>,
+    features: Vec<Feature>,
+}
+
+#[derive(Clone, Debug)]
+struct Stage {
+    threshold: f64,
+    stumps: Vec<Stump>,
+}
+
+#[derive(Clone, Debug)]
+struct Stump {
+    feature: usize,
+    threshold: f64,
+    left: f64,
+    right: f64,
+}
+
+/// A rectangle of a feature as `(x, y, width, height, weight)`.
+type WeightedRect = (u32, u32, u32, u32, f64);
+
+#[derive(Clone, Debug)]
+struct Feature {
+    rects: Vec<WeightedRect>,
+}
+
+impl Cascade {
+    /// The cascade bundled with rmr. See `FRONTAL_FACE` for its limits.
+    pub fn frontal_face() -> Self {
+        Self::parse(FRONTAL_FACE).expect("the bundled cascade is valid")
+    }
+
+    /// Reads a cascade saved by OpenCV, such as
+    /// `haarcascade_frontalface_default.xml`.
+    pub fn load<P: AsRef<Path>>(path: P) -> BoxResult<Self> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Parses a cascade in OpenCV's XML format.
+    pub fn parse(xml: &str) -> BoxResult<Self> {
+        let root = Element::parse(xml)?;
+        let cascade = root
+            .find("cascade")
+            .ok_or("Cascade is missing its <cascade> element")?;
+        if let Some(kind) = cascade.child("featureType") {
+            if kind.text.trim() != "HAAR" {
+                return Err(
+                    format!("Unsupported cascade feature type {}", kind.text.trim()).into(),
+                );
+            }
+        }
+
+        let width = cascade.number("width")?;
+        let height = cascade.number("height")?;
+        if width < 3 || height < 3 {
+            return Err("Cascade window must be at least 3 pixels wide and high".into());
+        }
+
+        let features = cascade
+            .items("features")?
+            .map(|feature| {
+                if feature
+                    .child("tilted")
+                    .is_some_and(|tilted| tilted.text.trim() != "0")
+                {
+                    return Err("Tilted cascade features aren't supported".into());
+                }
+                let rects = feature
+                    .items("rects")?
+                    .map(|rect| {
+                        let values = numbers(&rect.text)?;
+                        match values[..] {
+                            [x, y, w, h, weight]
+                                if x >= 0.0
+                                    && y >= 0.0
+                                    && w > 0.0
+                                    && h > 0.0
+                                    && x + w <= f64::from(width)
+                                    && y + h <= f64::from(height) =>
+                            {
+                                Ok((x as u32, y as u32, w as u32, h as u32, weight))
+                            }
+                            _ => {
+                                Err(format!("Invalid cascade rectangle: {}", rect.text.trim())
+                                    .into())
+                            }
+                        }
+                    })
+                    .collect::<BoxResult<Vec<_>>>()?;
+                if rects.is_empty() {
+                    return Err("Cascade feature has no rectangles".into());
+                }
+                Ok(Feature { rects })
+            })
+            .collect::<BoxResult<Vec<_>>>()?;
+
+        let stages = cascade
+            .items("stages")?
+            .map(|stage| {
+                let threshold = stage.number("stageThreshold")?;
+                let stumps = stage
+                    .items("weakClassifiers")?
+                    .map(|classifier| {
+                        let nodes = numbers(&classifier.text_of("internalNodes")?)?;
+                        let leaves = numbers(&classifier.text_of("leafValues")?)?;
+                        match (&nodes[..], &leaves[..]) {
+                            (&[_, _, feature, threshold], &[left, right])
+                                if feature >= 0.0 && (feature as usize) < features.len() =>
+                            {
+                                Ok(Stump {
+                                    feature: feature as usize,
+                                    threshold,
+                                    left,
+                                    right,
+                                })
+                            }
+                            _ => Err("Only single split cascade classifiers are supported".into()),
+                        }
+                    })
+                    .collect::<BoxResult<Vec<_>>>()?;
+                Ok(Stage { threshold, stumps })
+            })
+            .collect::<BoxResult<Vec<_>>>()?;
+        if stages.is_empty() {
+            return Err("Cascade has no stages".into());
+        }
+
+        Ok(Cascade {
+            width,
+            height,
+            stages,
+            features,
+        })
+    }
+
+    /// Scans `image` at every scale the window fits, returning the windows
+    /// every stage accepted after grouping overlapping ones.
+    pub fn detect(&self, image: &DynamicImage, options: &DetectOptions) -> Vec<Rect> {
+        let integral = Integral::new(&image.to_luma8());
+        let scale_factor = options.scale_factor.max(1.01);
+
+        let mut candidates = vec![];
+        let mut scale = 1.0;
+        loop {
+            let window_width = (f64::from(self.width) * scale).round() as u32;
+            let window_height = (f64::from(self.height) * scale).round() as u32;
+            if window_width > integral.width || window_height > integral.height {
+                break;
+            }
+
+            let scaled = self.scaled(scale, window_width, window_height);
+            // OpenCV steps two pixels of the scaled image until windows are
+            // twice the cascade's size, then one.
+            let step = if scale > 2.0 { scale } else { scale * 2.0 };
+            let step = step.round() as usize;
+            for y in (0..=integral.height - window_height).step_by(step) {
+                for x in (0..=integral.width - window_width).step_by(step) {
+                    if scaled.accepts(self, &integral, x, y) {
+                        candidates.push(Rect {
+                            x,
+                            y,
+                            width: window_width,
+                            height: window_height,
+                        });
+                    }
+                }
+            }
+            scale *= scale_factor;
+        }
+
+        group_rects(&candidates, options.min_neighbors)
+    }
+
+    /// The features scaled up to a `width` by `height` window, `scale` times
+    /// the cascade's size.
+    fn scaled(&self, scale: f64, width: u32, height: u32) -> ScaledCascade {
+        let scale_rect = |x: u32, y: u32, w: u32, h: u32| {
+            let scale = |value: u32| (f64::from(value) * scale).round() as u32;
+            let (x, y) = (scale(x).min(width - 1), scale(y).min(height - 1));
+            let w = scale(w).clamp(1, width - x);
+            let h = scale(h).clamp(1, height - y);
+            (x, y, w, h)
+        };
+
+        let features = self
+            .features
+            .iter()
+            .map(|feature| {
+                let mut rects: Vec<WeightedRect> = feature
+                    .rects
+                    .iter()
+                    .map(|&(x, y, w, h, weight)| {
+                        let (x, y, w, h) = scale_rect(x, y, w, h);
+                        (x, y, w, h, weight)
+                    })
+                    .collect();
+                // Rounding changes the areas, so the first rectangle is
+                // reweighted to keep a flat window scoring zero.
+                if rects.len() > 1 {
+                    let others: f64 = rects[1..]
+                        .iter()
+                        .map(|&(_, _, w, h, weight)| weight * f64::from(w * h))
+                        .sum();
+                    let (_, _, w, h, _) = rects[0];
+                    rects[0].4 = -others / f64::from(w * h);
+                }
+                rects
+            })
+            .collect();
+
+        // Variance is measured inside a one pixel border, as OpenCV does.
+        let (x, y, w, h) = scale_rect(1, 1, self.width - 2, self.height - 2);
+        ScaledCascade {
+            features,
+            norm_rect: (x, y, w, h),
+        }
+    }
+}
+
+struct ScaledCascade {
+    features: Vec<Vec<WeightedRect>>,
+    norm_rect: (u32, u32, u32, u32),
+}
+
+impl ScaledCascade {
+    fn accepts(&self, cascade: &Cascade, integral: &Integral, x: u32, y: u32) -> bool {
+        let (norm_x, norm_y, norm_w, norm_h) = self.norm_rect;
+        let area = f64::from(norm_w * norm_h);
+        let sum = integral.sum(x + norm_x, y + norm_y, norm_w, norm_h);
+        let squared = integral.squared_sum(x + norm_x, y + norm_y, norm_w, norm_h);
+        let mean = sum / area;
+        let variance = squared / area - mean * mean;
+        let deviation = if variance > 0.0 { variance.sqrt() } else { 1.0 };
+
+        cascade.stages.iter().all(|stage| {
+            let total: f64 = stage
+                .stumps
+                .iter()
+                .map(|stump| {
+                    let value: f64 = self.features[stump.feature]
+                        .iter()
+                        .map(|&(rx, ry, rw, rh, weight)| {
+                            weight * integral.sum(x + rx, y + ry, rw, rh)
+                        })
+                        .sum::<f64>()
+                        / area;
+                    if value < stump.threshold * deviation {
+                        stump.left
+                    } else {
+                        stump.right
+                    }
+                })
+                .sum();
+            total >= stage.threshold
+        })
+    }
+}
+
+/// Summed area tables of an image and of its squares.
+struct Integral {
+    width: u32,
+    height: u32,
+    sums: Vec<f64>,
+    squared_sums: Vec<f64>,
+}
+
+impl Integral {
+    fn new(luma: &GrayImage) -> Self {
+        let (width, height) = luma.dimensions();
+        let stride = width as usize + 1;
+        let mut sums = vec![0.0; stride * (height as usize + 1)];
+        let mut squared_sums = sums.clone();
+        for y in 0..height as usize {
+            let (mut row, mut squared_row) = (0.0, 0.0);
+            for x in 0..width as usize {
+                let value = f64::from(luma.get_pixel(x as u32, y as u32)[0]);
+                row += value;
+                squared_row += value * value;
+                sums[(y + 1) * stride + x + 1] = sums[y * stride + x + 1] + row;
+                squared_sums[(y + 1) * stride + x + 1] =
+                    squared_sums[y * stride + x + 1] + squared_row;
+            }
+        }
+        Integral {
+            width,
+            height,
+            sums,
+            squared_sums,
+        }
+    }
+
+    fn sum(&self, x: u32, y: u32, width: u32, height: u32) -> f64 {
+        Self::rect(&self.sums, self.width, x, y, width, height)
+    }
+
+    fn squared_sum(&self, x: u32, y: u32, width: u32, height: u32) -> f64 {
+        Self::rect(&self.squared_sums, self.width, x, y, width, height)
+    }
+
+    fn rect(table: &[f64], image_width: u32, x: u32, y: u32, width: u32, height: u32) -> f64 {
+        let stride = image_width as usize + 1;
+        let (left, top) = (x as usize, y as usize);
+        let (right, bottom) = (left + width as usize, top + height as usize);
+        table[bottom * stride + right] - table[top * stride + right] - table[bottom * stride + left]
+            + table[top * stride + left]
+    }
+}
+
+/// Clusters rectangles of similar position and size, returning the average
+/// of each cluster with more than `min_neighbors` other members.
+fn group_rects(rects: &[Rect], min_neighbors: usize) -> Vec<Rect> {
+    let similar = |a: &Rect, b: &Rect| {
+        let delta = 0.2 * f64::from(a.width.min(b.width) + a.height.min(b.height)) * 0.5;
+        let close = |p: u32, q: u32| f64::from(p.abs_diff(q)) <= delta;
+        close(a.x, b.x)
+            && close(a.y, b.y)
+            && close(a.x + a.width, b.x + b.width)
+            && close(a.y + a.height, b.y + b.height)
+    };
+
+    // Union-find over the similarity relation.
+    let mut parents: Vec<usize> = (0..rects.len()).collect();
+    fn root(parents: &mut [usize], mut i: usize) -> usize {
+        while parents[i] != i {
+            parents[i] = parents[parents[i]];
+            i = parents[i];
+        }
+        i
+    }
+    for i in 0..rects.len() {
+        for j in i + 1..rects.len() {
+            if similar(&rects[i], &rects[j]) {
+                let (a, b) = (root(&mut parents, i), root(&mut parents, j));
+                parents[a] = b;
+            }
+        }
+    }
+
+    let mut clusters: Vec<(usize, [u64; 5])> = vec![];
+    let mut cluster_of = vec![usize::MAX; rects.len()];
+    for (i, rect) in rects.iter().enumerate() {
+        let root = root(&mut parents, i);
+        if cluster_of[root] == usize::MAX {
+            cluster_of[root] = clusters.len();
+            clusters.push((root, [0; 5]));
+        }
+        let totals = &mut clusters[cluster_of[root]].1;
+        totals[0] += u64::from(rect.x);
+        totals[1] += u64::from(rect.y);
+        totals[2] += u64::from(rect.width);
+        totals[3] += u64::from(rect.height);
+        totals[4] += 1;
+    }
+
+    clusters
+        .into_iter()
+        .filter(|(_, totals)| totals[4] as usize > min_neighbors)
+        .map(|(_, totals)| {
+            let average = |total: u64| ((total + totals[4] / 2) / totals[4]) as u32;
+            Rect {
+                x: average(totals[0]),
+                y: average(totals[1]),
+                width: average(totals[2]),
+                height: average(totals[3]),
+            }
+        })
+        .collect()
+}
+
+/// Parses whitespace separated numbers.
+fn numbers(text: &str) -> BoxResult<Vec<f64>> {
+    text.split_whitespace()
+        .map(|word| {
+            word.parse()
+                .map_err(|_| format!("Invalid number in cascade: {}", word).into())
+        })
+        .collect()
+}
+
+/// Just enough of an XML element tree to read OpenCV's cascade files.
+/// Attributes are skipped.
+struct Element {
+    name: String,
+    text: String,
+    children: Vec<Element>,
+}
+
+impl Element {
+    fn parse(xml: &str) -> BoxResult<Self> {
+        let mut stack = vec![Element {
+            name: String::new(),
+            text: String::new(),
+            children: vec![],
+        }];
+        let mut rest = xml;
+        while let Some(start) = rest.find('<') {
+            stack
+                .last_mut()
+                .expect("the document is always open")
+                .text
+                .push_str(&rest[..start]);
+            rest = &rest[start..];
+
+            // Comments, declarations and processing instructions are skipped.
+            let (end, is_element) = if rest.starts_with("<!--") {
+                ("-->", false)
+            } else if rest.starts_with("<?") || rest.starts_with("<!") {
+                (">", false)
+            } else {
+                (">", true)
+            };
+            let close = rest.find(end).ok_or("Unclosed tag in cascade")?;
+            let tag = &rest[1..close];
+            rest = &rest[close + end.len()..];
+            if !is_element {
+                continue;
+            }
+
+            if let Some(name) = tag.strip_prefix('/') {
+                let element = stack.pop().ok_or("Unbalanced tags in cascade")?;
+                if element.name != name.trim() || stack.is_empty() {
+                    return Err(format!("Unexpected closing tag </{}> in cascade", name).into());
+                }
+                stack
+                    .last_mut()
+                    .expect("checked above")
+                    .children
+                    .push(element);
+            } else {
+                let self_closing = tag.ends_with('/');
+                let tag = tag.trim_end_matches('/');
+                let name = tag.split_whitespace().next().unwrap_or("").to_owned();
+                let element = Element {
+                    name,
+                    text: String::new(),
+                    children: vec![],
+                };
+                if self_closing {
+                    stack
+                        .last_mut()
+                        .expect("the document is always open")
+                        .children
+                        .push(element);
+                } else {
+                    stack.push(element);
+                }
+            }
+        }
+
+        match stack.len() {
+            1 => Ok(stack.pop().expect("checked above")),
+            _ => Err("Unclosed element in cascade".into()),
+        }
+    }
+
+    fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    /// The first element called `name` in a depth first search.
+    fn find(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find_map(|child| {
+            if child.name == name {
+                Some(child)
+            } else {
+                child.find(name)
+            }
+        })
+    }
+
+    fn text_of(&self, name: &str) -> BoxResult<String> {
+        self.child(name)
+            .map(|child| child.text.clone())
+            .ok_or_else(|| format!("Cascade is missing <{}>", name).into())
+    }
+
+    fn number<T: std::str::FromStr>(&self, name: &str) -> BoxResult<T> {
+        let text = self.text_of(name)?;
+        text.trim()
+            .parse()
+            .map_err(|_| format!("Invalid <{}> in cascade: {}", name, text.trim()).into())
+    }
+
+    /// The `<_>` items of the child called `name`.
+    fn items(&self, name: &str) -> BoxResult<impl Iterator<Item = &Element>> {
+        let list = self
+            .child(name)
+            .ok_or_else(|| format!("Cascade is missing <{}>", name))?;
+        Ok(list.children.iter().filter(|item| item.name == "_"))
+    }
+}
@@ -4,8 +4,19 @@ use clap::{App, Arg, ArgMatches, Values};
 
 use image::ImageFormat;
 
+use crate::carve::{
+    Direction, Interpolation, ResizeMode, Roi, SeamConstraint, SeamOrder, Strategy, Symmetry,
+};
+use crate::debug::DebugOptions;
+use crate::encode::{EncodeOptions, PngCompression};
+use crate::energy::{Boundary, EnergyFunction, TextOptions};
+use crate::lines::LineOptions;
+use crate::tiled::TileOptions;
 use crate::BoxResult;
 
+/// The path that stands in for stdin or stdout.
+pub const STDIO_PATH: &str = "-";
+
 pub fn parse_args() -> BoxResult<Config> {
     let matches = App::new("Red Mountain Resize")
         .version(crate_version!())
@@ -46,20 +57,300 @@ pub fn parse_args() -> BoxResult<Config> {
                 .value_name("DEBUG_PATH")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("debug_alpha")
+                .long("debug-alpha")
+                .value_name("ALPHA")
+                .takes_value(true)
+                .requires("debug_path")
+                .validator(validate_alpha),
+        )
+        .arg(
+            Arg::with_name("debug_legend")
+                .long("debug-legend")
+                .requires("debug_path"),
+        )
         .arg(
             Arg::with_name("input_path")
                 .required(true)
                 .value_name("INPUT_PATH")
-                .takes_value(true),
+                .takes_value(true)
+                .help("The image to resize, or '-' to read from stdin"),
         )
         .arg(
             Arg::with_name("output_path")
                 .required(false)
                 .value_name("OUTPUT_PATH")
                 .takes_value(true)
-                .validator(validate_extension),
+                .help("Where to write the result, or '-' to write to stdout"),
+        )
+        .arg(
+            Arg::with_name("format")
+                .long("format")
+                .value_name("FORMAT")
+                .takes_value(true)
+                .validator(validate_format)
+                .help(
+                    "The output format, overriding the output extension. WebP and HDR images \
+                     can be read but not written",
+                ),
+        )
+        .arg(
+            Arg::with_name("quality")
+                .long("quality")
+                .value_name("QUALITY")
+                .takes_value(true)
+                .validator(validate_quality)
+                .help("JPEG quality from 1 to 100"),
+        )
+        .arg(
+            Arg::with_name("png_compression")
+                .long("png-compression")
+                .value_name("LEVEL")
+                .takes_value(true)
+                .possible_values(&["fast", "default", "best", "huffman", "rle"]),
+        )
+        .arg(
+            Arg::with_name("strip_metadata")
+                .long("strip-metadata")
+                .help("Drops EXIF and ICC profile data instead of copying it to the output"),
+        )
+        .arg(
+            Arg::with_name("mode")
+                .long("mode")
+                .value_name("MODE")
+                .takes_value(true)
+                .possible_values(&["carve", "crop", "auto"])
+                .help(
+                    "Whether to shrink by carving seams, cropping, or whichever loses less energy",
+                ),
+        )
+        .arg(
+            Arg::with_name("pyramid")
+                .long("pyramid")
+                .value_name("FACTOR")
+                .takes_value(true)
+                .validator(validate_pyramid_factor)
+                .help("Finds seams on a copy downscaled by FACTOR, then refines them at full size"),
+        )
+        .arg(
+            Arg::with_name("disjoint_seams")
+                .long("disjoint-seams")
+                .value_name("SEAMS")
+                .takes_value(true)
+                .conflicts_with("pyramid")
+                .validator(validate_disjoint_seams)
+                .help("Removes up to SEAMS non-overlapping seams per path search, trading quality for speed"),
+        )
+        .arg(
+            Arg::with_name("energy")
+                .long("energy")
+                .value_name("FUNCTION")
+                .takes_value(true)
+                .possible_values(&["square", "absolute"]),
+        )
+        .arg(
+            Arg::with_name("boundary")
+                .long("boundary")
+                .value_name("BOUNDARY")
+                .takes_value(true)
+                .possible_values(&["wrap", "clamp", "mirror", "fixed"])
+                .help("How energy is calculated for pixels on the edge of the image"),
+        )
+        .arg(
+            Arg::with_name("order")
+                .long("order")
+                .value_name("ORDER")
+                .takes_value(true)
+                .possible_values(&["width-first", "height-first"]),
+        )
+        .arg(
+            Arg::with_name("interpolation")
+                .long("interpolation")
+                .value_name("INTERPOLATION")
+                .takes_value(true)
+                .possible_values(&["average", "duplicate"]),
+        )
+        .arg(
+            Arg::with_name("seam_window")
+                .long("seam-window")
+                .value_name("PIXELS")
+                .takes_value(true)
+                .validator(validate_seam_window)
+                .help("How far a seam may step sideways between rows [default: 1]"),
+        )
+        .arg(
+            Arg::with_name("diagonal_penalty")
+                .long("diagonal-penalty")
+                .value_name("PENALTY")
+                .takes_value(true)
+                .validator(validate_diagonal_penalty)
+                .help("Extra energy per pixel a seam steps sideways, for smoother seams"),
+        )
+        .arg(
+            Arg::with_name("straight_seams")
+                .long("straight-seams")
+                .conflicts_with_all(&["seam_window", "diagonal_penalty"])
+                .help("Only carve straight rows and columns"),
+        )
+        .arg(
+            Arg::with_name("protect_path")
+                .long("protect")
+                .value_name("MASK_PATH")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("protect_faces")
+                .long("protect-faces")
+                .help(
+                    "Finds faces with a Haar cascade and keeps seams out of them. The bundled \
+                     cascade is a rough hand-written heuristic that misses many faces; pass a \
+                     trained one with --face-cascade for reliable results",
+                ),
+        )
+        .arg(
+            Arg::with_name("face_cascade")
+                .long("face-cascade")
+                .value_name("CASCADE_PATH")
+                .takes_value(true)
+                .requires("protect_faces")
+                .help(
+                    "Finds faces with this OpenCV cascade file instead of the bundled one, such \
+                     as haarcascade_frontalface_default.xml from OpenCV's data/haarcascades",
+                ),
+        )
+        .arg(
+            Arg::with_name("face_mask_path")
+                .long("export-face-mask")
+                .value_name("MASK_PATH")
+                .takes_value(true)
+                .requires("protect_faces")
+                .help("Saves the mask of found faces"),
+        )
+        .arg(
+            Arg::with_name("protect_skin")
+                .long("protect-skin")
+                .help("Keeps seams out of skin-toned pixels"),
+        )
+        .arg(
+            Arg::with_name("protect_text")
+                .long("protect-text")
+                .help("Finds rows of text and keeps seams out of them"),
+        )
+        .arg(
+            Arg::with_name("skin_mask_path")
+                .long("export-skin-mask")
+                .value_name("MASK_PATH")
+                .takes_value(true)
+                .requires("protect_skin")
+                .help("Saves the mask of skin-toned pixels"),
+        )
+        .arg(
+            Arg::with_name("roi")
+                .long("roi")
+                .value_name("X,Y,WIDTH,HEIGHT")
+                .takes_value(true)
+                .number_of_values(4)
+                .value_delimiter(",")
+                .validator(validate_roi)
+                .help("Only carve seams through this rectangle"),
+        )
+        .arg(
+            Arg::with_name("roi_path")
+                .long("roi-mask")
+                .value_name("MASK_PATH")
+                .takes_value(true)
+                .conflicts_with("roi")
+                .help("Only carve seams through pixels where the mask is non-zero"),
+        )
+        .arg(
+            Arg::with_name("preserve_lines")
+                .long("preserve-lines")
+                .help("Finds straight lines and keeps seams from bending them"),
+        )
+        .arg(
+            Arg::with_name("line_weight")
+                .long("line-weight")
+                .value_name("ENERGY")
+                .takes_value(true)
+                .requires("preserve_lines")
+                .validator(validate_line_weight)
+                .help("Energy added along each straight line"),
+        )
+        .arg(
+            Arg::with_name("symmetry")
+                .long("symmetry")
+                .value_name("AXIS")
+                .takes_value(true)
+                .possible_values(&["vertical", "horizontal"])
+                .help("Removes seams in mirrored pairs about a vertical or horizontal axis"),
+        )
+        .arg(
+            Arg::with_name("symmetry_axis")
+                .long("symmetry-axis")
+                .value_name("POSITION")
+                .takes_value(true)
+                .requires("symmetry")
+                .validator(validate_symmetry_axis)
+                .help("Pixels from the left or top edge to the axis, instead of finding it"),
+        )
+        .arg(
+            Arg::with_name("threads")
+                .long("threads")
+                .value_name("THREADS")
+                .takes_value(true)
+                .validator(validate_threads),
+        )
+        .arg(
+            Arg::with_name("low_memory")
+                .long("low-memory")
+                .help("Plans enlargements without copying the image, for very large images"),
+        )
+        .arg(
+            Arg::with_name("tiled")
+                .long("tiled")
+                .conflicts_with_all(&[
+                    "debug_path",
+                    "format",
+                    "protect_faces",
+                    "protect_skin",
+                    "roi",
+                    "roi_path",
+                    "symmetry",
+                    "stats_path",
+                ])
+                .help(
+                    "Carves the width in vertical strips and streams a tiled TIFF, for images \
+                     too big to carve whole",
+                ),
+        )
+        .arg(
+            Arg::with_name("strip_width")
+                .long("strip-width")
+                .value_name("PIXELS")
+                .takes_value(true)
+                .requires("tiled")
+                .validator(validate_strip_width)
+                .help("The most columns each strip carves"),
+        )
+        .arg(
+            Arg::with_name("strip_overlap")
+                .long("strip-overlap")
+                .value_name("PIXELS")
+                .takes_value(true)
+                .requires("tiled")
+                .validator(validate_strip_overlap)
+                .help("Columns read on either side of each strip for context, at least one"),
+        )
+        .arg(
+            Arg::with_name("stats_path")
+                .long("stats")
+                .value_name("STATS_PATH")
+                .takes_value(true)
+                .help("Writes a JSON timing report to STATS_PATH, or stdout if '-'"),
         )
         .arg(Arg::with_name("time").short("t").long("time"))
+        .arg(Arg::with_name("quiet").short("q").long("quiet"))
         .get_matches();
 
     Config::try_from(matches)
@@ -85,23 +376,153 @@ fn validate_dimension(s: String) -> Result<(), String> {
     }
 }
 
-fn validate_extension(s: String) -> Result<(), String> {
-    let path = Path::new(&s);
-    get_format(path).map(|_| ())
+fn validate_alpha(s: String) -> Result<(), String> {
+    match s.parse::<f32>() {
+        Ok(n) if (0.0..=1.0).contains(&n) => Ok(()),
+        Ok(_) => Err("Alpha must be between 0 and 1".to_owned()),
+        Err(_) => Err("Invalid alpha".to_owned()),
+    }
+}
+
+fn validate_threads(s: String) -> Result<(), String> {
+    match s.parse::<usize>() {
+        Ok(0) => Err("Thread count must be greater than zero".to_owned()),
+        Ok(_) => Ok(()),
+        Err(_) => Err("Invalid thread count".to_owned()),
+    }
+}
+
+fn validate_strip_width(s: String) -> Result<(), String> {
+    match s.parse::<u32>() {
+        Ok(0) => Err("Strip widths must be greater than zero".to_owned()),
+        Ok(_) => Ok(()),
+        Err(_) => Err("Invalid strip width".to_owned()),
+    }
+}
+
+fn validate_strip_overlap(s: String) -> Result<(), String> {
+    match s.parse::<u32>() {
+        Ok(0) => Err("Strips must overlap by at least one column".to_owned()),
+        Ok(_) => Ok(()),
+        Err(_) => Err("Invalid strip overlap".to_owned()),
+    }
+}
+
+fn validate_seam_window(s: String) -> Result<(), String> {
+    s.parse::<usize>()
+        .map(|_| ())
+        .map_err(|_| "Invalid seam window".to_owned())
+}
+
+fn validate_diagonal_penalty(s: String) -> Result<(), String> {
+    s.parse::<u32>()
+        .map(|_| ())
+        .map_err(|_| "Invalid diagonal penalty".to_owned())
+}
+
+fn validate_pyramid_factor(s: String) -> Result<(), String> {
+    match s.parse::<usize>() {
+        Ok(n) if n >= 2 => Ok(()),
+        Ok(_) => Err("Pyramid factor must be at least 2".to_owned()),
+        Err(_) => Err("Invalid pyramid factor".to_owned()),
+    }
+}
+
+fn validate_disjoint_seams(s: String) -> Result<(), String> {
+    match s.parse::<usize>() {
+        Ok(0) => Err("Seam count must be greater than zero".to_owned()),
+        Ok(_) => Ok(()),
+        Err(_) => Err("Invalid seam count".to_owned()),
+    }
+}
+
+fn validate_roi(s: String) -> Result<(), String> {
+    s.parse::<u32>()
+        .map(|_| ())
+        .map_err(|_| "Invalid region of interest".to_owned())
+}
+
+fn validate_line_weight(s: String) -> Result<(), String> {
+    s.parse::<u32>()
+        .map(|_| ())
+        .map_err(|_| "Invalid line weight".to_owned())
+}
+
+fn validate_symmetry_axis(s: String) -> Result<(), String> {
+    match s.parse::<f64>() {
+        Ok(n) if n > 0.0 => Ok(()),
+        _ => Err("Symmetry axis must be a positive number".to_owned()),
+    }
+}
+
+fn validate_quality(s: String) -> Result<(), String> {
+    match s.parse::<u8>() {
+        Ok(n) if (1..=100).contains(&n) => Ok(()),
+        _ => Err("Quality must be between 1 and 100".to_owned()),
+    }
+}
+
+fn validate_format(s: String) -> Result<(), String> {
+    get_format_from_name(&s.to_lowercase())
+        .map_err(|_| "Invalid image format".to_owned())
+        .and_then(check_output_format)
+}
+
+fn check_output_format(format: ImageFormat) -> Result<(), String> {
+    match format {
+        ImageFormat::WebP => Err("Writing WebP images is not supported".to_owned()),
+        ImageFormat::Hdr => Err("Writing HDR images is not supported".to_owned()),
+        _ => Ok(()),
+    }
 }
 
 #[derive(Debug)]
 pub struct Config {
     pub input_path: PathBuf,
     pub output_path: Option<PathBuf>,
+    pub format: Option<ImageFormat>,
+    pub encode_options: EncodeOptions,
+    pub strip_metadata: bool,
     pub width: Option<isize>,
     pub height: Option<isize>,
     pub dimensions: Option<(usize, usize)>,
     pub debug_path: Option<PathBuf>,
+    pub debug_options: DebugOptions,
+    pub mode: ResizeMode,
+    pub strategy: Strategy,
+    pub energy_function: EnergyFunction,
+    pub boundary: Boundary,
+    pub seam_order: SeamOrder,
+    pub interpolation: Interpolation,
+    pub seam_constraint: SeamConstraint,
+    pub protect_path: Option<PathBuf>,
+    pub protect_faces: bool,
+    pub face_cascade_path: Option<PathBuf>,
+    pub face_mask_path: Option<PathBuf>,
+    pub protect_skin: bool,
+    pub skin_mask_path: Option<PathBuf>,
+    pub text: Option<TextOptions>,
+    pub roi: Option<Roi>,
+    pub roi_path: Option<PathBuf>,
+    pub lines: Option<LineOptions>,
+    pub symmetry: Option<Symmetry>,
+    pub threads: usize,
+    pub low_memory: bool,
+    pub tiles: Option<TileOptions>,
+    pub stats_path: Option<PathBuf>,
     pub time: bool,
+    pub quiet: bool,
 }
 
 impl Config {
+    pub fn input_is_stdin(&self) -> bool {
+        self.input_path == Path::new(STDIO_PATH)
+    }
+
+    pub fn output_is_stdout(&mut self) -> bool {
+        self.get_output_path() == Path::new(STDIO_PATH)
+    }
+
     pub fn get_output_path(&mut self) -> &Path {
         match self.output_path {
             Some(ref output_path) => output_path,
@@ -110,6 +531,11 @@ impl Config {
     }
 
     fn get_default_path(&mut self) -> &Path {
+        if self.input_is_stdin() {
+            self.output_path = Some(STDIO_PATH.into());
+            return self.output_path.as_ref().unwrap();
+        }
+
         let mut output_path = self.input_path.clone();
 
         let mut stem = self.input_path.file_stem().unwrap().to_owned();
@@ -129,7 +555,31 @@ impl Config {
             .expect("the input path")
             .into();
 
-        let output_path = matches.value_of("output_path").map(|s| s.into());
+        let output_path: Option<PathBuf> = matches.value_of("output_path").map(|s| s.into());
+        let format = matches
+            .value_of("format")
+            .and_then(|s| get_format_from_name(&s.to_lowercase()).ok());
+        // --format overrides the extension, so the output path only needs a
+        // known one without it.
+        if let (None, Some(path)) = (format, &output_path) {
+            if path != Path::new(STDIO_PATH) {
+                get_format(path).and_then(check_output_format)?;
+            }
+        }
+        let mut encode_options = EncodeOptions::default();
+        if let Some(quality) = matches.value_of("quality").and_then(|s| s.parse().ok()) {
+            encode_options.quality = quality;
+        }
+        encode_options.png_compression = match matches.value_of("png_compression") {
+            Some("default") => PngCompression::Default,
+            Some("best") => PngCompression::Best,
+            Some("huffman") => PngCompression::Huffman,
+            Some("rle") => PngCompression::Rle,
+            _ => PngCompression::Fast,
+        };
+
+        let strip_metadata = matches.is_present("strip_metadata");
+
         let width = matches.value_of("width").and_then(|s| s.parse().ok());
         let height = matches.value_of("height").and_then(|s| s.parse().ok());
 
@@ -139,16 +589,160 @@ impl Config {
 
         let debug_path = matches.value_of("debug_path").map(|s| s.into());
 
+        let mut debug_options = DebugOptions::default();
+        if let Some(alpha) = matches.value_of("debug_alpha").and_then(|s| s.parse().ok()) {
+            debug_options.alpha = alpha;
+        }
+        debug_options.legend = matches.is_present("debug_legend");
+
+        let mode = match matches.value_of("mode") {
+            Some("crop") => ResizeMode::Crop,
+            Some("auto") => ResizeMode::Auto,
+            _ => ResizeMode::Carve,
+        };
+
+        let pyramid = matches
+            .value_of("pyramid")
+            .and_then(|s| s.parse().ok())
+            .map(Strategy::pyramid);
+        let disjoint = matches
+            .value_of("disjoint_seams")
+            .and_then(|s| s.parse().ok())
+            .map(|seams| Strategy::Disjoint { seams });
+        let strategy = pyramid.or(disjoint).unwrap_or(Strategy::FullResolution);
+
+        let energy_function = match matches.value_of("energy") {
+            Some("absolute") => EnergyFunction::AbsoluteGradient,
+            _ => EnergyFunction::SquareGradient,
+        };
+
+        let boundary = match matches.value_of("boundary") {
+            Some("clamp") => Boundary::Clamp,
+            Some("mirror") => Boundary::Mirror,
+            Some("fixed") => Boundary::Fixed,
+            _ => Boundary::Wrap,
+        };
+
+        let seam_order = match matches.value_of("order") {
+            Some("height-first") => SeamOrder::HeightFirst,
+            _ => SeamOrder::WidthFirst,
+        };
+
+        let interpolation = match matches.value_of("interpolation") {
+            Some("duplicate") => Interpolation::Duplicate,
+            _ => Interpolation::Average,
+        };
+
+        let mut seam_constraint = SeamConstraint::default();
+        if matches.is_present("straight_seams") {
+            seam_constraint = SeamConstraint::straight();
+        }
+        if let Some(window) = matches.value_of("seam_window").and_then(|s| s.parse().ok()) {
+            seam_constraint.window = window;
+        }
+        if let Some(penalty) = matches
+            .value_of("diagonal_penalty")
+            .and_then(|s| s.parse().ok())
+        {
+            seam_constraint.diagonal_penalty = penalty;
+        }
+
+        let protect_path = matches.value_of("protect_path").map(|s| s.into());
+        let protect_faces = matches.is_present("protect_faces");
+        let face_cascade_path = matches.value_of("face_cascade").map(|s| s.into());
+        let face_mask_path = matches.value_of("face_mask_path").map(|s| s.into());
+        let protect_skin = matches.is_present("protect_skin");
+        let skin_mask_path = matches.value_of("skin_mask_path").map(|s| s.into());
+        let text = if matches.is_present("protect_text") {
+            Some(TextOptions::default())
+        } else {
+            None
+        };
+        let roi = matches.values_of("roi").map(Config::parse_roi);
+        let roi_path = matches.value_of("roi_path").map(|s| s.into());
+
+        let lines = if matches.is_present("preserve_lines") {
+            let mut lines = LineOptions::default();
+            if let Some(weight) = matches.value_of("line_weight").and_then(|s| s.parse().ok()) {
+                lines.weight = weight;
+            }
+            Some(lines)
+        } else {
+            None
+        };
+
+        let symmetry_direction = match matches.value_of("symmetry") {
+            Some("vertical") => Some(Direction::Vertical),
+            Some("horizontal") => Some(Direction::Horizontal),
+            _ => None,
+        };
+        let symmetry = symmetry_direction.map(|direction| Symmetry {
+            direction,
+            axis: matches
+                .value_of("symmetry_axis")
+                .and_then(|s| s.parse().ok()),
+        });
+        let threads = matches
+            .value_of("threads")
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(1);
+        let low_memory = matches.is_present("low_memory");
+        let tiles = if matches.is_present("tiled") {
+            let mut tiles = TileOptions::default();
+            if let Some(width) = matches.value_of("strip_width").and_then(|s| s.parse().ok()) {
+                tiles.strip_width = width;
+            }
+            if let Some(overlap) = matches
+                .value_of("strip_overlap")
+                .and_then(|s| s.parse().ok())
+            {
+                tiles.overlap = overlap;
+            }
+            Some(tiles)
+        } else {
+            None
+        };
+
+        let stats_path = matches.value_of("stats_path").map(|s| s.into());
+
         let time = matches.is_present("time");
+        let quiet = matches.is_present("quiet");
 
         Ok(Config {
             input_path,
             output_path,
+            format,
+            encode_options,
+            strip_metadata,
             width,
             height,
             dimensions,
             debug_path,
+            debug_options,
+            mode,
+            strategy,
+            energy_function,
+            boundary,
+            seam_order,
+            interpolation,
+            seam_constraint,
+            protect_path,
+            protect_faces,
+            face_cascade_path,
+            face_mask_path,
+            protect_skin,
+            skin_mask_path,
+            text,
+            roi,
+            roi_path,
+            lines,
+            symmetry,
+            threads,
+            low_memory,
+            tiles,
+            stats_path,
             time,
+            quiet,
         })
     }
 
@@ -157,27 +751,43 @@ impl Config {
         let y = values.next().and_then(|s| s.parse().ok()).expect("y value");
         (x, y)
     }
+
+    fn parse_roi(values: Values) -> Roi {
+        let values: Vec<u32> = values
+            .map(|s| s.parse().expect("validated ROI value"))
+            .collect();
+        Roi::Rect {
+            x: values[0],
+            y: values[1],
+            width: values[2],
+            height: values[3],
+        }
+    }
 }
 
 pub fn get_format<P: AsRef<Path>>(path: P) -> Result<ImageFormat, String> {
     match get_extension(&path) {
-        Some(extension) => match extension.as_str() {
-            "png" => Ok(ImageFormat::Png),
-            "jpg" | "jpeg" => Ok(ImageFormat::Jpeg),
-            "gif" => Ok(ImageFormat::Gif),
-            "webp" => Ok(ImageFormat::WebP),
-            "ppm" => Ok(ImageFormat::Pnm),
-            "tif" | "tiff" => Ok(ImageFormat::Tiff),
-            "tga" => Ok(ImageFormat::Tga),
-            "bmp" => Ok(ImageFormat::Bmp),
-            "ico" => Ok(ImageFormat::Ico),
-            "hdr" => Ok(ImageFormat::Hdr),
-            _ => Err("Invalid file extension".to_owned()),
-        },
+        Some(extension) => get_format_from_name(&extension),
         None => Err("No file extension given.".to_owned()),
     }
 }
 
+fn get_format_from_name(name: &str) -> Result<ImageFormat, String> {
+    match name {
+        "png" => Ok(ImageFormat::Png),
+        "jpg" | "jpeg" => Ok(ImageFormat::Jpeg),
+        "gif" => Ok(ImageFormat::Gif),
+        "webp" => Ok(ImageFormat::WebP),
+        "ppm" => Ok(ImageFormat::Pnm),
+        "tif" | "tiff" => Ok(ImageFormat::Tiff),
+        "tga" => Ok(ImageFormat::Tga),
+        "bmp" => Ok(ImageFormat::Bmp),
+        "ico" => Ok(ImageFormat::Ico),
+        "hdr" => Ok(ImageFormat::Hdr),
+        _ => Err("Invalid file extension".to_owned()),
+    }
+}
+
 fn get_extension<P: AsRef<Path>>(path: P) -> Option<String> {
     path.as_ref()
         .extension()
@@ -0,0 +1,50 @@
+use crate::energy::PixelEnergyPoint;
+use crate::grid::Grid;
+
+/// The columns kept by a crop, along with the energy of those it discards.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CropWindow {
+    pub start: usize,
+    pub len: usize,
+    pub removed_energy: u64,
+}
+
+/// The total energy of each column of the grid's view.
+pub fn column_energy(grid: &Grid<PixelEnergyPoint>) -> Vec<u64> {
+    (0..grid.width())
+        .map(|x| {
+            (0..grid.height())
+                .map(|y| u64::from(grid.get(x, y).energy))
+                .sum()
+        })
+        .collect()
+}
+
+/// Finds the `len` adjacent columns that retain the most energy. Ties go to
+/// the leftmost window.
+///
+/// # Panics
+///
+/// Panics if `len` is larger than the number of columns.
+pub fn find_crop_window(column_energy: &[u64], len: usize) -> CropWindow {
+    assert!(
+        len <= column_energy.len(),
+        "Crop window is larger than the image"
+    );
+
+    let total: u64 = column_energy.iter().sum();
+    let mut retained: u64 = column_energy[..len].iter().sum();
+    let mut best = (0, retained);
+    for start in 1..=(column_energy.len() - len) {
+        retained = retained - column_energy[start - 1] + column_energy[start + len - 1];
+        if retained > best.1 {
+            best = (start, retained);
+        }
+    }
+
+    CropWindow {
+        start: best.0,
+        len,
+        removed_energy: total - best.1,
+    }
+}
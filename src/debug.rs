@@ -0,0 +1,232 @@
+use image::{DynamicImage, GenericImage, GenericImageView, Rgba};
+
+use crate::carve::{Direction, SeamKind, SeamPoint};
+use crate::lines::LineSegment;
+
+const LEGEND_SCALE: u32 = 2;
+const LEGEND_PADDING: u32 = 4;
+const GLYPH_WIDTH: u32 = 3;
+const GLYPH_HEIGHT: u32 = 5;
+
+const LEGEND_ENTRIES: [(Direction, SeamKind); 4] = [
+    (Direction::Vertical, SeamKind::Removed),
+    (Direction::Vertical, SeamKind::Inserted),
+    (Direction::Horizontal, SeamKind::Removed),
+    (Direction::Horizontal, SeamKind::Inserted),
+];
+
+/// The overlay color used for detected straight lines.
+pub const LINE_COLOR: Rgba<u8> = Rgba([0, 255, 255, 255]);
+
+#[derive(Clone, Debug)]
+pub struct DebugOptions {
+    /// Opacity of the seam overlay, from `0.0` (invisible) to `1.0` (opaque).
+    pub alpha: f32,
+    /// Appends a strip below the image labelling each seam color.
+    pub legend: bool,
+}
+
+impl Default for DebugOptions {
+    fn default() -> Self {
+        DebugOptions {
+            alpha: 1.0,
+            legend: false,
+        }
+    }
+}
+
+/// The overlay color used for seams of the given direction and kind.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate image;
+/// # extern crate rmr;
+/// # use image::Rgba;
+/// # use rmr::carve::{Direction, SeamKind};
+/// # use rmr::debug::seam_color;
+/// # fn main() {
+/// let color = seam_color(Direction::Vertical, SeamKind::Removed);
+/// assert_eq!(Rgba([255, 0, 0, 255]), color);
+/// # }
+/// ```
+pub fn seam_color(direction: Direction, kind: SeamKind) -> Rgba<u8> {
+    match (direction, kind) {
+        (Direction::Vertical, SeamKind::Removed) => Rgba([255, 0, 0, 255]),
+        (Direction::Vertical, SeamKind::Inserted) => Rgba([0, 255, 0, 255]),
+        (Direction::Horizontal, SeamKind::Removed) => Rgba([0, 0, 255, 255]),
+        (Direction::Horizontal, SeamKind::Inserted) => Rgba([255, 255, 0, 255]),
+    }
+}
+
+pub fn create_debug_image(
+    image: &DynamicImage,
+    points: &[SeamPoint],
+    options: &DebugOptions,
+) -> DynamicImage {
+    create_debug_image_with_lines(image, points, &[], options)
+}
+
+/// Like `create_debug_image`, but also draws `lines` under the seams. The
+/// legend gains an entry for lines when there are any.
+pub fn create_debug_image_with_lines(
+    image: &DynamicImage,
+    points: &[SeamPoint],
+    lines: &[LineSegment],
+    options: &DebugOptions,
+) -> DynamicImage {
+    // Grayscale or palette inputs can't hold the overlay colors.
+    let mut image = DynamicImage::ImageRgba8(image.to_rgba8());
+    let alpha = options.alpha.clamp(0.0, 1.0);
+
+    for line in lines {
+        for (x, y) in line.points() {
+            if image.in_bounds(x, y) {
+                let blended = blend(image.get_pixel(x, y), LINE_COLOR, alpha);
+                image.put_pixel(x, y, blended);
+            }
+        }
+    }
+
+    for point in points {
+        let (x, y) = (point.x as u32, point.y as u32);
+        if !image.in_bounds(x, y) {
+            continue;
+        }
+        let color = seam_color(point.direction, point.kind);
+        let blended = blend(image.get_pixel(x, y), color, alpha);
+        image.put_pixel(x, y, blended);
+    }
+
+    if options.legend {
+        let mut entries: Vec<(Rgba<u8>, &str)> = LEGEND_ENTRIES
+            .iter()
+            .map(|&(direction, kind)| (seam_color(direction, kind), legend_label(direction, kind)))
+            .collect();
+        if !lines.is_empty() {
+            entries.push((LINE_COLOR, "LINES"));
+        }
+        image = append_legend(&image, &entries);
+    }
+
+    image
+}
+
+fn blend(background: Rgba<u8>, foreground: Rgba<u8>, alpha: f32) -> Rgba<u8> {
+    let mut data = [0; 4];
+    for (i, channel) in data.iter_mut().enumerate() {
+        let back = f32::from(background.0[i]);
+        let fore = f32::from(foreground.0[i]);
+        *channel = (fore * alpha + back * (1.0 - alpha)).round() as u8;
+    }
+    Rgba(data)
+}
+
+fn append_legend(image: &DynamicImage, entries: &[(Rgba<u8>, &str)]) -> DynamicImage {
+    let row_height = GLYPH_HEIGHT * LEGEND_SCALE + LEGEND_PADDING;
+    let legend_height = row_height * entries.len() as u32 + LEGEND_PADDING;
+    let legend_width = entries
+        .iter()
+        .map(|&(_, label)| label.len() as u32)
+        .max()
+        .unwrap_or(0)
+        * (GLYPH_WIDTH + 1)
+        * LEGEND_SCALE
+        + row_height
+        + LEGEND_PADDING * 2;
+
+    let (width, height) = image.dimensions();
+    let mut output = DynamicImage::new_rgba8(width.max(legend_width), height + legend_height);
+    output
+        .copy_from(image, 0, 0)
+        .expect("legend canvas should fit the image");
+
+    let black = Rgba([0, 0, 0, 255]);
+    for y in height..output.height() {
+        for x in 0..output.width() {
+            output.put_pixel(x, y, black);
+        }
+    }
+
+    let swatch_size = GLYPH_HEIGHT * LEGEND_SCALE;
+    let white = Rgba([255, 255, 255, 255]);
+    for (i, &(color, label)) in entries.iter().enumerate() {
+        let top = height + LEGEND_PADDING + row_height * i as u32;
+        fill_rect(
+            &mut output,
+            LEGEND_PADDING,
+            top,
+            swatch_size,
+            swatch_size,
+            color,
+        );
+
+        let text_left = LEGEND_PADDING * 2 + swatch_size;
+        draw_text(&mut output, text_left, top, label, white);
+    }
+
+    output
+}
+
+fn legend_label(direction: Direction, kind: SeamKind) -> &'static str {
+    match (direction, kind) {
+        (Direction::Vertical, SeamKind::Removed) => "VERTICAL REMOVED",
+        (Direction::Vertical, SeamKind::Inserted) => "VERTICAL INSERTED",
+        (Direction::Horizontal, SeamKind::Removed) => "HORIZONTAL REMOVED",
+        (Direction::Horizontal, SeamKind::Inserted) => "HORIZONTAL INSERTED",
+    }
+}
+
+fn fill_rect(
+    image: &mut DynamicImage,
+    left: u32,
+    top: u32,
+    width: u32,
+    height: u32,
+    color: Rgba<u8>,
+) {
+    for y in top..top + height {
+        for x in left..left + width {
+            if image.in_bounds(x, y) {
+                image.put_pixel(x, y, color);
+            }
+        }
+    }
+}
+
+fn draw_text(image: &mut DynamicImage, left: u32, top: u32, text: &str, color: Rgba<u8>) {
+    for (i, c) in text.chars().enumerate() {
+        let glyph_left = left + i as u32 * (GLYPH_WIDTH + 1) * LEGEND_SCALE;
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for column in 0..GLYPH_WIDTH {
+                if bits & (0b100 >> column) != 0 {
+                    let x = glyph_left + column * LEGEND_SCALE;
+                    let y = top + row as u32 * LEGEND_SCALE;
+                    fill_rect(image, x, y, LEGEND_SCALE, LEGEND_SCALE, color);
+                }
+            }
+        }
+    }
+}
+
+// A 3x5 bitmap font covering only the characters used by the legend labels.
+fn glyph(c: char) -> [u8; 5] {
+    match c {
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        _ => [0; 5],
+    }
+}
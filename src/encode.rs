@@ -0,0 +1,107 @@
+use std::fs::File;
+use std::io::{BufWriter, Cursor, Write};
+use std::path::Path;
+
+use image::codecs::png::{CompressionType, FilterType, PngEncoder};
+use image::codecs::tiff::TiffEncoder;
+use image::{DynamicImage, GenericImageView, ImageFormat, ImageOutputFormat};
+
+use crate::BoxResult;
+
+/// The default JPEG quality, matching the `image` crate.
+pub const DEFAULT_JPEG_QUALITY: u8 = 75;
+
+/// Compression levels for PNG output.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PngCompression {
+    #[default]
+    Fast,
+    Default,
+    Best,
+    Huffman,
+    Rle,
+}
+
+impl From<PngCompression> for CompressionType {
+    fn from(compression: PngCompression) -> Self {
+        match compression {
+            PngCompression::Fast => CompressionType::Fast,
+            PngCompression::Default => CompressionType::Default,
+            PngCompression::Best => CompressionType::Best,
+            PngCompression::Huffman => CompressionType::Huffman,
+            PngCompression::Rle => CompressionType::Rle,
+        }
+    }
+}
+
+/// Settings handed to the encoder for the output format. Options that don't
+/// apply to the chosen format are ignored.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EncodeOptions {
+    /// JPEG quality, from 1 to 100.
+    pub quality: u8,
+    pub png_compression: PngCompression,
+}
+
+impl Default for EncodeOptions {
+    fn default() -> Self {
+        EncodeOptions {
+            quality: DEFAULT_JPEG_QUALITY,
+            png_compression: PngCompression::default(),
+        }
+    }
+}
+
+/// Encodes `image` as `format` into `writer`.
+pub fn write_image<W: Write>(
+    image: &DynamicImage,
+    writer: &mut W,
+    format: ImageFormat,
+    options: &EncodeOptions,
+) -> BoxResult<()> {
+    match format {
+        ImageFormat::Jpeg => {
+            image.write_to(writer, ImageOutputFormat::Jpeg(options.quality))?;
+        }
+        ImageFormat::Png => {
+            let (width, height) = image.dimensions();
+            let encoder = PngEncoder::new_with_quality(
+                writer,
+                options.png_compression.into(),
+                FilterType::Sub,
+            );
+            encoder.encode(&image.to_bytes(), width, height, image.color())?;
+        }
+        ImageFormat::Tiff => {
+            // The TIFF encoder seeks back to write its offsets, so it encodes
+            // into memory first.
+            let (width, height) = image.dimensions();
+            let mut buffer = Cursor::new(vec![]);
+            TiffEncoder::new(&mut buffer).encode(
+                &image.to_bytes(),
+                width,
+                height,
+                image.color(),
+            )?;
+            writer.write_all(buffer.get_ref())?;
+        }
+        ImageFormat::WebP => {
+            return Err("Writing WebP images is not supported".into());
+        }
+        _ => image.write_to(writer, format)?,
+    }
+    Ok(())
+}
+
+/// Encodes `image` as `format` into a new file at `path`.
+pub fn save_image<P: AsRef<Path>>(
+    image: &DynamicImage,
+    path: P,
+    format: ImageFormat,
+    options: &EncodeOptions,
+) -> BoxResult<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_image(image, &mut writer, format, options)?;
+    writer.flush()?;
+    Ok(())
+}
@@ -1,11 +1,106 @@
-use image::{Pixel, Rgba};
+use std::f64::consts::PI;
+
+use image::{DynamicImage, GenericImageView, GrayImage, Luma, Pixel, Rgba};
+
+/// Energy added to protected pixels so seams route around them whenever an
+/// unprotected path exists.
+pub const PROTECTED_ENERGY: u32 = 1 << 24;
+
+/// Energy added to pixels for each edge of the image they're on with
+/// `Boundary::Fixed`. It's higher than any gradient, so seams stay off the
+/// edges unless they have to cross protected pixels instead.
+pub const BORDER_ENERGY: u32 = 1 << 20;
+
+/// How the neighbors of pixels on the edge of the image are found when
+/// calculating energy.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Boundary {
+    /// Wrap around to the opposite edge.
+    #[default]
+    Wrap,
+    /// Repeat the edge pixel, so edges compare against themselves.
+    Clamp,
+    /// Reflect back into the image, so edges compare the pixel next to them
+    /// against itself.
+    Mirror,
+    /// Find neighbors as with `Clamp`, then add `BORDER_ENERGY` for each
+    /// edge a pixel is on. Every seam still has to cross two edges, where the
+    /// corners cost the most.
+    Fixed,
+}
+
+impl Boundary {
+    /// The indices either side of `index` along an axis of `len` pixels.
+    pub fn neighbors(self, index: usize, len: usize) -> (usize, usize) {
+        let last = len - 1;
+        match self {
+            Boundary::Wrap => (
+                if index == 0 { last } else { index - 1 },
+                if index == last { 0 } else { index + 1 },
+            ),
+            Boundary::Clamp | Boundary::Fixed => (index.saturating_sub(1), (index + 1).min(last)),
+            Boundary::Mirror => (
+                if index == 0 { 1.min(last) } else { index - 1 },
+                if index == last {
+                    last.saturating_sub(1)
+                } else {
+                    index + 1
+                },
+            ),
+        }
+    }
+
+    /// The energy added to the pixel at `(x, y)` of a `width` by `height`
+    /// image for being on its edges.
+    pub fn border_energy(self, x: usize, y: usize, width: usize, height: usize) -> u32 {
+        if self != Boundary::Fixed {
+            return 0;
+        }
+        let edges = u32::from(x == 0 || x == width - 1) + u32::from(y == 0 || y == height - 1);
+        edges * BORDER_ENERGY
+    }
+}
+
+/// How the difference between two neighboring pixels is measured when
+/// calculating energy.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EnergyFunction {
+    /// Sum of squared channel differences (the dual-gradient energy).
+    #[default]
+    SquareGradient,
+    /// Sum of absolute channel differences. Less sensitive to strong edges.
+    AbsoluteGradient,
+}
+
+impl EnergyFunction {
+    pub fn gradient(self, a: &Rgba<u8>, b: &Rgba<u8>) -> u32 {
+        match self {
+            EnergyFunction::SquareGradient => square_gradient(a, b),
+            EnergyFunction::AbsoluteGradient => absolute_gradient(a, b),
+        }
+    }
+
+    /// The energy of a pixel given its four neighbors.
+    pub fn energy(
+        self,
+        left: &PixelEnergyPoint,
+        right: &PixelEnergyPoint,
+        up: &PixelEnergyPoint,
+        down: &PixelEnergyPoint,
+    ) -> u32 {
+        self.gradient(&left.pixel, &right.pixel) + self.gradient(&up.pixel, &down.pixel)
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct PixelEnergyPoint {
     pub pixel: Rgba<u8>,
     pub energy: u32,
     pub path_cost: u32,
-    pub original_position: (usize, usize),
+    /// The index of the pixel this point started as, counting across the
+    /// rows of the input image. Anything else known about the pixel, like
+    /// whether it's protected, is kept by the carver under this index.
+    pub original_index: u32,
 }
 
 impl PixelEnergyPoint {
@@ -33,26 +128,18 @@ impl PixelEnergyPoint {
     /// # }
     /// ```
     pub fn square_gradient(&self, other: &PixelEnergyPoint) -> u32 {
-        let pixel1_channels = self.pixel.channels();
-        let pixel2_channels = other.pixel.channels();
-
-        let mut sum = 0;
-        for i in 0..pixel1_channels.len() {
-            // Values are cast to u32 to prevent overflow when summing
-            let a: u32 = pixel1_channels[i].into();
-            let b: u32 = pixel2_channels[i].into();
-            if a >= b {
-                sum += (a - b).pow(2);
-            } else {
-                sum += (b - a).pow(2);
-            }
-        }
-        sum
+        square_gradient(&self.pixel, &other.pixel)
     }
 
+    /// Blends two points into a new one. The result takes on the original
+    /// index of `self` so seams that later pass through an inserted pixel
+    /// can still be traced back to the input image, and it's treated like
+    /// `self` wherever the carver looks that index up.
     pub fn average(&self, other: &PixelEnergyPoint) -> PixelEnergyPoint {
         let data = average_pixel_data(&self.pixel.0, &other.pixel.0);
-        Rgba(data).into()
+        let mut pep: PixelEnergyPoint = Rgba(data).into();
+        pep.original_index = self.original_index;
+        pep
     }
 }
 
@@ -62,9 +149,409 @@ impl From<Rgba<u8>> for PixelEnergyPoint {
             pixel,
             energy: 0,
             path_cost: 0,
-            original_position: (0, 0),
+            original_index: 0,
+        }
+    }
+}
+
+/// Settings for finding text to protect with a stroke width transform.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TextOptions {
+    /// The Sobel gradient, summed over both axes, a pixel needs to count as
+    /// the edge of a stroke.
+    pub edge_threshold: u32,
+    /// The widest stroke a letter may have, in pixels.
+    pub max_stroke_width: u32,
+    /// The shortest letter kept, in pixels.
+    pub min_height: u32,
+    /// The tallest letter kept, in pixels.
+    pub max_height: u32,
+    /// The fewest letters in a row that count as text.
+    pub min_letters: usize,
+}
+
+impl Default for TextOptions {
+    fn default() -> Self {
+        TextOptions {
+            edge_threshold: 256,
+            max_stroke_width: 16,
+            min_height: 6,
+            max_height: 256,
+            min_letters: 3,
+        }
+    }
+}
+
+/// How far around each row of letters the text mask reaches, so the
+/// antialiased edges of the letters are protected too.
+const TEXT_PADDING: usize = 2;
+
+/// Marks the rows of letters in `image`, both dark on light and light on
+/// dark.
+///
+/// Letters are found with Epshtein, Ofek and Wexler's stroke width
+/// transform: rays cast across each edge pixel measure how wide the stroke
+/// behind it is, and connected pixels of similar width that are shaped like
+/// letters are kept when enough of them line up in a row.
+pub fn text_mask(image: &DynamicImage, options: &TextOptions) -> GrayImage {
+    let (width, height) = image.dimensions();
+    let strokes = Strokes::new(&image.to_luma8(), options.edge_threshold);
+    let mut mask = GrayImage::new(width, height);
+    for &dark in &[true, false] {
+        let widths = strokes.widths(dark, options.max_stroke_width);
+        let letters = strokes.letters(&widths, options);
+        for (left, top, right, bottom) in rows_of_letters(&letters, options.min_letters) {
+            let right = (right + TEXT_PADDING).min(width as usize - 1);
+            let bottom = (bottom + TEXT_PADDING).min(height as usize - 1);
+            for y in top.saturating_sub(TEXT_PADDING)..=bottom {
+                for x in left.saturating_sub(TEXT_PADDING)..=right {
+                    mask.put_pixel(x as u32, y as u32, Luma([255]));
+                }
+            }
+        }
+    }
+    mask
+}
+
+/// A connected run of similar strokes shaped like a letter.
+struct Letter {
+    left: usize,
+    top: usize,
+    right: usize,
+    bottom: usize,
+    stroke_width: f32,
+}
+
+impl Letter {
+    fn height(&self) -> usize {
+        self.bottom - self.top + 1
+    }
+
+    fn center_y(&self) -> f32 {
+        (self.top + self.bottom) as f32 / 2.0
+    }
+
+    /// Whether `self` and `other` look like neighboring letters of the same
+    /// row of text.
+    fn neighbors(&self, other: &Letter) -> bool {
+        let (short, tall) = ordered(self.height() as f32, other.height() as f32);
+        let (thin, thick) = ordered(self.stroke_width, other.stroke_width);
+        let gap = self.left.max(other.left) as f32 - self.right.min(other.right) as f32;
+        tall <= short * 2.0
+            && thick <= thin * 2.0
+            && (self.center_y() - other.center_y()).abs() <= tall / 2.0
+            && gap <= tall
+    }
+}
+
+fn ordered(a: f32, b: f32) -> (f32, f32) {
+    if a <= b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+/// The bounds of every row of at least `min_letters` neighboring letters.
+fn rows_of_letters(letters: &[Letter], min_letters: usize) -> Vec<(usize, usize, usize, usize)> {
+    fn root(parents: &mut [usize], mut i: usize) -> usize {
+        while parents[i] != i {
+            parents[i] = parents[parents[i]];
+            i = parents[i];
+        }
+        i
+    }
+
+    let mut parents: Vec<usize> = (0..letters.len()).collect();
+    for i in 0..letters.len() {
+        for j in i + 1..letters.len() {
+            if letters[i].neighbors(&letters[j]) {
+                let (a, b) = (root(&mut parents, i), root(&mut parents, j));
+                parents[a] = b;
+            }
         }
     }
+
+    let mut rows: Vec<(usize, (usize, usize, usize, usize))> = vec![];
+    let mut row_of: Vec<Option<usize>> = vec![None; letters.len()];
+    for (i, letter) in letters.iter().enumerate() {
+        let root = root(&mut parents, i);
+        let bounds = (letter.left, letter.top, letter.right, letter.bottom);
+        match row_of[root] {
+            Some(row) => {
+                let (count, (left, top, right, bottom)) = rows[row];
+                rows[row] = (
+                    count + 1,
+                    (
+                        left.min(letter.left),
+                        top.min(letter.top),
+                        right.max(letter.right),
+                        bottom.max(letter.bottom),
+                    ),
+                );
+            }
+            None => {
+                row_of[root] = Some(rows.len());
+                rows.push((1, bounds));
+            }
+        }
+    }
+    rows.into_iter()
+        .filter(|&(count, _)| count >= min_letters)
+        .map(|(_, bounds)| bounds)
+        .collect()
+}
+
+/// The Sobel gradients of an image.
+struct Strokes {
+    width: usize,
+    height: usize,
+    gradients: Vec<(i32, i32)>,
+    threshold: u32,
+}
+
+impl Strokes {
+    fn new(luma: &GrayImage, threshold: u32) -> Self {
+        let (width, height) = (luma.width() as usize, luma.height() as usize);
+        let pixel = |x: usize, y: usize| {
+            let x = x.min(width - 1) as u32;
+            let y = y.min(height - 1) as u32;
+            i32::from(luma.get_pixel(x, y)[0])
+        };
+
+        let mut gradients = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let (left, right) = (x.saturating_sub(1), x + 1);
+                let (up, down) = (y.saturating_sub(1), y + 1);
+                let gx = pixel(right, up) + 2 * pixel(right, y) + pixel(right, down)
+                    - pixel(left, up)
+                    - 2 * pixel(left, y)
+                    - pixel(left, down);
+                let gy = pixel(left, down) + 2 * pixel(x, down) + pixel(right, down)
+                    - pixel(left, up)
+                    - 2 * pixel(x, up)
+                    - pixel(right, up);
+                gradients.push((gx, gy));
+            }
+        }
+
+        Strokes {
+            width,
+            height,
+            gradients,
+            threshold,
+        }
+    }
+
+    /// The edge pixels, thinned to the one strongest pixel across each edge
+    /// so rays don't stop on the near side of their own edge. Ties go to the
+    /// pixel on the stroke, darker when `dark` is set.
+    fn edges(&self, dark: bool) -> Vec<bool> {
+        (0..self.width * self.height)
+            .map(|index| {
+                let magnitude = self.magnitude(index);
+                if magnitude < self.threshold {
+                    return false;
+                }
+                let (dx, dy) = self.direction(index);
+                let (dx, dy) = (dx.round() as isize, dy.round() as isize);
+                let (x, y) = ((index % self.width) as isize, (index / self.width) as isize);
+                let darker = self.index(x - dx, y - dy).map_or(0, |i| self.magnitude(i));
+                let brighter = self.index(x + dx, y + dy).map_or(0, |i| self.magnitude(i));
+                if dark {
+                    magnitude > darker && magnitude >= brighter
+                } else {
+                    magnitude >= darker && magnitude > brighter
+                }
+            })
+            .collect()
+    }
+
+    fn magnitude(&self, index: usize) -> u32 {
+        let (gx, gy) = self.gradients[index];
+        gx.unsigned_abs() + gy.unsigned_abs()
+    }
+
+    /// The unit vector of the gradient at `index`, pointing towards
+    /// brighter pixels.
+    fn direction(&self, index: usize) -> (f64, f64) {
+        let (gx, gy) = self.gradients[index];
+        let (gx, gy) = (f64::from(gx), f64::from(gy));
+        let length = gx.hypot(gy);
+        (gx / length, gy / length)
+    }
+
+    fn index(&self, x: isize, y: isize) -> Option<usize> {
+        if x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height {
+            Some(y as usize * self.width + x as usize)
+        } else {
+            None
+        }
+    }
+
+    /// The width of the stroke each pixel lies on, or infinity off strokes.
+    /// Strokes are darker than their surroundings when `dark` is set.
+    fn widths(&self, dark: bool, max_width: u32) -> Vec<f32> {
+        let mut widths = vec![f32::INFINITY; self.width * self.height];
+        let mut rays = vec![];
+        let edges = self.edges(dark);
+        for start in (0..edges.len()).filter(|&index| edges[index]) {
+            if let Some(ray) = self.ray(start, &edges, dark, max_width) {
+                // Both ends of the ray are on the stroke.
+                let (start, end) = (ray[0], ray[ray.len() - 1]);
+                let dx = (start % self.width) as f32 - (end % self.width) as f32;
+                let dy = (start / self.width) as f32 - (end / self.width) as f32;
+                let width = dx.hypot(dy) + 1.0;
+                for &index in &ray {
+                    widths[index] = widths[index].min(width);
+                }
+                rays.push(ray);
+            }
+        }
+
+        // Rays into the corners of strokes are longer than the strokes are
+        // wide, so each ray is capped at its median width.
+        for ray in &rays {
+            let mut along: Vec<f32> = ray.iter().map(|&index| widths[index]).collect();
+            along.sort_by(f32::total_cmp);
+            let median = along[along.len() / 2];
+            for &index in ray {
+                widths[index] = widths[index].min(median);
+            }
+        }
+        widths
+    }
+
+    /// The pixels from the edge at `start` across its stroke to the facing
+    /// edge, if there is one within `max_width` pixels.
+    fn ray(&self, start: usize, edges: &[bool], dark: bool, max_width: u32) -> Option<Vec<usize>> {
+        let sign = if dark { -1.0 } else { 1.0 };
+        let (dx, dy) = self.direction(start);
+        let (dx, dy) = (dx * sign, dy * sign);
+        let (x, y) = ((start % self.width) as f64, (start / self.width) as f64);
+
+        let mut ray = vec![start];
+        // Half pixel steps so diagonal rays don't skip over edges.
+        for step in 1..=max_width * 2 {
+            let distance = f64::from(step) / 2.0;
+            let index = self.index(
+                (x + dx * distance).round() as isize,
+                (y + dy * distance).round() as isize,
+            )?;
+            if ray.last() == Some(&index) {
+                continue;
+            }
+            ray.push(index);
+            if edges[index] {
+                // The facing edge must point back within 60 degrees, which lets
+                // rays from the corners of strokes count.
+                let (other_dx, other_dy) = self.direction(index);
+                let dot = dx * other_dx * sign + dy * other_dy * sign;
+                return if dot < -(PI / 3.0).cos() {
+                    Some(ray)
+                } else {
+                    None
+                };
+            }
+        }
+        None
+    }
+
+    /// The connected groups of pixels with similar stroke widths that are
+    /// shaped like letters.
+    fn letters(&self, widths: &[f32], options: &TextOptions) -> Vec<Letter> {
+        let mut seen = vec![false; widths.len()];
+        let mut letters = vec![];
+        for start in 0..widths.len() {
+            if seen[start] || widths[start].is_infinite() {
+                continue;
+            }
+            seen[start] = true;
+            let mut stack = vec![start];
+            let mut members = vec![];
+            while let Some(index) = stack.pop() {
+                members.push(index);
+                let (x, y) = ((index % self.width) as isize, (index / self.width) as isize);
+                for (nx, ny) in
+                    (y - 1..=y + 1).flat_map(|ny| (x - 1..=x + 1).map(move |nx| (nx, ny)))
+                {
+                    if let Some(next) = self.index(nx, ny) {
+                        let (thin, thick) = ordered(widths[index], widths[next]);
+                        if !seen[next] && thick.is_finite() && thick <= thin * 3.0 {
+                            seen[next] = true;
+                            stack.push(next);
+                        }
+                    }
+                }
+            }
+            if let Some(letter) = self.letter(&members, widths, options) {
+                letters.push(letter);
+            }
+        }
+        letters
+    }
+
+    /// The letter made of `members`, if they're shaped like one.
+    fn letter(&self, members: &[usize], widths: &[f32], options: &TextOptions) -> Option<Letter> {
+        let xs = members.iter().map(|&index| index % self.width);
+        let ys = members.iter().map(|&index| index / self.width);
+        let letter_width = xs.clone().max()? - xs.clone().min()? + 1;
+        let letter_height = ys.clone().max()? - ys.clone().min()? + 1;
+
+        let mut strokes: Vec<f32> = members.iter().map(|&index| widths[index]).collect();
+        strokes.sort_by(f32::total_cmp);
+        let stroke_width = strokes[strokes.len() / 2];
+        let mean = strokes.iter().sum::<f32>() / strokes.len() as f32;
+        let variance = strokes
+            .iter()
+            .map(|width| (width - mean).powi(2))
+            .sum::<f32>()
+            / strokes.len() as f32;
+        let diameter = (letter_width as f32).hypot(letter_height as f32);
+
+        let shaped = (options.min_height as usize..=options.max_height as usize)
+            .contains(&letter_height)
+            && letter_width <= letter_height * 10
+            && letter_height <= letter_width * 10
+            && variance.sqrt() <= mean / 2.0
+            && diameter <= stroke_width * 10.0;
+        if shaped {
+            Some(Letter {
+                left: xs.clone().min()?,
+                top: ys.clone().min()?,
+                right: xs.max()?,
+                bottom: ys.max()?,
+                stroke_width,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+fn square_gradient(pixel1: &Rgba<u8>, pixel2: &Rgba<u8>) -> u32 {
+    let mut sum = 0;
+    for (&a, &b) in pixel1.channels().iter().zip(pixel2.channels()) {
+        // Values are cast to u32 to prevent overflow when summing
+        let a: u32 = a.into();
+        let b: u32 = b.into();
+        if a >= b {
+            sum += (a - b).pow(2);
+        } else {
+            sum += (b - a).pow(2);
+        }
+    }
+    sum
+}
+
+fn absolute_gradient(pixel1: &Rgba<u8>, pixel2: &Rgba<u8>) -> u32 {
+    pixel1
+        .channels()
+        .iter()
+        .zip(pixel2.channels())
+        .map(|(&a, &b)| (i32::from(a) - i32::from(b)).unsigned_abs())
+        .sum()
 }
 
 fn average_pixel_data(pixel1: &[u8; 4], pixel2: &[u8; 4]) -> [u8; 4] {
@@ -1,9 +1,13 @@
 use std::cell::Cell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::mem;
 use std::rc::{Rc, Weak};
 
 use image::{DynamicImage, GenericImageView};
 
-use crate::energy::PixelEnergyPoint;
+use crate::carve::Direction;
+use crate::energy::{Boundary, PixelEnergyPoint};
 
 type StrongPosition = Rc<Cell<(usize, usize)>>;
 type WeakPosition = Weak<Cell<(usize, usize)>>;
@@ -19,79 +23,86 @@ impl Token {
     }
 }
 
-#[derive(Clone)]
-struct Item<T> {
-    val: T,
-    pos: Option<StrongPosition>,
+/// A grid of points stored in image row order, viewed along the direction
+/// seams are carved in.
+///
+/// Every coordinate API takes `x` across the seams and `y` along them, so a
+/// vertical view matches image coordinates and a horizontal view swaps them.
+/// Changing the view is free; the points are never moved.
+///
+/// Only points with a token are tracked as they move, in a table beside the
+/// points keyed by where each one is stored, so untracked points cost no
+/// more than the values themselves.
+pub struct Grid<T> {
+    points: Vec<Vec<T>>,
+    tracked: HashMap<(usize, usize), StrongPosition>,
+    direction: Direction,
 }
 
-impl<T> Item<T> {
-    fn update_pos(&mut self, x: usize, y: usize) {
-        if let Some(ref mut pos) = self.pos {
-            pos.set((x, y));
+impl<T> Grid<T> {
+    pub fn new(points: Vec<Vec<T>>) -> Self {
+        let direction = Direction::Vertical;
+        Self {
+            points,
+            tracked: HashMap::new(),
+            direction,
         }
     }
-}
 
-impl<T: Clone> Item<T> {
-    fn clone_unindexed(&self) -> Item<T> {
-        Item {
-            val: self.val.clone(),
-            pos: None,
-        }
+    /// The direction seams run through the grid's coordinate APIs.
+    pub fn direction(&self) -> Direction {
+        self.direction
     }
-}
 
-pub struct Grid<T> {
-    points: Vec<Vec<Item<T>>>,
-    rotated: bool,
-}
+    pub fn set_direction(&mut self, direction: Direction) {
+        self.direction = direction;
+    }
 
-impl<T> Grid<T> {
-    pub fn new(points: Vec<Vec<T>>) -> Self {
-        let points = Grid::convert_container(points);
-        let rotated = false;
-        Self { points, rotated }
+    /// The size of the grid in image coordinates, whatever the direction.
+    pub fn dimensions(&self) -> (usize, usize) {
+        let rows = self.points.len();
+        let cols = self.points.first().map_or(0, Vec::len);
+        (cols, rows)
     }
 
     pub fn height(&self) -> usize {
-        self.points.len()
+        let (width, height) = self.dimensions();
+        match self.direction {
+            Direction::Vertical => height,
+            Direction::Horizontal => width,
+        }
     }
 
     pub fn width(&self) -> usize {
-        self.points[0].len()
+        let (width, height) = self.dimensions();
+        match self.direction {
+            Direction::Vertical => width,
+            Direction::Horizontal => height,
+        }
     }
 
     pub fn get(&self, x: usize, y: usize) -> &T {
-        &self.points[y][x].val
+        let (row, col) = self.index(x, y);
+        &self.points[row][col]
     }
 
+    /// The points left of, right of, above and below `(x, y)`, wrapping
+    /// around at the edges.
     pub fn get_adjacent(&self, x: usize, y: usize) -> (&T, &T, &T, &T) {
-        let left = if x == 0 {
-            self.get(self.width() - 1, y)
-        } else {
-            self.get(x - 1, y)
-        };
-
-        let right = if x == self.width() - 1 {
-            self.get(0, y)
-        } else {
-            self.get(x + 1, y)
-        };
-
-        let up = if y == 0 {
-            self.get(x, self.height() - 1)
-        } else {
-            self.get(x, y - 1)
-        };
-
-        let down = if y == self.height() - 1 {
-            self.get(x, 0)
-        } else {
-            self.get(x, y + 1)
-        };
-
-        (left, right, up, down)
+        self.get_adjacent_within(x, y, Boundary::Wrap)
+    }
+
+    /// Like `get_adjacent`, with the neighbors of edge points found as
+    /// `boundary` says.
+    pub fn get_adjacent_within(&self, x: usize, y: usize, boundary: Boundary) -> (&T, &T, &T, &T) {
+        let (x_left, x_right) = boundary.neighbors(x, self.width());
+        let (y_up, y_down) = boundary.neighbors(y, self.height());
+        (
+            self.get(x_left, y),
+            self.get(x_right, y),
+            self.get(x, y_up),
+            self.get(x, y_down),
+        )
     }
 
     pub fn get_parents(&self, x: usize, y: usize) -> [Option<&T>; 3] {
@@ -114,27 +125,28 @@ impl<T> Grid<T> {
         parents
     }
 
-    pub fn iter_parents(&self, mut x: usize, mut y: usize) -> ParentIter<T> {
-        let remaining;
+    pub fn iter_parents(&self, x: usize, y: usize) -> ParentIter<'_, T> {
+        self.iter_parents_within(x, y, 1)
+    }
+
+    /// Iterates over the points in the row above that are at most `window`
+    /// columns away from `x`, from left to right.
+    pub fn iter_parents_within(&self, x: usize, y: usize, window: usize) -> ParentIter<'_, T> {
         if y == 0 {
-            remaining = 0;
-        } else {
-            y -= 1;
-            if x == 0 {
-                remaining = 2;
-            } else if x == self.width() - 1 {
-                x -= 1;
-                remaining = 2;
-            } else {
-                x -= 1;
-                remaining = 3;
-            }
+            return ParentIter {
+                x,
+                y,
+                remaining: 0,
+                grid: self,
+            };
         }
 
+        let first = x.saturating_sub(window);
+        let last = x.saturating_add(window).min(self.width() - 1);
         ParentIter {
-            x,
-            y,
-            remaining,
+            x: first,
+            y: y - 1,
+            remaining: last - first + 1,
             grid: self,
         }
     }
@@ -157,10 +169,19 @@ impl<T> Grid<T> {
         parents
     }
 
-    pub fn iter_parents_with_coords(&self, x: usize, y: usize) -> ParentCoordIter<T> {
+    pub fn iter_parents_with_coords(&self, x: usize, y: usize) -> ParentCoordIter<'_, T> {
         self.iter_parents(x, y).coordinate()
     }
 
+    pub fn iter_parents_with_coords_within(
+        &self,
+        x: usize,
+        y: usize,
+        window: usize,
+    ) -> ParentCoordIter<'_, T> {
+        self.iter_parents_within(x, y, window).coordinate()
+    }
+
     pub fn get_row(&self, y: usize) -> Vec<&T> {
         let mut row = vec![];
         for x in 0..self.width() {
@@ -178,94 +199,197 @@ impl<T> Grid<T> {
     }
 
     pub fn get_mut(&mut self, x: usize, y: usize) -> &mut T {
-        &mut self.points[y][x].val
+        let (row, col) = self.index(x, y);
+        &mut self.points[row][col]
     }
 
-    pub fn rotate(&mut self) {
-        self.rotated = !self.rotated;
-
-        let mut rows = vec![];
-        'outer: loop {
-            let mut row = vec![];
-            for cur in &mut self.points {
-                match cur.pop() {
-                    Some(val) => row.push(val),
-                    None => break 'outer,
-                }
-            }
-            rows.push(row);
-        }
-        rows.reverse();
-
-        self.points = rows;
+    /// Iterates in image row order, whatever the direction.
+    pub fn iter<'a>(&'a self) -> Box<dyn Iterator<Item = &'a T> + 'a> {
+        Box::new(self.points.iter().flat_map(|row| row.iter()))
     }
 
-    pub fn is_rotated(&self) -> bool {
-        self.rotated
+    pub fn iter_mut<'a>(&'a mut self) -> Box<dyn Iterator<Item = &'a mut T> + 'a> {
+        Box::new(self.points.iter_mut().flat_map(|row| row.iter_mut()))
     }
 
-    pub fn iter<'a>(&'a self) -> Box<dyn Iterator<Item = &'a T> + 'a> {
+    /// Iterates in image row order with image coordinates, whatever the
+    /// direction.
+    pub fn coord_iter<'a>(&'a self) -> Box<dyn Iterator<Item = (usize, usize, &'a T)> + 'a> {
         Box::new(
             self.points
                 .iter()
-                .flat_map(|row| row.iter())
-                .map(|item| &item.val),
-        )
-    }
-
-    pub fn iter_mut<'a>(&'a mut self) -> Box<dyn Iterator<Item = &'a mut T> + 'a> {
-        Box::new(
-            self.points
-                .iter_mut()
-                .flat_map(|row| row.iter_mut())
-                .map(|item| &mut item.val),
+                .enumerate()
+                .flat_map(|(y, row)| row.iter().enumerate().map(move |(x, val)| (x, y, val))),
         )
     }
 
-    pub fn coord_iter<'a>(&'a self) -> Box<dyn Iterator<Item = (usize, usize, &'a T)> + 'a> {
-        let rotated = self.is_rotated();
-        Box::new(self.points.iter().enumerate().flat_map(move |(y, row)| {
-            row.iter().enumerate().map(move |(x, item)| {
-                if !rotated {
-                    (x, y, &item.val)
-                } else {
-                    (y, x, &item.val)
-                }
-            })
-        }))
-    }
-
     pub fn coord_iter_mut<'a>(
         &'a mut self,
     ) -> Box<dyn Iterator<Item = (usize, usize, &'a mut T)> + 'a> {
-        let rotated = self.is_rotated();
         Box::new(
             self.points
                 .iter_mut()
                 .enumerate()
-                .flat_map(move |(y, row)| {
-                    row.iter_mut().enumerate().map(move |(x, item)| {
-                        if !rotated {
-                            (x, y, &mut item.val)
-                        } else {
-                            (y, x, &mut item.val)
-                        }
-                    })
-                }),
+                .flat_map(|(y, row)| row.iter_mut().enumerate().map(move |(x, val)| (x, y, val))),
         )
     }
 
+    /// Approximate heap memory held by the grid, in bytes.
+    pub fn memory_footprint(&self) -> usize {
+        let rows = self.points.capacity() * mem::size_of::<Vec<T>>();
+        let items: usize = self.points.iter().map(|row| row.capacity()).sum();
+        // Each tracked point has a slot in the table and a shared position.
+        let slot = mem::size_of::<((usize, usize), StrongPosition)>() + 1;
+        let position = 2 * mem::size_of::<usize>() + mem::size_of::<Cell<(usize, usize)>>();
+        rows + items * mem::size_of::<T>()
+            + self.tracked.capacity() * slot
+            + self.tracked.len() * position
+    }
+
+    /// Removes the last column of the view, which is the last row of the
+    /// image when the view is horizontal.
     pub fn remove_last_column(&mut self) {
-        for row in &mut self.points {
-            row.pop()
-                .expect("Attempted to remove column from empty grid");
+        let last = self.width() - 1;
+        match self.direction {
+            Direction::Vertical => {
+                for row in &mut self.points {
+                    row.pop()
+                        .expect("Attempted to remove column from empty grid");
+                }
+            }
+            Direction::Horizontal => {
+                self.points
+                    .pop()
+                    .expect("Attempted to remove column from empty grid");
+            }
         }
+        self.move_tracked(|x, y| if x == last { None } else { Some((x, y)) });
+    }
+
+    /// Removes one point from every row of the view, at `seam[y]` in row `y`,
+    /// and returns them in row order. The points after each removed one
+    /// move back to fill the gap, and the view ends up a column narrower.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `seam` doesn't have an entry for each row, or one is out of
+    /// bounds.
+    pub fn remove_seam(&mut self, seam: &[usize]) -> Vec<T> {
+        assert_eq!(self.height(), seam.len(), "Seam must cover every row");
+
+        let removed = match self.direction {
+            Direction::Vertical => seam
+                .iter()
+                .zip(self.points.iter_mut())
+                .map(|(&x, row)| row.remove(x))
+                .collect(),
+            Direction::Horizontal => {
+                // Each view row is a column of storage, so the removed point is
+                // swapped down to the last row, which is then dropped.
+                let last = self.points.len() - 1;
+                for (col, &x) in seam.iter().enumerate() {
+                    assert!(x <= last, "Seam is outside of the grid");
+                    for row in x..last {
+                        let (upper, lower) = self.points.split_at_mut(row + 1);
+                        mem::swap(&mut upper[row][col], &mut lower[0][col]);
+                    }
+                }
+                self.points
+                    .pop()
+                    .expect("Attempted to remove seam from empty grid")
+            }
+        };
+        self.move_tracked(|x, y| match x.cmp(&seam[y]) {
+            Ordering::Less => Some((x, y)),
+            Ordering::Equal => None,
+            Ordering::Greater => Some((x - 1, y)),
+        });
+        removed
+    }
+
+    /// Inserts `values[y]` into every row `y` of the view at `seam[y]`,
+    /// moving the points from there on forward. The view ends up a column
+    /// wider.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `seam` or `values` doesn't have an entry for each row, or a
+    /// position is past the end of its row.
+    pub fn insert_seam(&mut self, seam: &[usize], values: Vec<T>) {
+        assert_eq!(self.height(), seam.len(), "Seam must cover every row");
+        assert_eq!(seam.len(), values.len(), "Seam must have a value per row");
+
+        match self.direction {
+            Direction::Vertical => {
+                for ((&x, val), row) in seam.iter().zip(values).zip(self.points.iter_mut()) {
+                    row.insert(x, val);
+                }
+            }
+            Direction::Horizontal => {
+                // The values start out as a new last row of storage and are
+                // swapped up into place.
+                let last = self.points.len();
+                assert!(
+                    seam.iter().all(|&x| x <= last),
+                    "Seam is outside of the grid"
+                );
+                self.points.push(values);
+                for (col, &x) in seam.iter().enumerate() {
+                    for row in (x + 1..=last).rev() {
+                        let (upper, lower) = self.points.split_at_mut(row);
+                        mem::swap(&mut upper[row - 1][col], &mut lower[0][col]);
+                    }
+                }
+            }
+        }
+        self.move_tracked(|x, y| {
+            if x < seam[y] {
+                Some((x, y))
+            } else {
+                Some((x + 1, y))
+            }
+        });
     }
 
+    /// Makes room for `additional` more columns of the view, so inserting
+    /// seams doesn't leave rows with spare capacity.
+    pub fn reserve_columns(&mut self, additional: usize) {
+        match self.direction {
+            Direction::Vertical => {
+                for row in &mut self.points {
+                    row.reserve_exact(additional);
+                }
+            }
+            Direction::Horizontal => self.points.reserve_exact(additional),
+        }
+    }
+
+    /// Keeps `len` columns of the view starting at column `start`, dropping
+    /// the rest. Every outstanding token is invalidated.
+    pub fn crop_columns(&mut self, start: usize, len: usize) {
+        match self.direction {
+            Direction::Vertical => {
+                for row in &mut self.points {
+                    row.drain(..start);
+                    row.truncate(len);
+                }
+            }
+            Direction::Horizontal => {
+                self.points.drain(..start);
+                self.points.truncate(len);
+            }
+        }
+
+        self.tracked = HashMap::new();
+    }
+
+    /// A token that follows the point at `(x, y)` as seams move it, until
+    /// the point leaves the grid. Any older token for the point dies.
     pub fn make_token(&mut self, x: usize, y: usize) -> Token {
-        let master = Rc::new(Cell::new(self.rotate_point((x, y))));
+        let (row, col) = self.index(x, y);
+        let master = Rc::new(Cell::new((row, col)));
         let position = Rc::downgrade(&master);
-        self.points[y][x].pos = Some(master);
+        self.tracked.insert((row, col), master);
         Token { position }
     }
 
@@ -286,50 +410,57 @@ impl<T> Grid<T> {
     }
 
     pub fn trade(&self, token: Token) -> Option<&T> {
-        token.try_get().map(|(x, y)| &self.get_internal(x, y).val)
+        token.try_get().map(|(row, col)| &self.points[row][col])
     }
 
     pub fn trade_mut(&mut self, token: Token) -> Option<&mut T> {
         token
             .try_get()
-            .map(move |(x, y)| &mut self.get_mut_internal(x, y).val)
+            .map(move |(row, col)| &mut self.points[row][col])
     }
 
     pub fn get_token_adjacent(&self, token: &Token) -> Option<(&T, &T, &T, &T)> {
-        token
-            .try_get()
-            .map(|point| self.rotate_point(point))
+        self.get_token_point(token)
             .map(|(x, y)| self.get_adjacent(x, y))
     }
 
-    fn rotate_point(&self, point: (usize, usize)) -> (usize, usize) {
-        if !self.is_rotated() {
-            point
-        } else {
-            (point.1, point.0)
-        }
+    /// Where the token's point is in the view, if it's still in the grid.
+    pub fn get_token_point(&self, token: &Token) -> Option<(usize, usize)> {
+        token.try_get().map(|(row, col)| self.view_point(row, col))
     }
 
-    fn convert_container(points: Vec<Vec<T>>) -> Vec<Vec<Item<T>>> {
-        points
-            .into_iter()
-            .map(|row| row.into_iter().map(|val| Item { val, pos: None }).collect())
-            .collect()
+    /// Maps view coordinates to the row and column they're stored at. This is
+    /// the only place the direction of the view is applied.
+    fn index(&self, x: usize, y: usize) -> (usize, usize) {
+        match self.direction {
+            Direction::Vertical => (y, x),
+            Direction::Horizontal => (x, y),
+        }
     }
 
-    fn get_internal(&self, x: usize, y: usize) -> &Item<T> {
-        if !self.is_rotated() {
-            &self.points[y][x]
-        } else {
-            &self.points[x][y]
+    /// The inverse of `index`.
+    fn view_point(&self, row: usize, col: usize) -> (usize, usize) {
+        match self.direction {
+            Direction::Vertical => (col, row),
+            Direction::Horizontal => (row, col),
         }
     }
 
-    fn get_mut_internal(&mut self, x: usize, y: usize) -> &mut Item<T> {
-        if !self.is_rotated() {
-            &mut self.points[y][x]
-        } else {
-            &mut self.points[x][y]
+    /// Moves every tracked point to where `to` says its view coordinates
+    /// went, or drops it if `to` returns `None`. Points whose tokens are all
+    /// gone stop being tracked.
+    fn move_tracked(&mut self, to: impl Fn(usize, usize) -> Option<(usize, usize)>) {
+        let tracked = mem::take(&mut self.tracked);
+        for ((row, col), position) in tracked {
+            if Rc::weak_count(&position) == 0 {
+                continue;
+            }
+            let (x, y) = self.view_point(row, col);
+            if let Some((x, y)) = to(x, y) {
+                let index = self.index(x, y);
+                position.set(index);
+                self.tracked.insert(index, position);
+            }
         }
     }
 }
@@ -337,55 +468,70 @@ impl<T> Grid<T> {
 impl<T: Clone> Grid<T> {
     pub fn shift_row_left_from_point(&mut self, x: usize, y: usize) {
         for x in x..(self.width() - 1) {
-            let mut clone = self.points[y][x + 1].clone();
-            if !self.is_rotated() {
-                clone.update_pos(x, y);
+            let clone = self.get(x + 1, y).clone();
+            *self.get_mut(x, y) = clone;
+        }
+        self.move_tracked(|tracked_x, tracked_y| {
+            if tracked_y != y || tracked_x < x {
+                Some((tracked_x, tracked_y))
+            } else if tracked_x == x {
+                None
             } else {
-                clone.update_pos(y, x);
+                Some((tracked_x - 1, tracked_y))
             }
-            self.points[y][x] = clone;
-        }
+        });
     }
 
     pub fn shift_row_right_from_point(&mut self, x: usize, y: usize) {
+        let last = self.width() - 1;
         for x in (x + 1..self.width()).rev() {
-            let mut clone = self.points[y][x - 1].clone();
-            if !self.is_rotated() {
-                clone.update_pos(x, y);
+            let clone = self.get(x - 1, y).clone();
+            *self.get_mut(x, y) = clone;
+        }
+        self.move_tracked(|tracked_x, tracked_y| {
+            if tracked_y != y || tracked_x < x {
+                Some((tracked_x, tracked_y))
+            } else if tracked_x == last {
+                None
             } else {
-                clone.update_pos(y, x);
+                Some((tracked_x + 1, tracked_y))
             }
-            self.points[y][x] = clone;
-        }
+        });
     }
 
+    /// Repeats the last column of the view, which is the last row of the
+    /// image when the view is horizontal.
     pub fn add_last_column(&mut self) {
-        for row in &mut self.points {
-            let clone = row
-                .last()
-                .expect("Attempted to get last from empty grid")
-                .clone();
-            row.push(clone);
-        }
-    }
-
-    fn clone_points_without_positions(&self) -> Vec<Vec<Item<T>>> {
-        let mut rows = vec![];
-        for row in &self.points {
-            let new_row = row.iter().map(|item| item.clone_unindexed()).collect();
-            rows.push(new_row);
+        match self.direction {
+            Direction::Vertical => {
+                for row in &mut self.points {
+                    let clone = row
+                        .last()
+                        .expect("Attempted to get last from empty grid")
+                        .clone();
+                    row.push(clone);
+                }
+            }
+            Direction::Horizontal => {
+                let clone = self
+                    .points
+                    .last()
+                    .expect("Attempted to get last from empty grid")
+                    .clone();
+                self.points.push(clone);
+            }
         }
-        rows
     }
 }
 
-// Manually implementing clone prevents cloned grids from updating their
-// parent's tokens via Rc's that would otherwise get cloned too.
+// Manually implementing clone keeps cloned grids from updating their
+// parent's tokens through the positions they would otherwise share.
 impl<T: Clone> Clone for Grid<T> {
     fn clone(&self) -> Self {
         Self {
-            points: self.clone_points_without_positions(),
-            rotated: self.rotated,
+            points: self.points.clone(),
+            tracked: HashMap::new(),
+            direction: self.direction,
         }
     }
 }
@@ -394,13 +540,13 @@ impl<'a> From<&'a DynamicImage> for Grid<PixelEnergyPoint> {
     fn from(image: &'a DynamicImage) -> Self {
         let (width, height) = image.dimensions();
 
-        let mut rows = vec![];
+        let mut rows = Vec::with_capacity(height as usize);
         for y in 0..height {
-            let mut row = vec![];
+            let mut row = Vec::with_capacity(width as usize);
             for x in 0..width {
                 let pixel = image.get_pixel(x, y);
                 let mut pep: PixelEnergyPoint = pixel.into();
-                pep.original_position = (x as usize, y as usize);
+                pep.original_index = y * width + x;
                 row.push(pep);
             }
             rows.push(row);
@@ -413,7 +559,7 @@ impl<'a> From<&'a DynamicImage> for Grid<PixelEnergyPoint> {
 pub struct ParentIter<'a, T: 'a> {
     x: usize,
     y: usize,
-    remaining: u32,
+    remaining: usize,
     grid: &'a Grid<T>,
 }
 
@@ -0,0 +1,272 @@
+use std::any::Any;
+
+use image::{Bgr, Bgra, DynamicImage, ImageBuffer, Luma, LumaA, Pixel, Primitive, Rgb, Rgba};
+
+use crate::carve::{Direction, Interpolation};
+use crate::grid::Grid;
+
+/// Values that can be blended when a seam is inserted between them.
+pub trait Average {
+    fn average(&self, other: &Self) -> Self;
+}
+
+macro_rules! impl_average_for_integers {
+    ($($t:ty),*) => {$(
+        impl Average for $t {
+            // Halving first keeps the sum from overflowing.
+            fn average(&self, other: &Self) -> Self {
+                (self >> 1) + (other >> 1) + (self & other & 1)
+            }
+        }
+    )*};
+}
+
+impl_average_for_integers!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize);
+
+impl Average for f32 {
+    fn average(&self, other: &Self) -> Self {
+        (self + other) / 2.0
+    }
+}
+
+impl Average for f64 {
+    fn average(&self, other: &Self) -> Self {
+        (self + other) / 2.0
+    }
+}
+
+macro_rules! impl_average_for_pixels {
+    ($($pixel:ident),*) => {$(
+        impl<T: Primitive + Average + 'static> Average for $pixel<T> {
+            fn average(&self, other: &Self) -> Self {
+                self.map2(other, |a, b| a.average(&b))
+            }
+        }
+    )*};
+}
+
+impl_average_for_pixels!(Luma, LumaA, Rgb, Rgba, Bgr, Bgra);
+
+/// Per-pixel data, such as a depth map or segmentation labels, that has the
+/// same seams removed and inserted as the image it's carved with.
+#[derive(Clone)]
+pub struct Layer<T> {
+    grid: Grid<T>,
+    average: Option<fn(&T, &T) -> T>,
+}
+
+impl<T: Clone> Layer<T> {
+    /// A layer that fills inserted seams by repeating the value to their
+    /// left, which suits values like labels that can't be blended.
+    pub fn new(grid: Grid<T>) -> Self {
+        Layer {
+            grid,
+            average: None,
+        }
+    }
+
+    /// A layer that fills inserted seams with `average` of the values on
+    /// either side.
+    pub fn with_average(grid: Grid<T>, average: fn(&T, &T) -> T) -> Self {
+        Layer {
+            grid,
+            average: Some(average),
+        }
+    }
+
+    pub fn grid(&self) -> &Grid<T> {
+        &self.grid
+    }
+
+    pub fn into_grid(self) -> Grid<T> {
+        self.grid
+    }
+
+    pub fn interpolation(&self) -> Interpolation {
+        match self.average {
+            Some(_) => Interpolation::Average,
+            None => Interpolation::Duplicate,
+        }
+    }
+}
+
+impl<T: Average + Clone> Layer<T> {
+    pub fn with_interpolation(grid: Grid<T>, interpolation: Interpolation) -> Self {
+        match interpolation {
+            Interpolation::Average => Layer::with_average(grid, T::average),
+            Interpolation::Duplicate => Layer::new(grid),
+        }
+    }
+}
+
+impl<P: Pixel + Average + 'static> Layer<P> {
+    pub fn from_image(
+        image: &ImageBuffer<P, Vec<P::Subpixel>>,
+        interpolation: Interpolation,
+    ) -> Self {
+        let rows = image.rows().map(|row| row.copied().collect()).collect();
+        Layer::with_interpolation(Grid::new(rows), interpolation)
+    }
+
+    /// The layer as carved so far.
+    pub fn to_image(&self) -> ImageBuffer<P, Vec<P::Subpixel>> {
+        let (width, height) = self.grid.dimensions();
+        let mut image = ImageBuffer::new(width as u32, height as u32);
+        for (x, y, &pixel) in self.grid.coord_iter() {
+            image.put_pixel(x as u32, y as u32, pixel);
+        }
+        image
+    }
+}
+
+/// The operations a carver applies to each of its layers, whatever they hold.
+pub(crate) trait CarvedLayer {
+    fn dimensions(&self) -> (usize, usize);
+    fn set_direction(&mut self, direction: Direction);
+    fn remove_seam(&mut self, seam: &[usize]);
+    /// Inserts a value after `seam[y]` in every row `y` of the view.
+    fn insert_seam_after(&mut self, seam: &[usize]);
+    fn crop_columns(&mut self, start: usize, len: usize);
+    fn memory_footprint(&self) -> usize;
+    fn box_clone(&self) -> Box<dyn CarvedLayer>;
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T: Clone + 'static> CarvedLayer for Layer<T> {
+    fn dimensions(&self) -> (usize, usize) {
+        self.grid.dimensions()
+    }
+
+    fn set_direction(&mut self, direction: Direction) {
+        self.grid.set_direction(direction);
+    }
+
+    fn remove_seam(&mut self, seam: &[usize]) {
+        self.grid.remove_seam(seam);
+    }
+
+    fn insert_seam_after(&mut self, seam: &[usize]) {
+        let width = self.grid.width();
+        let values = seam
+            .iter()
+            .enumerate()
+            .map(|(y, &x)| {
+                let left = self.grid.get(x, y);
+                match self.average {
+                    Some(average) => average(left, self.grid.get((x + 1).min(width - 1), y)),
+                    None => left.clone(),
+                }
+            })
+            .collect();
+
+        let after: Vec<usize> = seam.iter().map(|&x| x + 1).collect();
+        self.grid.insert_seam(&after, values);
+    }
+
+    fn crop_columns(&mut self, start: usize, len: usize) {
+        self.grid.crop_columns(start, len);
+    }
+
+    fn memory_footprint(&self) -> usize {
+        self.grid.memory_footprint()
+    }
+
+    fn box_clone(&self) -> Box<dyn CarvedLayer> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl Clone for Box<dyn CarvedLayer> {
+    fn clone(&self) -> Self {
+        self.box_clone()
+    }
+}
+
+macro_rules! dynamic_layer {
+    ($($variant:ident($pixel:ty)),*) => {
+        /// An image layer that keeps the pixel type it was given.
+        #[derive(Clone)]
+        pub(crate) enum DynamicLayer {
+            $($variant(Layer<$pixel>)),*
+        }
+
+        impl DynamicLayer {
+            pub(crate) fn new(image: &DynamicImage, interpolation: Interpolation) -> Self {
+                match image {
+                    $(DynamicImage::$variant(image) => {
+                        DynamicLayer::$variant(Layer::from_image(image, interpolation))
+                    })*
+                }
+            }
+
+            pub(crate) fn to_image(&self) -> DynamicImage {
+                match self {
+                    $(DynamicLayer::$variant(layer) => DynamicImage::$variant(layer.to_image())),*
+                }
+            }
+
+            fn layer(&self) -> &dyn CarvedLayer {
+                match self {
+                    $(DynamicLayer::$variant(layer) => layer),*
+                }
+            }
+
+            fn layer_mut(&mut self) -> &mut dyn CarvedLayer {
+                match self {
+                    $(DynamicLayer::$variant(layer) => layer),*
+                }
+            }
+        }
+    };
+}
+
+dynamic_layer!(
+    ImageLuma8(Luma<u8>),
+    ImageLumaA8(LumaA<u8>),
+    ImageRgb8(Rgb<u8>),
+    ImageRgba8(Rgba<u8>),
+    ImageBgr8(Bgr<u8>),
+    ImageBgra8(Bgra<u8>),
+    ImageLuma16(Luma<u16>),
+    ImageLumaA16(LumaA<u16>),
+    ImageRgb16(Rgb<u16>),
+    ImageRgba16(Rgba<u16>)
+);
+
+impl CarvedLayer for DynamicLayer {
+    fn dimensions(&self) -> (usize, usize) {
+        self.layer().dimensions()
+    }
+
+    fn set_direction(&mut self, direction: Direction) {
+        self.layer_mut().set_direction(direction);
+    }
+
+    fn remove_seam(&mut self, seam: &[usize]) {
+        self.layer_mut().remove_seam(seam);
+    }
+
+    fn insert_seam_after(&mut self, seam: &[usize]) {
+        self.layer_mut().insert_seam_after(seam);
+    }
+
+    fn crop_columns(&mut self, start: usize, len: usize) {
+        self.layer_mut().crop_columns(start, len);
+    }
+
+    fn memory_footprint(&self) -> usize {
+        self.layer().memory_footprint()
+    }
+
+    fn box_clone(&self) -> Box<dyn CarvedLayer> {
+        Box::new(self.clone())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
@@ -1,9 +1,26 @@
 #[macro_use]
 extern crate clap;
 
+pub mod bitmap;
 pub mod carve;
+pub mod cascade;
 pub mod config;
+pub mod crop;
+pub mod debug;
+pub mod encode;
 pub mod energy;
 pub mod grid;
+pub mod layer;
+pub mod lines;
+pub mod metadata;
+pub mod progress;
+pub mod protect;
+pub mod resize;
+pub mod stats;
+pub mod tiff;
+pub mod tiled;
+
+pub use crate::resize::{resize, ResizeOptions, ResizeOutput, ResizeStats};
+pub use crate::tiled::{resize_tiled, TileOptions};
 
 pub type BoxResult<T> = Result<T, Box<dyn std::error::Error>>;
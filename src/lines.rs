@@ -0,0 +1,306 @@
+use std::f64::consts::PI;
+
+use image::{DynamicImage, GrayImage};
+
+/// Angles tried by the Hough transform, one per degree.
+const THETA_STEPS: usize = 180;
+
+/// How far either side of a line, in pixels, an edge pixel may be and still
+/// count as part of it.
+const LINE_TOLERANCE: i64 = 1;
+
+/// How far either side of a found segment edge pixels are claimed by it, so
+/// the other side of a thick edge isn't found as a second line.
+const CLAIM_RADIUS: i64 = 2;
+
+/// Settings for finding straight lines to keep straight.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LineOptions {
+    /// The Sobel gradient, summed over both axes, a pixel needs to count as
+    /// an edge.
+    pub edge_threshold: u32,
+    /// The shortest segment kept, in pixels.
+    pub min_length: u32,
+    /// The longest run of missing edge pixels a segment may bridge.
+    pub max_gap: u32,
+    /// The most segments kept, strongest first.
+    pub max_lines: usize,
+    /// Energy added to every pixel of a segment.
+    pub weight: u32,
+}
+
+impl Default for LineOptions {
+    fn default() -> Self {
+        LineOptions {
+            edge_threshold: 256,
+            min_length: 32,
+            max_gap: 4,
+            max_lines: 32,
+            weight: 1 << 18,
+        }
+    }
+}
+
+/// A straight segment of an image, in image coordinates.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LineSegment {
+    pub start: (u32, u32),
+    pub end: (u32, u32),
+}
+
+impl LineSegment {
+    /// The distance between the ends of the segment.
+    pub fn len(&self) -> f64 {
+        let dx = f64::from(self.end.0) - f64::from(self.start.0);
+        let dy = f64::from(self.end.1) - f64::from(self.start.1);
+        dx.hypot(dy)
+    }
+
+    /// Every pixel on the segment, from `start` to `end`.
+    pub fn points(&self) -> Vec<(u32, u32)> {
+        // Bresenham's line algorithm.
+        let (mut x, mut y) = (i64::from(self.start.0), i64::from(self.start.1));
+        let (end_x, end_y) = (i64::from(self.end.0), i64::from(self.end.1));
+        let dx = (end_x - x).abs();
+        let dy = -(end_y - y).abs();
+        let step_x = if x < end_x { 1 } else { -1 };
+        let step_y = if y < end_y { 1 } else { -1 };
+        let mut error = dx + dy;
+
+        let mut points = vec![];
+        loop {
+            points.push((x as u32, y as u32));
+            if x == end_x && y == end_y {
+                return points;
+            }
+            let doubled = 2 * error;
+            if doubled >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if doubled <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+    }
+}
+
+/// Finds the straight segments of `image` with a Hough transform over its
+/// edges, strongest first.
+pub fn detect_lines(image: &DynamicImage, options: &LineOptions) -> Vec<LineSegment> {
+    let edges = EdgeMap::new(&image.to_luma8(), options.edge_threshold);
+    let (width, height) = (edges.width as f64, edges.height as f64);
+    let diagonal = width.hypot(height).ceil() as usize;
+    let rho_len = 2 * diagonal + 1;
+    let angles: Vec<(f64, f64)> = (0..THETA_STEPS)
+        .map(|step| (step as f64 * PI / THETA_STEPS as f64).sin_cos())
+        .collect();
+
+    let mut votes = vec![0u32; THETA_STEPS * rho_len];
+    for (x, y) in edges.iter() {
+        for (theta, &(sin, cos)) in angles.iter().enumerate() {
+            let rho = (x as f64 * cos + y as f64 * sin).round() as i64 + diagonal as i64;
+            votes[theta * rho_len + rho as usize] += 1;
+        }
+    }
+
+    let mut peaks: Vec<(u32, usize, usize)> = (0..THETA_STEPS)
+        .flat_map(|theta| (0..rho_len).map(move |rho| (theta, rho)))
+        .filter_map(|(theta, rho)| {
+            let count = votes[theta * rho_len + rho];
+            let strongest = count >= options.min_length
+                && neighborhood(theta, rho, rho_len).all(|(other_theta, other_rho)| {
+                    let other = votes[other_theta * rho_len + other_rho];
+                    other < count || (other == count && (other_theta, other_rho) >= (theta, rho))
+                });
+            if strongest {
+                Some((count, theta, rho))
+            } else {
+                None
+            }
+        })
+        .collect();
+    peaks.sort_by_key(|&(count, theta, rho)| (std::cmp::Reverse(count), theta, rho));
+
+    let mut claimed = vec![false; edges.width * edges.height];
+    let mut lines = vec![];
+    for (_, theta, rho) in peaks {
+        let (sin, cos) = angles[theta];
+        let rho = rho as f64 - diagonal as f64;
+        for run in edges.runs_along(sin, cos, rho, options) {
+            if lines.len() == options.max_lines {
+                return lines;
+            }
+            // Most of a run already claimed is another side of a line that's
+            // been found.
+            let fresh = run
+                .iter()
+                .filter(|&&(x, y)| !claimed[y * edges.width + x])
+                .count();
+            if fresh * 2 < run.len() {
+                continue;
+            }
+            for &(x, y) in &run {
+                edges.claim(&mut claimed, x, y, sin, cos);
+            }
+            let (start, end) = (run[0], run[run.len() - 1]);
+            lines.push(LineSegment {
+                start: (start.0 as u32, start.1 as u32),
+                end: (end.0 as u32, end.1 as u32),
+            });
+        }
+    }
+    lines
+}
+
+/// The accumulator cells within two steps of `(theta, rho)`.
+fn neighborhood(theta: usize, rho: usize, rho_len: usize) -> impl Iterator<Item = (usize, usize)> {
+    let thetas = theta.saturating_sub(2)..=(theta + 2).min(THETA_STEPS - 1);
+    thetas.flat_map(move |theta| {
+        (rho.saturating_sub(2)..=(rho + 2).min(rho_len - 1)).map(move |rho| (theta, rho))
+    })
+}
+
+/// The pixels of an image strong enough to be edges.
+struct EdgeMap {
+    width: usize,
+    height: usize,
+    edges: Vec<bool>,
+}
+
+impl EdgeMap {
+    fn new(luma: &GrayImage, threshold: u32) -> Self {
+        let (width, height) = (luma.width() as usize, luma.height() as usize);
+        let pixel = |x: usize, y: usize| {
+            let x = x.min(width - 1) as u32;
+            let y = y.min(height - 1) as u32;
+            i32::from(luma.get_pixel(x, y)[0])
+        };
+
+        let mut edges = vec![false; width * height];
+        for y in 0..height {
+            for x in 0..width {
+                let (left, right) = (x.saturating_sub(1), x + 1);
+                let (up, down) = (y.saturating_sub(1), y + 1);
+                let gx = pixel(right, up) + 2 * pixel(right, y) + pixel(right, down)
+                    - pixel(left, up)
+                    - 2 * pixel(left, y)
+                    - pixel(left, down);
+                let gy = pixel(left, down) + 2 * pixel(x, down) + pixel(right, down)
+                    - pixel(left, up)
+                    - 2 * pixel(x, up)
+                    - pixel(right, up);
+                edges[y * width + x] = gx.unsigned_abs() + gy.unsigned_abs() >= threshold;
+            }
+        }
+
+        EdgeMap {
+            width,
+            height,
+            edges,
+        }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        let width = self.width;
+        self.edges
+            .iter()
+            .enumerate()
+            .filter(|&(_, &edge)| edge)
+            .map(move |(index, _)| (index % width, index / width))
+    }
+
+    fn contains(&self, x: i64, y: i64) -> bool {
+        x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height
+    }
+
+    fn is_edge(&self, x: i64, y: i64) -> bool {
+        self.contains(x, y) && self.edges[y as usize * self.width + x as usize]
+    }
+
+    /// The pixels along the line `x cos + y sin = rho`, stepping along
+    /// whichever axis it runs closest to.
+    fn line_pixels(&self, sin: f64, cos: f64, rho: f64) -> Vec<(i64, i64)> {
+        if sin.abs() >= cos.abs() {
+            (0..self.width as i64)
+                .map(|x| (x, ((rho - x as f64 * cos) / sin).round() as i64))
+                .collect()
+        } else {
+            (0..self.height as i64)
+                .map(|y| (((rho - y as f64 * sin) / cos).round() as i64, y))
+                .collect()
+        }
+    }
+
+    /// The offset of one pixel across the line, which runs mostly along x
+    /// when `sin` is the larger.
+    fn across(sin: f64, cos: f64) -> (i64, i64) {
+        if sin.abs() >= cos.abs() {
+            (0, 1)
+        } else {
+            (1, 0)
+        }
+    }
+
+    /// Runs of edge pixels along the line `x cos + y sin = rho` at least
+    /// `min_length` long, bridging gaps of up to `max_gap` pixels.
+    fn runs_along(
+        &self,
+        sin: f64,
+        cos: f64,
+        rho: f64,
+        options: &LineOptions,
+    ) -> Vec<Vec<(usize, usize)>> {
+        let (across_x, across_y) = Self::across(sin, cos);
+        let mut runs = vec![];
+        let mut run: Vec<(usize, usize)> = vec![];
+        let mut gap = 0;
+
+        let mut finish = |run: &mut Vec<(usize, usize)>| {
+            let long_enough = match (run.first(), run.last()) {
+                (Some(&start), Some(&end)) => {
+                    let segment = LineSegment {
+                        start: (start.0 as u32, start.1 as u32),
+                        end: (end.0 as u32, end.1 as u32),
+                    };
+                    segment.len() + 1.0 >= f64::from(options.min_length)
+                }
+                _ => false,
+            };
+            if long_enough {
+                runs.push(std::mem::take(run));
+            } else {
+                run.clear();
+            }
+        };
+
+        for (x, y) in self.line_pixels(sin, cos, rho) {
+            let on_line = (-LINE_TOLERANCE..=LINE_TOLERANCE)
+                .any(|offset| self.is_edge(x + offset * across_x, y + offset * across_y));
+            if on_line && self.contains(x, y) {
+                run.push((x as usize, y as usize));
+                gap = 0;
+            } else if !run.is_empty() {
+                gap += 1;
+                if gap > options.max_gap {
+                    finish(&mut run);
+                    gap = 0;
+                }
+            }
+        }
+        finish(&mut run);
+        runs
+    }
+
+    /// Marks the pixels within `CLAIM_RADIUS` across the line of `(x, y)`.
+    fn claim(&self, claimed: &mut [bool], x: usize, y: usize, sin: f64, cos: f64) {
+        let (across_x, across_y) = Self::across(sin, cos);
+        for offset in -CLAIM_RADIUS..=CLAIM_RADIUS {
+            let (x, y) = (x as i64 + offset * across_x, y as i64 + offset * across_y);
+            if self.contains(x, y) {
+                claimed[y as usize * self.width + x as usize] = true;
+            }
+        }
+    }
+}
@@ -0,0 +1,502 @@
+use std::convert::TryFrom;
+
+use image::DynamicImage;
+use img_parts::{Bytes, DynImage, ImageEXIF, ImageICC};
+
+use crate::BoxResult;
+
+const TAG_ORIENTATION: u16 = 0x0112;
+const TAG_EXIF_IFD: u16 = 0x8769;
+const TAG_GPS_IFD: u16 = 0x8825;
+const TAG_INTEROP_IFD: u16 = 0xA005;
+const TAG_ICC_PROFILE: u16 = 0x8773;
+const TAG_PIXEL_X_DIMENSION: u16 = 0xA002;
+const TAG_PIXEL_Y_DIMENSION: u16 = 0xA003;
+
+const TYPE_SHORT: u16 = 3;
+const TYPE_LONG: u16 = 4;
+const TYPE_RATIONAL: u16 = 5;
+const TYPE_UNDEFINED: u16 = 7;
+const TYPE_SRATIONAL: u16 = 10;
+
+const IFD_ENTRY_LEN: usize = 12;
+
+/// Tags describing how a TIFF file stores its pixels rather than the picture
+/// itself. They always come from the encoder and are never treated as EXIF.
+const LAYOUT_TAGS: &[u16] = &[
+    254, 255, 256, 257, 258, 259, 262, 266, 273, 277, 278, 279, 282, 283, 284, 296, 317, 322, 323,
+    324, 325, 330, 338, 339, 513, 514,
+];
+
+/// How many IFDs deep pointers are followed, so a file whose IFDs point at
+/// each other can't recurse forever.
+const MAX_IFD_DEPTH: usize = 4;
+
+/// The EXIF orientation of an image that needs no transformation.
+pub const ORIENTATION_NORMAL: u16 = 1;
+
+/// Metadata carried from the input image to the output.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Metadata {
+    /// Raw EXIF data, starting at the TIFF header.
+    pub exif: Option<Vec<u8>>,
+    pub icc_profile: Option<Vec<u8>>,
+    /// The EXIF orientation tag, from 1 to 8.
+    pub orientation: u16,
+}
+
+impl Metadata {
+    /// Reads the metadata of an encoded JPEG, PNG, WebP or TIFF image. Formats
+    /// without metadata support produce empty metadata rather than an error.
+    pub fn read(bytes: &[u8]) -> Metadata {
+        if is_tiff(bytes) {
+            return read_tiff(bytes);
+        }
+
+        let image = match DynImage::from_bytes(Bytes::copy_from_slice(bytes)) {
+            Ok(Some(image)) => image,
+            _ => return Metadata::empty(),
+        };
+
+        let exif = image.exif().map(|exif| exif.to_vec());
+        let orientation = exif
+            .as_ref()
+            .and_then(|exif| read_orientation(exif))
+            .unwrap_or(ORIENTATION_NORMAL);
+
+        Metadata {
+            exif,
+            icc_profile: image.icc_profile().map(|icc| icc.to_vec()),
+            orientation,
+        }
+    }
+
+    pub fn empty() -> Metadata {
+        Metadata {
+            exif: None,
+            icc_profile: None,
+            orientation: ORIENTATION_NORMAL,
+        }
+    }
+
+    /// Metadata describing an image that has had its orientation applied and
+    /// been resized to `width` by `height`.
+    pub fn updated_for(&self, width: u32, height: u32) -> Metadata {
+        let exif = self.exif.clone().map(|mut exif| {
+            patch_exif(&mut exif, width, height);
+            exif
+        });
+
+        Metadata {
+            exif,
+            icc_profile: self.icc_profile.clone(),
+            orientation: ORIENTATION_NORMAL,
+        }
+    }
+
+    /// Inserts the metadata into an encoded image. Only JPEG, PNG, WebP and
+    /// TIFF output can hold it; other formats are returned unchanged.
+    pub fn embed(&self, encoded: Vec<u8>) -> BoxResult<Vec<u8>> {
+        if self.exif.is_none() && self.icc_profile.is_none() {
+            return Ok(encoded);
+        }
+        if is_tiff(&encoded) {
+            return self.embed_tiff(encoded);
+        }
+
+        let mut image = match DynImage::from_bytes(Bytes::from(encoded.clone()))? {
+            Some(image) => image,
+            None => return Ok(encoded),
+        };
+
+        image.set_exif(self.exif.clone().map(Bytes::from));
+        image.set_icc_profile(self.icc_profile.clone().map(Bytes::from));
+
+        let mut output = vec![];
+        image.encoder().write_to(&mut output)?;
+        Ok(output)
+    }
+
+    // A TIFF file holds its metadata as tags in its first IFD, so the EXIF
+    // tags and the ICC profile are merged into a copy of that IFD appended
+    // to the file. The pixel data stays where it is.
+    fn embed_tiff(&self, mut encoded: Vec<u8>) -> BoxResult<Vec<u8>> {
+        let (little_endian, mut entries, next) = {
+            let reader = TiffReader::new(&encoded).ok_or("Invalid TIFF header")?;
+            let ifd = reader.first_ifd().ok_or("TIFF output has no IFD")?;
+            let count = reader.read_u16(ifd).ok_or("TIFF output has no IFD")? as usize;
+            let next = reader
+                .read_u32(ifd + 2 + count * IFD_ENTRY_LEN)
+                .unwrap_or(0);
+            let entries = reader.read_ifd(ifd, reader.little_endian, MAX_IFD_DEPTH);
+            (reader.little_endian, entries, next)
+        };
+
+        if let Some(ref exif) = self.exif {
+            let reader = TiffReader::new(exif).ok_or("EXIF data has no TIFF header")?;
+            let ifd = reader.first_ifd().ok_or("EXIF data has no IFD")?;
+            for entry in reader.read_ifd(ifd, little_endian, MAX_IFD_DEPTH) {
+                if LAYOUT_TAGS.contains(&entry.tag) || entry.tag == TAG_ICC_PROFILE {
+                    continue;
+                }
+                entries.retain(|other| other.tag != entry.tag);
+                entries.push(entry);
+            }
+        }
+        if let Some(ref icc_profile) = self.icc_profile {
+            entries.retain(|entry| entry.tag != TAG_ICC_PROFILE);
+            entries.push(Entry {
+                tag: TAG_ICC_PROFILE,
+                kind: TYPE_UNDEFINED,
+                count: u32::try_from(icc_profile.len())?,
+                value: Value::Data(icc_profile.clone()),
+            });
+        }
+
+        let ifd = write_ifd(&mut encoded, little_endian, &entries, next);
+        if u32::try_from(encoded.len()).is_err() {
+            return Err("TIFF output is too large to hold its metadata".into());
+        }
+        encoded[4..8].copy_from_slice(&u32_bytes(ifd as u32, little_endian));
+        Ok(encoded)
+    }
+}
+
+/// Rotates and flips `image` so it displays upright without relying on its
+/// EXIF orientation.
+pub fn apply_orientation(image: DynamicImage, orientation: u16) -> DynamicImage {
+    match orientation {
+        2 => image.fliph(),
+        3 => image.rotate180(),
+        4 => image.flipv(),
+        5 => image.rotate90().fliph(),
+        6 => image.rotate90(),
+        7 => image.rotate270().fliph(),
+        8 => image.rotate270(),
+        _ => image,
+    }
+}
+
+fn is_tiff(bytes: &[u8]) -> bool {
+    bytes.starts_with(b"II*\0") || bytes.starts_with(b"MM\0*")
+}
+
+// A TIFF file keeps its EXIF tags alongside the layout tags in its first
+// IFD, so they're copied out into an EXIF block of their own.
+fn read_tiff(bytes: &[u8]) -> Metadata {
+    let orientation = read_orientation(bytes).unwrap_or(ORIENTATION_NORMAL);
+    let reader = match TiffReader::new(bytes) {
+        Some(reader) => reader,
+        None => return Metadata::empty(),
+    };
+    let entries = match reader.first_ifd() {
+        Some(ifd) => reader.read_ifd(ifd, reader.little_endian, MAX_IFD_DEPTH),
+        None => vec![],
+    };
+
+    let mut icc_profile = None;
+    let mut exif_entries = vec![];
+    for entry in entries {
+        match entry {
+            Entry {
+                tag: TAG_ICC_PROFILE,
+                value: Value::Data(data),
+                ..
+            } => icc_profile = Some(data),
+            _ if LAYOUT_TAGS.contains(&entry.tag) => {}
+            _ => exif_entries.push(entry),
+        }
+    }
+
+    let exif = if exif_entries.is_empty() {
+        None
+    } else {
+        let mut exif = bytes[..4].to_vec();
+        exif.extend_from_slice(&u32_bytes(8, reader.little_endian));
+        write_ifd(&mut exif, reader.little_endian, &exif_entries, 0);
+        Some(exif)
+    };
+
+    Metadata {
+        exif,
+        icc_profile,
+        orientation,
+    }
+}
+
+fn read_orientation(tiff: &[u8]) -> Option<u16> {
+    let reader = TiffReader::new(tiff)?;
+    let entry = reader.find_entry(reader.first_ifd()?, TAG_ORIENTATION)?;
+    reader.read_short_value(entry)
+}
+
+// Rewrites the tags that no longer describe the output in place, so none of
+// the offsets within the EXIF block change.
+fn patch_exif(exif: &mut [u8], width: u32, height: u32) {
+    let (orientation, dimensions) = {
+        let reader = match TiffReader::new(exif) {
+            Some(reader) => reader,
+            None => return,
+        };
+        let first_ifd = match reader.first_ifd() {
+            Some(offset) => offset,
+            None => return,
+        };
+
+        let orientation = reader.find_entry(first_ifd, TAG_ORIENTATION);
+        let exif_ifd = reader
+            .find_entry(first_ifd, TAG_EXIF_IFD)
+            .and_then(|entry| reader.read_u32(entry + 8))
+            .map(|offset| offset as usize);
+        let dimensions = exif_ifd.map(|ifd| {
+            (
+                reader.find_entry(ifd, TAG_PIXEL_X_DIMENSION),
+                reader.find_entry(ifd, TAG_PIXEL_Y_DIMENSION),
+            )
+        });
+        (orientation, dimensions)
+    };
+
+    let little_endian = exif.starts_with(b"II");
+    if let Some(entry) = orientation {
+        write_value(exif, little_endian, entry, u32::from(ORIENTATION_NORMAL));
+    }
+    if let Some((x_entry, y_entry)) = dimensions {
+        if let Some(entry) = x_entry {
+            write_value(exif, little_endian, entry, width);
+        }
+        if let Some(entry) = y_entry {
+            write_value(exif, little_endian, entry, height);
+        }
+    }
+}
+
+fn u16_bytes(value: u16, little_endian: bool) -> [u8; 2] {
+    if little_endian {
+        value.to_le_bytes()
+    } else {
+        value.to_be_bytes()
+    }
+}
+
+fn u32_bytes(value: u32, little_endian: bool) -> [u8; 4] {
+    if little_endian {
+        value.to_le_bytes()
+    } else {
+        value.to_be_bytes()
+    }
+}
+
+/// The size in bytes of each value of a TIFF field type, or `None` for
+/// unknown types.
+fn type_size(kind: u16) -> Option<usize> {
+    match kind {
+        1 | 2 | 6 | 7 => Some(1),
+        3 | 8 => Some(2),
+        4 | 9 | 11 | 13 => Some(4),
+        5 | 10 | 12 => Some(8),
+        _ => None,
+    }
+}
+
+fn is_ifd_pointer(tag: u16) -> bool {
+    tag == TAG_EXIF_IFD || tag == TAG_GPS_IFD || tag == TAG_INTEROP_IFD
+}
+
+/// An IFD entry copied out of a TIFF structure, with its value already in
+/// the byte order it will be written in.
+struct Entry {
+    tag: u16,
+    kind: u16,
+    count: u32,
+    value: Value,
+}
+
+enum Value {
+    Data(Vec<u8>),
+    /// The entries of the IFD a pointer tag refers to.
+    Ifd(Vec<Entry>),
+}
+
+/// Appends an IFD holding `entries` to `data`, returning its offset. Values
+/// too big for their entry and the IFDs pointed to follow it.
+fn write_ifd(data: &mut Vec<u8>, little_endian: bool, entries: &[Entry], next: u32) -> usize {
+    let mut entries: Vec<&Entry> = entries.iter().collect();
+    entries.sort_by_key(|entry| entry.tag);
+
+    // Offsets in a TIFF file must be even.
+    if data.len() % 2 == 1 {
+        data.push(0);
+    }
+    let ifd = data.len();
+    data.extend_from_slice(&u16_bytes(entries.len() as u16, little_endian));
+    data.resize(ifd + 2 + entries.len() * IFD_ENTRY_LEN, 0);
+    data.extend_from_slice(&u32_bytes(next, little_endian));
+
+    for (i, entry) in entries.iter().enumerate() {
+        let mut field = [0; 4];
+        match entry.value {
+            Value::Data(ref value) if value.len() <= 4 => {
+                field[..value.len()].copy_from_slice(value);
+            }
+            Value::Data(ref value) => {
+                if data.len() % 2 == 1 {
+                    data.push(0);
+                }
+                field = u32_bytes(data.len() as u32, little_endian);
+                data.extend_from_slice(value);
+            }
+            Value::Ifd(ref sub_entries) => {
+                let offset = write_ifd(data, little_endian, sub_entries, 0);
+                field = u32_bytes(offset as u32, little_endian);
+            }
+        }
+
+        let start = ifd + 2 + i * IFD_ENTRY_LEN;
+        data[start..start + 2].copy_from_slice(&u16_bytes(entry.tag, little_endian));
+        data[start + 2..start + 4].copy_from_slice(&u16_bytes(entry.kind, little_endian));
+        data[start + 4..start + 8].copy_from_slice(&u32_bytes(entry.count, little_endian));
+        data[start + 8..start + 12].copy_from_slice(&field);
+    }
+    ifd
+}
+
+fn write_value(exif: &mut [u8], little_endian: bool, entry: usize, value: u32) {
+    let kind = {
+        let reader = TiffReader {
+            data: exif,
+            little_endian,
+        };
+        reader.read_u16(entry + 2)
+    };
+
+    match kind {
+        Some(TYPE_SHORT) if value <= u32::from(u16::MAX) => {
+            let bytes = if little_endian {
+                (value as u16).to_le_bytes()
+            } else {
+                (value as u16).to_be_bytes()
+            };
+            exif[entry + 8..entry + 10].copy_from_slice(&bytes);
+        }
+        Some(TYPE_LONG) => {
+            let bytes = if little_endian {
+                value.to_le_bytes()
+            } else {
+                value.to_be_bytes()
+            };
+            exif[entry + 8..entry + 12].copy_from_slice(&bytes);
+        }
+        _ => {}
+    }
+}
+
+struct TiffReader<'a> {
+    data: &'a [u8],
+    little_endian: bool,
+}
+
+impl<'a> TiffReader<'a> {
+    fn new(data: &'a [u8]) -> Option<Self> {
+        if !is_tiff(data) {
+            return None;
+        }
+        Some(TiffReader {
+            data,
+            little_endian: data.starts_with(b"II"),
+        })
+    }
+
+    fn first_ifd(&self) -> Option<usize> {
+        self.read_u32(4).map(|offset| offset as usize)
+    }
+
+    /// The offset of the entry for `tag` in the IFD starting at `ifd`.
+    fn find_entry(&self, ifd: usize, tag: u16) -> Option<usize> {
+        let count = self.read_u16(ifd)? as usize;
+        (0..count)
+            .map(|i| ifd + 2 + i * IFD_ENTRY_LEN)
+            .take_while(|&entry| entry + IFD_ENTRY_LEN <= self.data.len())
+            .find(|&entry| self.read_u16(entry) == Some(tag))
+    }
+
+    /// The entries of the IFD at `ifd` that can be copied, converted to the
+    /// given byte order. Pointers to other IFDs are followed `depth` deep.
+    fn read_ifd(&self, ifd: usize, little_endian: bool, depth: usize) -> Vec<Entry> {
+        let count = self.read_u16(ifd).unwrap_or(0) as usize;
+        (0..count)
+            .filter_map(|i| self.read_entry(ifd + 2 + i * IFD_ENTRY_LEN, little_endian, depth))
+            .collect()
+    }
+
+    fn read_entry(&self, entry: usize, little_endian: bool, depth: usize) -> Option<Entry> {
+        let tag = self.read_u16(entry)?;
+        let kind = self.read_u16(entry + 2)?;
+        let count = self.read_u32(entry + 4)?;
+
+        if is_ifd_pointer(tag) {
+            let offset = self.read_u32(entry + 8)? as usize;
+            let depth = depth.checked_sub(1)?;
+            return Some(Entry {
+                tag,
+                kind: TYPE_LONG,
+                count: 1,
+                value: Value::Ifd(self.read_ifd(offset, little_endian, depth)),
+            });
+        }
+
+        let size = type_size(kind)?;
+        let len = (count as usize).checked_mul(size)?;
+        let start = if len <= 4 {
+            entry + 8
+        } else {
+            self.read_u32(entry + 8)? as usize
+        };
+        let mut value = self.data.get(start..start.checked_add(len)?)?.to_vec();
+
+        if self.little_endian != little_endian {
+            // Rationals are pairs of 4 byte numbers.
+            let width = match kind {
+                TYPE_RATIONAL | TYPE_SRATIONAL => 4,
+                _ => size,
+            };
+            for number in value.chunks_mut(width) {
+                number.reverse();
+            }
+        }
+
+        Some(Entry {
+            tag,
+            kind,
+            count,
+            value: Value::Data(value),
+        })
+    }
+
+    fn read_short_value(&self, entry: usize) -> Option<u16> {
+        match self.read_u16(entry + 2)? {
+            TYPE_SHORT => self.read_u16(entry + 8),
+            _ => None,
+        }
+    }
+
+    fn read_u16(&self, offset: usize) -> Option<u16> {
+        let bytes = self.data.get(offset..offset + 2)?;
+        let bytes = [bytes[0], bytes[1]];
+        Some(if self.little_endian {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    }
+
+    fn read_u32(&self, offset: usize) -> Option<u32> {
+        let bytes = self.data.get(offset..offset + 4)?;
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        Some(if self.little_endian {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    }
+}
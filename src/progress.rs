@@ -0,0 +1,76 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::carve::Direction;
+
+/// The stage of a resize that a `Progress` report refers to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    /// Computing the energy of every pixel before a pass.
+    Energy,
+    /// Finding the seams an enlargement will duplicate.
+    GrowPrecompute,
+    /// Removing or inserting seams.
+    Carve,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Progress {
+    pub phase: Phase,
+    pub direction: Direction,
+    /// Units of work finished so far in this phase. For the seam phases this
+    /// is a count of seams.
+    pub completed: usize,
+    pub total: usize,
+}
+
+/// Returned by an `Observer` to decide whether the resize keeps going.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Control {
+    Continue,
+    Cancel,
+}
+
+pub trait Observer {
+    fn on_progress(&mut self, progress: &Progress) -> Control;
+}
+
+impl<F> Observer for F
+where
+    F: FnMut(&Progress) -> Control,
+{
+    fn on_progress(&mut self, progress: &Progress) -> Control {
+        self(progress)
+    }
+}
+
+/// The error produced when an `Observer` cancels a resize.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Resize was cancelled")
+    }
+}
+
+impl Error for Cancelled {}
+
+pub(crate) fn report(
+    observer: &mut dyn Observer,
+    phase: Phase,
+    direction: Direction,
+    completed: usize,
+    total: usize,
+) -> Result<(), Cancelled> {
+    let progress = Progress {
+        phase,
+        direction,
+        completed,
+        total,
+    };
+    match observer.on_progress(&progress) {
+        Control::Continue => Ok(()),
+        Control::Cancel => Err(Cancelled),
+    }
+}
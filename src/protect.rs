@@ -0,0 +1,74 @@
+use image::{DynamicImage, GenericImageView, GrayImage, Luma};
+
+use crate::cascade::Rect;
+use crate::BoxResult;
+
+/// The value of protected pixels in the masks made here.
+const PROTECTED: Luma<u8> = Luma([255]);
+
+/// The blue and red chroma ranges of skin in YCbCr, from Chai and Ngan's
+/// face segmentation work. They hold across most skin tones because skin
+/// varies mostly in brightness.
+const SKIN_CB: (f32, f32) = (77.0, 127.0);
+const SKIN_CR: (f32, f32) = (133.0, 173.0);
+
+/// Pixels darker than this are too noisy to judge by color.
+const MIN_SKIN_LUMA: f32 = 40.0;
+
+/// Marks every pixel with a skin tone, leaving out specks of fewer than five
+/// skin pixels in their 3x3 neighbourhood.
+pub fn skin_mask(image: &DynamicImage) -> GrayImage {
+    let (width, height) = image.dimensions();
+    let rgba = image.to_rgba8();
+    let skin: Vec<bool> = rgba
+        .pixels()
+        .map(|pixel| {
+            let [r, g, b, a] = pixel.0;
+            let (r, g, b) = (f32::from(r), f32::from(g), f32::from(b));
+            let y = 0.299 * r + 0.587 * g + 0.114 * b;
+            let cb = 128.0 - 0.168_736 * r - 0.331_264 * g + 0.5 * b;
+            let cr = 128.0 + 0.5 * r - 0.418_688 * g - 0.081_312 * b;
+            a > 0
+                && y >= MIN_SKIN_LUMA
+                && (SKIN_CB.0..=SKIN_CB.1).contains(&cb)
+                && (SKIN_CR.0..=SKIN_CR.1).contains(&cr)
+        })
+        .collect();
+
+    GrayImage::from_fn(width, height, |x, y| {
+        let (x, y) = (x as usize, y as usize);
+        let (width, height) = (width as usize, height as usize);
+        let neighbours = (y.saturating_sub(1)..=(y + 1).min(height - 1))
+            .flat_map(|ny| (x.saturating_sub(1)..=(x + 1).min(width - 1)).map(move |nx| (nx, ny)))
+            .filter(|&(nx, ny)| skin[ny * width + nx])
+            .count();
+        if skin[y * width + x] && neighbours >= 5 {
+            PROTECTED
+        } else {
+            Luma([0])
+        }
+    })
+}
+
+/// Marks every pixel inside `faces` on a `width` by `height` mask.
+pub fn face_mask(width: u32, height: u32, faces: &[Rect]) -> GrayImage {
+    let mut mask = GrayImage::new(width, height);
+    for face in faces {
+        for y in face.y..(face.y + face.height).min(height) {
+            for x in face.x..(face.x + face.width).min(width) {
+                mask.put_pixel(x, y, PROTECTED);
+            }
+        }
+    }
+    mask
+}
+
+/// Protects every pixel either mask protects.
+pub fn merge_masks(a: &GrayImage, b: &GrayImage) -> BoxResult<GrayImage> {
+    if a.dimensions() != b.dimensions() {
+        return Err("Protection masks must be the same size".into());
+    }
+    Ok(GrayImage::from_fn(a.width(), a.height(), |x, y| {
+        Luma([a.get_pixel(x, y)[0].max(b.get_pixel(x, y)[0])])
+    }))
+}
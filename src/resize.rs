@@ -0,0 +1,215 @@
+use std::time::{Duration, Instant};
+
+use image::{DynamicImage, GrayImage};
+
+use crate::carve::{
+    Carver, CarverOptions, Direction, Interpolation, ResizeMode, Roi, SeamConstraint, SeamCounts,
+    SeamOrder, SeamPoint, Strategy, Symmetry,
+};
+use crate::energy::{Boundary, EnergyFunction, TextOptions};
+use crate::lines::{LineOptions, LineSegment};
+use crate::progress::{Control, Observer, Progress};
+use crate::stats::PassStats;
+use crate::BoxResult;
+
+/// Everything needed to resize an image with `rmr::resize`.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate image;
+/// # extern crate rmr;
+/// # use image::{DynamicImage, GenericImageView};
+/// # use rmr::carve::SeamOrder;
+/// # use rmr::ResizeOptions;
+/// # fn main() {
+/// let image = DynamicImage::new_rgb8(8, 6);
+/// let options = ResizeOptions::new(6, 7).seam_order(SeamOrder::HeightFirst);
+///
+/// let output = rmr::resize(&image, options).unwrap();
+/// assert_eq!((6, 7), output.image.dimensions());
+/// # }
+/// ```
+pub struct ResizeOptions<'a> {
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+    pub(crate) carver: CarverOptions,
+    pub(crate) layers: Vec<(DynamicImage, Interpolation)>,
+    pub(crate) observer: Option<Box<dyn Observer + 'a>>,
+}
+
+impl<'a> ResizeOptions<'a> {
+    /// Options targeting an image of `width` by `height` pixels.
+    pub fn new(width: usize, height: usize) -> Self {
+        ResizeOptions {
+            width,
+            height,
+            carver: CarverOptions::default(),
+            layers: vec![],
+            observer: None,
+        }
+    }
+
+    pub fn mode(mut self, mode: ResizeMode) -> Self {
+        self.carver.mode = mode;
+        self
+    }
+
+    pub fn strategy(mut self, strategy: Strategy) -> Self {
+        self.carver.strategy = strategy;
+        self
+    }
+
+    pub fn energy_function(mut self, energy_function: EnergyFunction) -> Self {
+        self.carver.energy_function = energy_function;
+        self
+    }
+
+    pub fn boundary(mut self, boundary: Boundary) -> Self {
+        self.carver.boundary = boundary;
+        self
+    }
+
+    pub fn seam_order(mut self, seam_order: SeamOrder) -> Self {
+        self.carver.seam_order = seam_order;
+        self
+    }
+
+    pub fn protect_mask(mut self, mask: GrayImage) -> Self {
+        self.carver.protect_mask = Some(mask);
+        self
+    }
+
+    pub fn text(mut self, text: TextOptions) -> Self {
+        self.carver.text = Some(text);
+        self
+    }
+
+    pub fn roi(mut self, roi: Roi) -> Self {
+        self.carver.roi = Some(roi);
+        self
+    }
+
+    pub fn symmetry(mut self, symmetry: Symmetry) -> Self {
+        self.carver.symmetry = Some(symmetry);
+        self
+    }
+
+    pub fn lines(mut self, lines: LineOptions) -> Self {
+        self.carver.lines = Some(lines);
+        self
+    }
+
+    pub fn interpolation(mut self, interpolation: Interpolation) -> Self {
+        self.carver.interpolation = interpolation;
+        self
+    }
+
+    pub fn seam_constraint(mut self, seam_constraint: SeamConstraint) -> Self {
+        self.carver.seam_constraint = seam_constraint;
+        self
+    }
+
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.carver.threads = threads;
+        self
+    }
+
+    pub fn low_memory(mut self, low_memory: bool) -> Self {
+        self.carver.low_memory = low_memory;
+        self
+    }
+
+    /// Whether `ResizeOutput::seams` is filled in. It's on by default.
+    pub fn seam_log(mut self, seam_log: bool) -> Self {
+        self.carver.seam_log = seam_log;
+        self
+    }
+
+    /// Carves `image` along with the input, filling inserted seams as
+    /// `interpolation` says. Layers are returned in the order they're added.
+    pub fn layer(mut self, image: DynamicImage, interpolation: Interpolation) -> Self {
+        self.layers.push((image, interpolation));
+        self
+    }
+
+    pub fn observer<O: Observer + 'a>(mut self, observer: O) -> Self {
+        self.observer = Some(Box::new(observer));
+        self
+    }
+}
+
+pub struct ResizeOutput {
+    pub image: DynamicImage,
+    /// Every seam pixel removed or inserted, in input image coordinates.
+    /// Empty if the seam log was turned off.
+    pub seams: Vec<SeamPoint>,
+    /// The layers carved along with the image, each with its own pixel type.
+    pub layers: Vec<DynamicImage>,
+    /// The straight lines found in the input, if line preservation was on.
+    pub lines: Vec<LineSegment>,
+    pub stats: ResizeStats,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ResizeStats {
+    /// Time spent carving, excluding image decoding and encoding.
+    pub elapsed: Duration,
+    pub seams: SeamCounts,
+    pub passes: Vec<PassStats>,
+    pub peak_memory_bytes: usize,
+}
+
+/// Resizes `image` to the size given in `options` using seam carving.
+pub fn resize(image: &DynamicImage, options: ResizeOptions) -> BoxResult<ResizeOutput> {
+    let ResizeOptions {
+        width,
+        height,
+        carver,
+        layers,
+        observer,
+    } = options;
+
+    if width == 0 || height == 0 {
+        return Err("Target dimensions must be greater than zero".into());
+    }
+
+    let directions = carver.seam_order.directions();
+    let mut carver = Carver::with_options(image, carver)?;
+    for (layer, interpolation) in &layers {
+        carver.add_image_layer(layer, *interpolation)?;
+    }
+
+    let start = Instant::now();
+    let mut observer = observer.unwrap_or_else(|| Box::new(|_: &Progress| Control::Continue));
+    // Each pass is checked against the region of interest as the previous
+    // one left it.
+    for &direction in &directions {
+        let target = match direction {
+            Direction::Vertical => width,
+            Direction::Horizontal => height,
+        };
+        carver.check_roi(direction, target)?;
+        carver.carve(direction, target, observer.as_mut())?;
+    }
+    let image = carver.image();
+    let stats = ResizeStats {
+        elapsed: start.elapsed(),
+        seams: carver.seam_counts(),
+        passes: carver.pass_stats().to_vec(),
+        peak_memory_bytes: carver.peak_memory_bytes(),
+    };
+    let layers = (0..layers.len())
+        .filter_map(|index| carver.image_layer(index))
+        .collect();
+    let lines = carver.lines().to_vec();
+    let seams = carver.get_seam_points();
+
+    Ok(ResizeOutput {
+        image,
+        seams,
+        layers,
+        lines,
+        stats,
+    })
+}
@@ -0,0 +1,160 @@
+use std::fmt::Write;
+use std::time::Duration;
+
+use crate::carve::Direction;
+use crate::resize::ResizeStats;
+
+/// Measurements for one carving pass in a single direction.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PassStats {
+    pub direction: Direction,
+    /// Time spent calculating the energy of every pixel before the pass.
+    pub energy: Duration,
+    /// Time spent finding the seams to duplicate. Zero when shrinking.
+    pub grow_precompute: Duration,
+    /// Time spent removing or inserting seams.
+    pub carve: Duration,
+    pub seams_removed: usize,
+    pub seams_inserted: usize,
+    /// Sum of the path costs of every seam in the pass.
+    pub seam_energy: u64,
+    /// Whether the pass cropped the image instead of carving seams.
+    pub cropped: bool,
+}
+
+impl PassStats {
+    pub fn new(direction: Direction) -> Self {
+        PassStats {
+            direction,
+            energy: Duration::default(),
+            grow_precompute: Duration::default(),
+            carve: Duration::default(),
+            seams_removed: 0,
+            seams_inserted: 0,
+            seam_energy: 0,
+            cropped: false,
+        }
+    }
+
+    pub fn seams(&self) -> usize {
+        self.seams_removed + self.seams_inserted
+    }
+
+    pub fn mean_seam_energy(&self) -> f64 {
+        mean(self.seam_energy, self.seams())
+    }
+}
+
+/// A full accounting of a CLI run, serialized with `to_json`.
+pub struct Report<'a> {
+    pub decode: Duration,
+    pub encode: Duration,
+    pub resize: &'a ResizeStats,
+}
+
+impl<'a> Report<'a> {
+    pub fn to_json(&self) -> String {
+        let resize = self.resize;
+        let seam_energy: u64 = resize.passes.iter().map(|pass| pass.seam_energy).sum();
+        let seams: usize = resize.passes.iter().map(PassStats::seams).sum();
+
+        let mut json = String::new();
+        json.push_str("{\n");
+        json.push_str("  \"timings\": {\n");
+        let _ = writeln!(json, "    \"decode\": {},", seconds(self.decode));
+        let _ = writeln!(json, "    \"resize\": {},", seconds(resize.elapsed));
+        let _ = writeln!(json, "    \"encode\": {}", seconds(self.encode));
+        json.push_str("  },\n");
+
+        json.push_str("  \"passes\": [");
+        for (i, pass) in resize.passes.iter().enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            json.push_str("\n    {\n");
+            let _ = writeln!(
+                json,
+                "      \"direction\": \"{}\",",
+                direction(pass.direction)
+            );
+            let _ = writeln!(json, "      \"cropped\": {},", pass.cropped);
+            let _ = writeln!(json, "      \"energy\": {},", seconds(pass.energy));
+            let _ = writeln!(
+                json,
+                "      \"grow_precompute\": {},",
+                seconds(pass.grow_precompute)
+            );
+            let _ = writeln!(json, "      \"carve\": {},", seconds(pass.carve));
+            let _ = writeln!(json, "      \"seams_removed\": {},", pass.seams_removed);
+            let _ = writeln!(json, "      \"seams_inserted\": {},", pass.seams_inserted);
+            let _ = writeln!(json, "      \"total_seam_energy\": {},", pass.seam_energy);
+            let _ = writeln!(
+                json,
+                "      \"mean_seam_energy\": {}",
+                pass.mean_seam_energy()
+            );
+            json.push_str("    }");
+        }
+        if !resize.passes.is_empty() {
+            json.push_str("\n  ");
+        }
+        json.push_str("],\n");
+
+        let counts = &resize.seams;
+        json.push_str("  \"seams\": {\n");
+        let _ = writeln!(
+            json,
+            "    \"vertical_removed\": {},",
+            counts.vertical_removed
+        );
+        let _ = writeln!(
+            json,
+            "    \"vertical_inserted\": {},",
+            counts.vertical_inserted
+        );
+        let _ = writeln!(
+            json,
+            "    \"horizontal_removed\": {},",
+            counts.horizontal_removed
+        );
+        let _ = writeln!(
+            json,
+            "    \"horizontal_inserted\": {}",
+            counts.horizontal_inserted
+        );
+        json.push_str("  },\n");
+
+        let _ = writeln!(json, "  \"total_seam_energy\": {},", seam_energy);
+        let _ = writeln!(
+            json,
+            "  \"mean_seam_energy\": {},",
+            mean(seam_energy, seams)
+        );
+        let _ = writeln!(
+            json,
+            "  \"peak_memory_bytes\": {}",
+            resize.peak_memory_bytes
+        );
+        json.push_str("}\n");
+        json
+    }
+}
+
+fn seconds(duration: Duration) -> f64 {
+    duration.as_secs_f64()
+}
+
+fn mean(total: u64, count: usize) -> f64 {
+    if count == 0 {
+        0.0
+    } else {
+        total as f64 / count as f64
+    }
+}
+
+fn direction(direction: Direction) -> &'static str {
+    match direction {
+        Direction::Vertical => "vertical",
+        Direction::Horizontal => "horizontal",
+    }
+}
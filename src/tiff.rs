@@ -0,0 +1,491 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use image::{DynamicImage, Rgba, RgbaImage};
+
+use crate::tiled::StripSource;
+use crate::BoxResult;
+
+/// The width and height of the tiles written by `TiledTiffWriter`.
+pub const TILE_SIZE: u32 = 256;
+
+const TILE_BYTES: usize = (TILE_SIZE * TILE_SIZE * 4) as usize;
+
+const SHORT: u16 = 3;
+const LONG: u16 = 4;
+const LONG8: u16 = 16;
+
+const IMAGE_WIDTH: u16 = 256;
+const IMAGE_LENGTH: u16 = 257;
+const BITS_PER_SAMPLE: u16 = 258;
+const COMPRESSION: u16 = 259;
+const PHOTOMETRIC_INTERPRETATION: u16 = 262;
+const SAMPLES_PER_PIXEL: u16 = 277;
+const PLANAR_CONFIGURATION: u16 = 284;
+const TILE_WIDTH: u16 = 322;
+const TILE_LENGTH: u16 = 323;
+const TILE_OFFSETS: u16 = 324;
+const TILE_BYTE_COUNTS: u16 = 325;
+const EXTRA_SAMPLES: u16 = 338;
+
+const UNSUPPORTED: &str = "Only uncompressed 8-bit RGB or RGBA tiled TIFFs can be read in strips";
+
+/// Writes an uncompressed RGBA TIFF in 256 pixel square tiles, taking the
+/// image a few columns at a time from left to right.
+///
+/// Only the columns that don't yet fill a column of tiles are held in memory.
+/// Files too big for 32-bit offsets are written as BigTIFF.
+pub struct TiledTiffWriter<W: Write + Seek> {
+    writer: W,
+    width: u32,
+    height: u32,
+    big: bool,
+    /// The RGBA bytes of each row of the columns not yet written out.
+    pending: Vec<Vec<u8>>,
+    pending_width: u32,
+    columns_written: u32,
+    /// Where each tile starts, row by row.
+    offsets: Vec<u64>,
+}
+
+impl TiledTiffWriter<File> {
+    pub fn create<P: AsRef<Path>>(path: P, width: u32, height: u32) -> BoxResult<Self> {
+        TiledTiffWriter::new(File::create(path)?, width, height)
+    }
+}
+
+impl<W: Write + Seek> TiledTiffWriter<W> {
+    /// Starts a `width` by `height` image at the start of `writer`.
+    pub fn new(mut writer: W, width: u32, height: u32) -> BoxResult<Self> {
+        if width == 0 || height == 0 {
+            return Err("Image dimensions must be greater than zero".into());
+        }
+        let tiles = tiles_across(width) as u64 * tiles_across(height) as u64;
+        // Leaves room for the directory after the tiles.
+        let big = tiles * (TILE_BYTES as u64 + 16) + 4096 > u64::from(u32::MAX);
+
+        writer.seek(SeekFrom::Start(0))?;
+        writer.write_all(b"II")?;
+        if big {
+            writer.write_all(&43u16.to_le_bytes())?;
+            writer.write_all(&8u16.to_le_bytes())?;
+            writer.write_all(&0u16.to_le_bytes())?;
+            writer.write_all(&0u64.to_le_bytes())?;
+        } else {
+            writer.write_all(&42u16.to_le_bytes())?;
+            writer.write_all(&0u32.to_le_bytes())?;
+        }
+
+        Ok(TiledTiffWriter {
+            writer,
+            width,
+            height,
+            big,
+            pending: vec![vec![]; height as usize],
+            pending_width: 0,
+            columns_written: 0,
+            offsets: vec![0; tiles as usize],
+        })
+    }
+
+    /// Appends the columns of `image`, which must be as tall as the output,
+    /// to the right of those already written.
+    pub fn write_columns(&mut self, image: &DynamicImage) -> BoxResult<()> {
+        let image = image.to_rgba8();
+        if image.width() == 0 {
+            return Ok(());
+        }
+        if image.height() != self.height {
+            return Err("Columns must be as tall as the image".into());
+        }
+        let received = self.columns_written + self.pending_width + image.width();
+        if received > self.width {
+            return Err(format!("More than {} columns were written", self.width).into());
+        }
+
+        let row_bytes = image.width() as usize * 4;
+        for (pending, row) in self.pending.iter_mut().zip(image.chunks(row_bytes)) {
+            pending.extend_from_slice(row);
+        }
+        self.pending_width += image.width();
+        while self.pending_width >= TILE_SIZE {
+            self.write_tile_column(TILE_SIZE)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the last tiles and the directory describing them, returning
+    /// the writer.
+    pub fn finish(mut self) -> BoxResult<W> {
+        let received = self.columns_written + self.pending_width;
+        if received != self.width {
+            return Err(format!("Only {} of {} columns were written", received, self.width).into());
+        }
+        if self.pending_width > 0 {
+            self.write_tile_column(self.pending_width)?;
+        }
+
+        let offset_type = if self.big { LONG8 } else { LONG };
+        let offset_size = if self.big { 8 } else { 4 };
+        let offsets: Vec<u8> = self
+            .offsets
+            .iter()
+            .flat_map(|&offset| offset.to_le_bytes()[..offset_size].to_vec())
+            .collect();
+        let byte_counts: Vec<u8> = self
+            .offsets
+            .iter()
+            .flat_map(|_| (TILE_BYTES as u32).to_le_bytes().to_vec())
+            .collect();
+        let count = self.offsets.len() as u64;
+        let entries = vec![
+            Entry::new(IMAGE_WIDTH, LONG, 1, &self.width.to_le_bytes()),
+            Entry::new(IMAGE_LENGTH, LONG, 1, &self.height.to_le_bytes()),
+            Entry::new(BITS_PER_SAMPLE, SHORT, 4, &shorts(&[8, 8, 8, 8])),
+            Entry::new(COMPRESSION, SHORT, 1, &shorts(&[1])),
+            Entry::new(PHOTOMETRIC_INTERPRETATION, SHORT, 1, &shorts(&[2])),
+            Entry::new(SAMPLES_PER_PIXEL, SHORT, 1, &shorts(&[4])),
+            Entry::new(PLANAR_CONFIGURATION, SHORT, 1, &shorts(&[1])),
+            Entry::new(TILE_WIDTH, LONG, 1, &TILE_SIZE.to_le_bytes()),
+            Entry::new(TILE_LENGTH, LONG, 1, &TILE_SIZE.to_le_bytes()),
+            Entry::new(TILE_OFFSETS, offset_type, count, &offsets),
+            Entry::new(TILE_BYTE_COUNTS, LONG, count, &byte_counts),
+            // Unassociated alpha.
+            Entry::new(EXTRA_SAMPLES, SHORT, 1, &shorts(&[2])),
+        ];
+        self.write_directory(&entries)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    /// Writes the tiles under the first `width` pending columns, padding
+    /// them out to whole tiles.
+    fn write_tile_column(&mut self, width: u32) -> BoxResult<()> {
+        let column = (self.columns_written / TILE_SIZE) as usize;
+        let across = tiles_across(self.width) as usize;
+        let row_bytes = width as usize * 4;
+        let mut tile = vec![0; TILE_BYTES];
+        for tile_row in 0..tiles_across(self.height) as usize {
+            for (y, line) in tile.chunks_mut(TILE_SIZE as usize * 4).enumerate() {
+                line.iter_mut().for_each(|byte| *byte = 0);
+                if let Some(row) = self.pending.get(tile_row * TILE_SIZE as usize + y) {
+                    line[..row_bytes].copy_from_slice(&row[..row_bytes]);
+                }
+            }
+            self.offsets[tile_row * across + column] = self.writer.stream_position()?;
+            self.writer.write_all(&tile)?;
+        }
+
+        for row in &mut self.pending {
+            row.drain(..row_bytes);
+        }
+        self.pending_width -= width;
+        self.columns_written += width;
+        Ok(())
+    }
+
+    /// Writes the values too big to fit in their entries, then the
+    /// directory itself, and points the header at it.
+    fn write_directory(&mut self, entries: &[Entry]) -> BoxResult<()> {
+        let inline = if self.big { 8 } else { 4 };
+        let mut fields = vec![];
+        for entry in entries {
+            if entry.data.len() > inline {
+                let offset = self.writer.stream_position()?;
+                self.writer.write_all(&entry.data)?;
+                fields.push(offset.to_le_bytes()[..inline].to_vec());
+            } else {
+                let mut field = entry.data.clone();
+                field.resize(inline, 0);
+                fields.push(field);
+            }
+        }
+
+        let directory = self.writer.stream_position()?;
+        if self.big {
+            self.writer
+                .write_all(&(entries.len() as u64).to_le_bytes())?;
+        } else {
+            self.writer
+                .write_all(&(entries.len() as u16).to_le_bytes())?;
+        }
+        for (entry, field) in entries.iter().zip(&fields) {
+            self.writer.write_all(&entry.tag.to_le_bytes())?;
+            self.writer.write_all(&entry.field_type.to_le_bytes())?;
+            if self.big {
+                self.writer.write_all(&entry.count.to_le_bytes())?;
+            } else {
+                self.writer.write_all(&(entry.count as u32).to_le_bytes())?;
+            }
+            self.writer.write_all(field)?;
+        }
+        // There's no next directory.
+        self.writer.write_all(&vec![0; inline])?;
+
+        if self.big {
+            self.writer.seek(SeekFrom::Start(8))?;
+            self.writer.write_all(&directory.to_le_bytes())?;
+        } else {
+            self.writer.seek(SeekFrom::Start(4))?;
+            self.writer.write_all(&(directory as u32).to_le_bytes())?;
+        }
+        self.writer.seek(SeekFrom::End(0))?;
+        Ok(())
+    }
+}
+
+/// One entry in a TIFF directory, with its values in little endian bytes.
+struct Entry {
+    tag: u16,
+    field_type: u16,
+    count: u64,
+    data: Vec<u8>,
+}
+
+impl Entry {
+    fn new(tag: u16, field_type: u16, count: u64, data: &[u8]) -> Self {
+        Entry {
+            tag,
+            field_type,
+            count,
+            data: data.to_vec(),
+        }
+    }
+}
+
+fn shorts(values: &[u16]) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect()
+}
+
+fn tiles_across(size: u32) -> u32 {
+    size.div_ceil(TILE_SIZE)
+}
+
+/// Reads columns of an uncompressed, 8-bit RGB or RGBA, little endian tiled
+/// TIFF, such as those `TiledTiffWriter` writes, one tile at a time.
+pub struct TiledTiffReader<R: Read + Seek> {
+    reader: R,
+    width: u32,
+    height: u32,
+    tile_width: u32,
+    tile_height: u32,
+    samples: u32,
+    offsets: Vec<u64>,
+}
+
+impl TiledTiffReader<BufReader<File>> {
+    pub fn open<P: AsRef<Path>>(path: P) -> BoxResult<Self> {
+        TiledTiffReader::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read + Seek> TiledTiffReader<R> {
+    /// Reads the header and first directory of the TIFF in `reader`.
+    pub fn new(mut reader: R) -> BoxResult<Self> {
+        // Nothing the header claims can be bigger than the file, which bounds
+        // every allocation made for it.
+        let len = reader.seek(SeekFrom::End(0))?;
+        let mut header = [0; 4];
+        reader.seek(SeekFrom::Start(0))?;
+        reader.read_exact(&mut header)?;
+        let big = match header {
+            [b'I', b'I', 42, 0] => false,
+            [b'I', b'I', 43, 0] => true,
+            _ => return Err(UNSUPPORTED.into()),
+        };
+        let directory = if big {
+            read_u64(&mut reader, 8)?
+        } else {
+            u64::from(read_u32(&mut reader, 4)?)
+        };
+        let tags = read_directory(&mut reader, directory, big, len)?;
+
+        let value = |tag: u16, default: Option<u64>| {
+            tags.get(&tag)
+                .and_then(|values| values.first().copied())
+                .or(default)
+                .ok_or(UNSUPPORTED)
+        };
+        let samples = value(SAMPLES_PER_PIXEL, Some(1))?;
+        let bits = tags.get(&BITS_PER_SAMPLE).ok_or(UNSUPPORTED)?;
+        if value(COMPRESSION, Some(1))? != 1
+            || value(PLANAR_CONFIGURATION, Some(1))? != 1
+            || value(PHOTOMETRIC_INTERPRETATION, None)? != 2
+            || !(samples == 3 || samples == 4)
+            || bits.len() != samples as usize
+            || bits.iter().any(|&bits| bits != 8)
+        {
+            return Err(UNSUPPORTED.into());
+        }
+
+        let dimension = |tag: u16| -> BoxResult<u32> {
+            match u32::try_from(value(tag, None)?) {
+                Ok(dimension) if dimension > 0 => Ok(dimension),
+                _ => Err(UNSUPPORTED.into()),
+            }
+        };
+        let reader = TiledTiffReader {
+            reader,
+            width: dimension(IMAGE_WIDTH)?,
+            height: dimension(IMAGE_LENGTH)?,
+            tile_width: dimension(TILE_WIDTH)?,
+            tile_height: dimension(TILE_LENGTH)?,
+            samples: samples as u32,
+            offsets: tags.get(&TILE_OFFSETS).cloned().ok_or(UNSUPPORTED)?,
+        };
+        let tiles = u64::from(reader.width.div_ceil(reader.tile_width))
+            * u64::from(reader.height.div_ceil(reader.tile_height));
+        let tile_bytes = u64::from(reader.tile_width)
+            * u64::from(reader.tile_height)
+            * u64::from(reader.samples);
+        if reader.offsets.len() as u64 != tiles
+            || reader
+                .offsets
+                .iter()
+                .any(|&offset| offset.checked_add(tile_bytes).is_none_or(|end| end > len))
+        {
+            return Err(UNSUPPORTED.into());
+        }
+        Ok(reader)
+    }
+}
+
+impl<R: Read + Seek> StripSource for TiledTiffReader<R> {
+    fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn read_strip(&mut self, x: u32, width: u32) -> BoxResult<DynamicImage> {
+        if width == 0 || x.checked_add(width).is_none_or(|end| end > self.width) {
+            return Err("Strip is outside the image".into());
+        }
+        let mut strip = RgbaImage::new(width, self.height);
+        let samples = self.samples as usize;
+        let tile_width = self.tile_width as usize;
+        let mut tile = vec![0; tile_width * self.tile_height as usize * samples];
+        let across = self.width.div_ceil(self.tile_width);
+        for column in x / self.tile_width..=(x + width - 1) / self.tile_width {
+            for row in 0..self.height.div_ceil(self.tile_height) {
+                let offset = self.offsets[(row * across + column) as usize];
+                self.reader.seek(SeekFrom::Start(offset))?;
+                self.reader.read_exact(&mut tile)?;
+
+                let left = column * self.tile_width;
+                let top = row * self.tile_height;
+                let columns = left.max(x)..(left + self.tile_width).min(x + width);
+                for tile_y in 0..self.tile_height.min(self.height - top) {
+                    for image_x in columns.clone() {
+                        let start =
+                            (tile_y as usize * tile_width + (image_x - left) as usize) * samples;
+                        let pixel = &tile[start..start + samples];
+                        let alpha = if samples == 4 { pixel[3] } else { 255 };
+                        strip.put_pixel(
+                            image_x - x,
+                            top + tile_y,
+                            Rgba([pixel[0], pixel[1], pixel[2], alpha]),
+                        );
+                    }
+                }
+            }
+        }
+        Ok(DynamicImage::ImageRgba8(strip))
+    }
+}
+
+/// Reads the entries of the directory at `offset`, keeping the values of
+/// the integer ones. Counts that would run past `len`, the length of the
+/// file, are rejected before anything is allocated for them.
+fn read_directory<R: Read + Seek>(
+    reader: &mut R,
+    offset: u64,
+    big: bool,
+    len: u64,
+) -> BoxResult<HashMap<u16, Vec<u64>>> {
+    let (count, entry_size) = if big {
+        (read_u64(reader, offset)?, 20)
+    } else {
+        (u64::from(read_u16(reader, offset)?), 12)
+    };
+    let first = offset + if big { 8 } else { 2 };
+    if fits(first, count, entry_size, len).is_none() {
+        return Err(UNSUPPORTED.into());
+    }
+
+    let mut tags = HashMap::new();
+    for index in 0..count {
+        let entry = first + index * entry_size;
+        let tag = read_u16(reader, entry)?;
+        let field_type = read_u16(reader, entry + 2)?;
+        let (count, field) = if big {
+            (read_u64(reader, entry + 4)?, entry + 12)
+        } else {
+            (u64::from(read_u32(reader, entry + 4)?), entry + 8)
+        };
+        let size = match field_type {
+            SHORT => 2,
+            LONG => 4,
+            LONG8 => 8,
+            _ => continue,
+        };
+        let inline = if big { 8 } else { 4 };
+        let start = if count.saturating_mul(size) <= inline {
+            field
+        } else if big {
+            read_u64(reader, field)?
+        } else {
+            u64::from(read_u32(reader, field)?)
+        };
+
+        let bytes = fits(start, count, size, len).ok_or(UNSUPPORTED)?;
+        let mut bytes = vec![0; bytes as usize];
+        reader.seek(SeekFrom::Start(start))?;
+        reader.read_exact(&mut bytes)?;
+        let values = bytes
+            .chunks(size as usize)
+            .map(|chunk| {
+                let mut value = [0; 8];
+                value[..chunk.len()].copy_from_slice(chunk);
+                u64::from_le_bytes(value)
+            })
+            .collect();
+        tags.insert(tag, values);
+    }
+    Ok(tags)
+}
+
+/// The size of `count` values of `size` bytes starting at `start`, if they
+/// end within `len` bytes.
+fn fits(start: u64, count: u64, size: u64, len: u64) -> Option<u64> {
+    let bytes = count.checked_mul(size)?;
+    match start.checked_add(bytes) {
+        Some(end) if end <= len => Some(bytes),
+        _ => None,
+    }
+}
+
+fn read_u16<R: Read + Seek>(reader: &mut R, offset: u64) -> BoxResult<u16> {
+    let mut bytes = [0; 2];
+    reader.seek(SeekFrom::Start(offset))?;
+    reader.read_exact(&mut bytes)?;
+    Ok(u16::from_le_bytes(bytes))
+}
+
+fn read_u32<R: Read + Seek>(reader: &mut R, offset: u64) -> BoxResult<u32> {
+    let mut bytes = [0; 4];
+    reader.seek(SeekFrom::Start(offset))?;
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64<R: Read + Seek>(reader: &mut R, offset: u64) -> BoxResult<u64> {
+    let mut bytes = [0; 8];
+    reader.seek(SeekFrom::Start(offset))?;
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
@@ -0,0 +1,324 @@
+use std::convert::TryFrom;
+use std::io::{Seek, Write};
+use std::time::Instant;
+
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, GenericImage, GenericImageView, GrayImage};
+
+use crate::carve::{Carver, CarverOptions, Direction, Roi};
+use crate::progress::{Control, Observer, Progress};
+use crate::resize::{ResizeOptions, ResizeStats};
+use crate::tiff::TiledTiffWriter;
+use crate::BoxResult;
+
+/// A full height image that can be read a few columns at a time, so it
+/// needn't be held in memory whole.
+pub trait StripSource {
+    /// The width and height of the whole image.
+    fn size(&self) -> (u32, u32);
+
+    /// Reads the `width` columns starting at column `x`.
+    fn read_strip(&mut self, x: u32, width: u32) -> BoxResult<DynamicImage>;
+}
+
+impl StripSource for DynamicImage {
+    fn size(&self) -> (u32, u32) {
+        self.dimensions()
+    }
+
+    fn read_strip(&mut self, x: u32, width: u32) -> BoxResult<DynamicImage> {
+        if width == 0 || x + width > self.width() {
+            return Err("Strip is outside the image".into());
+        }
+        Ok(self.crop_imm(x, 0, width, self.height()))
+    }
+}
+
+/// How `resize_tiled` splits an image into strips.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TileOptions {
+    /// The most columns any strip carves. Memory use grows with this times
+    /// the image height, rather than with the whole image.
+    pub strip_width: u32,
+    /// Columns of the neighbouring strips read on either side of a strip.
+    /// They're never carved, but give the pixels along the strip's edges
+    /// the neighbours they have in the whole image, so their energy, and
+    /// the text and lines found around them, match. Must be at least one.
+    pub overlap: u32,
+}
+
+impl Default for TileOptions {
+    fn default() -> Self {
+        TileOptions {
+            strip_width: 1024,
+            overlap: 16,
+        }
+    }
+}
+
+/// Resizes the image read from `source` to the width in `options` one
+/// vertical strip at a time, streaming the result to `output` as a tiled
+/// TIFF.
+///
+/// The image is split into strips of nearly equal width, and each removes
+/// or inserts its share of the seams through its own columns only, so no
+/// seam crosses from one strip to the next. The shares come from carving a
+/// copy of the whole image scaled down to about one strip's width first, so
+/// strips with less going on in them give up more seams. That reads every
+/// strip twice. Only the width can change, and regions of interest,
+/// symmetry and layers, which span the whole image, aren't supported. A
+/// protection mask must cover the whole image.
+pub fn resize_tiled<S: StripSource, W: Write + Seek>(
+    source: &mut S,
+    options: ResizeOptions,
+    tiles: &TileOptions,
+    output: W,
+) -> BoxResult<ResizeStats> {
+    let ResizeOptions {
+        width,
+        height,
+        mut carver,
+        layers,
+        observer,
+    } = options;
+    let (input_width, input_height) = source.size();
+
+    if width == 0 || height == 0 {
+        return Err("Target dimensions must be greater than zero".into());
+    }
+    if height != input_height as usize {
+        return Err("Tiled carving can only change the width".into());
+    }
+    if carver.roi.is_some() || carver.symmetry.is_some() || !layers.is_empty() {
+        return Err("Tiled carving doesn't support regions of interest, symmetry or layers".into());
+    }
+    if tiles.strip_width == 0 {
+        return Err("Strip widths must be greater than zero".into());
+    }
+    if tiles.overlap == 0 {
+        return Err("Strips must overlap by at least one column".into());
+    }
+    let protect_mask = carver.protect_mask.take();
+    if let Some(ref mask) = protect_mask {
+        if mask.dimensions() != (input_width, input_height) {
+            return Err("Protection mask must be the same size as the image".into());
+        }
+    }
+    let strips = input_width.div_ceil(tiles.strip_width);
+    let width = match u32::try_from(width) {
+        Ok(width) if width >= strips => width,
+        _ => {
+            return Err(format!(
+                "The image is carved in {} strips, so it can't be narrowed to {} pixels",
+                strips, width
+            )
+            .into())
+        }
+    };
+
+    let mut observer = observer.unwrap_or_else(|| Box::new(|_: &Progress| Control::Continue));
+    let mut stats = ResizeStats::default();
+    let cores: Vec<u32> = (0..strips)
+        .map(|index| share(input_width, strips, index))
+        .collect();
+    let targets = if strips == 1 {
+        vec![width]
+    } else {
+        let start = Instant::now();
+        let estimate = Estimate {
+            cores: &cores,
+            height: input_height,
+            factor: input_width.div_ceil(tiles.strip_width),
+            options: &carver,
+            protect_mask: protect_mask.as_ref(),
+        };
+        let seams = estimate.seams(source, width, observer.as_mut(), &mut stats)?;
+        stats.elapsed += start.elapsed();
+        cores
+            .iter()
+            .zip(seams)
+            .map(|(&core, seams)| {
+                if width < input_width {
+                    core - seams
+                } else {
+                    core + seams
+                }
+            })
+            .collect()
+    };
+
+    let mut writer = TiledTiffWriter::new(output, width, input_height)?;
+    let mut x = 0u32;
+    for (&core, &target) in cores.iter().zip(&targets) {
+        let left = x.saturating_sub(tiles.overlap);
+        let right = (x + core + tiles.overlap).min(input_width);
+        let strip = source.read_strip(left, right - left)?;
+
+        let mut options = carver.clone();
+        options.protect_mask = protect_mask
+            .as_ref()
+            .map(|mask| imageops::crop_imm(mask, left, 0, right - left, input_height).to_image());
+        options.roi = Some(Roi::Rect {
+            x: x - left,
+            y: 0,
+            width: core,
+            height: input_height,
+        });
+        let start = Instant::now();
+        let mut strip_carver = Carver::with_options(&strip, options)?;
+        let strip_target = (right - left - core + target) as usize;
+        strip_carver.check_roi(Direction::Vertical, strip_target)?;
+        strip_carver.carve(Direction::Vertical, strip_target, observer.as_mut())?;
+        stats.elapsed += start.elapsed();
+
+        // The overlap on the left is never carved, so the strip's own
+        // columns still start where they did.
+        let carved = strip_carver.image();
+        writer.write_columns(&carved.crop_imm(x - left, 0, target, input_height))?;
+
+        let seams = strip_carver.seam_counts();
+        stats.seams.vertical_removed += seams.vertical_removed;
+        stats.seams.vertical_inserted += seams.vertical_inserted;
+        stats.passes.extend_from_slice(strip_carver.pass_stats());
+        stats.peak_memory_bytes = stats
+            .peak_memory_bytes
+            .max(strip_carver.peak_memory_bytes());
+        x += core;
+    }
+    writer.finish()?;
+    Ok(stats)
+}
+
+/// A copy of the whole image scaled down by `factor`, a strip at a time, to
+/// find where its seams would go.
+struct Estimate<'a> {
+    cores: &'a [u32],
+    height: u32,
+    factor: u32,
+    options: &'a CarverOptions,
+    protect_mask: Option<&'a GrayImage>,
+}
+
+impl Estimate<'_> {
+    /// How many of the seams taking the image to `width` each strip gets,
+    /// in proportion to how many seams through the scaled down copy fall in
+    /// its columns. Narrowing leaves every strip at least one column.
+    fn seams<S: StripSource>(
+        &self,
+        source: &mut S,
+        width: u32,
+        observer: &mut dyn Observer,
+        stats: &mut ResizeStats,
+    ) -> BoxResult<Vec<u32>> {
+        let scaled = |size: u32| size.div_ceil(self.factor);
+        let widths: Vec<u32> = self.cores.iter().map(|&core| scaled(core)).collect();
+        let proxy_width: u32 = widths.iter().sum();
+        let proxy_height = scaled(self.height);
+
+        let mut proxy = DynamicImage::new_rgba8(proxy_width, proxy_height);
+        let mut mask = self
+            .protect_mask
+            .map(|_| GrayImage::new(proxy_width, proxy_height));
+        let (mut x, mut proxy_x) = (0, 0);
+        for (&core, &proxy_core) in self.cores.iter().zip(&widths) {
+            let strip = source.read_strip(x, core)?;
+            proxy.copy_from(
+                &strip.resize_exact(proxy_core, proxy_height, FilterType::Triangle),
+                proxy_x,
+                0,
+            )?;
+            if let (Some(mask), Some(protect_mask)) = (mask.as_mut(), self.protect_mask) {
+                let strip = imageops::crop_imm(protect_mask, x, 0, core, self.height).to_image();
+                let strip =
+                    imageops::resize(&strip, proxy_core, proxy_height, FilterType::Triangle);
+                mask.copy_from(&strip, proxy_x, 0)?;
+            }
+            x += core;
+            proxy_x += proxy_core;
+        }
+
+        // Text and lines are found at full resolution only.
+        let options = CarverOptions {
+            protect_mask: mask,
+            text: None,
+            lines: None,
+            low_memory: false,
+            seam_log: true,
+            ..self.options.clone()
+        };
+        let total = x.abs_diff(width);
+        let proxy_target = if width < x {
+            proxy_width - scaled(total).min(proxy_width - 1)
+        } else {
+            proxy_width + scaled(total)
+        };
+        let mut carver = Carver::with_options(&proxy, options)?;
+        carver.carve(Direction::Vertical, proxy_target as usize, observer)?;
+        stats.peak_memory_bytes = stats.peak_memory_bytes.max(carver.peak_memory_bytes());
+
+        let mut weights = vec![0u64; self.cores.len()];
+        for point in carver.get_seam_points() {
+            let mut end = 0;
+            let strip = widths
+                .iter()
+                .position(|&width| {
+                    end += width;
+                    (point.x as u32) < end
+                })
+                .unwrap_or(widths.len() - 1);
+            weights[strip] += 1;
+        }
+        let caps: Vec<u32> = if width < x {
+            self.cores.iter().map(|&core| core - 1).collect()
+        } else {
+            vec![u32::MAX; self.cores.len()]
+        };
+        Ok(apportion(total, &weights, &caps))
+    }
+}
+
+/// Splits `total` into parts in proportion to `weights`, none more than its
+/// cap, handing out what's left after rounding down by the largest
+/// remainders. Parts with no weight only get what the others have no room
+/// for. The caps must add up to at least `total`.
+fn apportion(total: u32, weights: &[u64], caps: &[u32]) -> Vec<u32> {
+    let mut parts = vec![0; weights.len()];
+    let mut remaining = total;
+    // Each round shares what's left among the parts below their caps.
+    while remaining > 0 {
+        let open: Vec<usize> = (0..parts.len())
+            .filter(|&index| parts[index] < caps[index])
+            .collect();
+        let mut weight: u128 = open.iter().map(|&index| u128::from(weights[index])).sum();
+        let even = weight == 0;
+        if even {
+            weight = open.len() as u128;
+        }
+
+        let mut given = 0;
+        let mut remainders = vec![];
+        for &index in &open {
+            let share = u128::from(remaining) * if even { 1 } else { u128::from(weights[index]) };
+            let part = ((share / weight) as u32).min(caps[index] - parts[index]);
+            parts[index] += part;
+            given += part;
+            remainders.push((share % weight, index));
+        }
+        remaining -= given;
+
+        if given == 0 {
+            remainders.sort_unstable_by(|a, b| b.cmp(a));
+            for (_, index) in remainders.into_iter().take(remaining as usize) {
+                parts[index] += 1;
+                remaining -= 1;
+            }
+        }
+    }
+    parts
+}
+
+/// The `index`th of `parts` nearly equal parts of `total`, with the larger
+/// parts first.
+fn share(total: u32, parts: u32, index: u32) -> u32 {
+    total / parts + u32::from(index < total % parts)
+}
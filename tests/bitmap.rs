@@ -0,0 +1,28 @@
+use rmr::bitmap::Bitmap;
+
+#[test]
+fn bitmap_new_is_cleared_test() {
+    let bitmap = Bitmap::new(130);
+
+    assert_eq!(130, bitmap.len());
+    assert!((0..130).all(|index| !bitmap.get(index)));
+}
+
+#[test]
+fn bitmap_set_test() {
+    let mut bitmap = Bitmap::new(130);
+
+    for &index in &[0, 63, 64, 129] {
+        bitmap.set(index, true);
+    }
+    bitmap.set(63, false);
+
+    let set: Vec<usize> = (0..130).filter(|&index| bitmap.get(index)).collect();
+    assert_eq!(vec![0, 64, 129], set);
+}
+
+#[test]
+#[should_panic]
+fn bitmap_out_of_bounds_test() {
+    Bitmap::new(64).get(64);
+}
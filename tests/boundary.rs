@@ -0,0 +1,111 @@
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+
+use rmr::carve::Direction;
+use rmr::energy::Boundary;
+use rmr::ResizeOptions;
+
+const BOUNDARIES: [Boundary; 4] = [
+    Boundary::Wrap,
+    Boundary::Clamp,
+    Boundary::Mirror,
+    Boundary::Fixed,
+];
+
+#[test]
+fn boundary_ramp_test() {
+    // Wrapping compares the black left edge with the white right edge, so
+    // the edges look like the strongest detail in the image. Clamping and
+    // mirroring see the edges as flatter than the middle.
+    let input = make_ramp(16, 8);
+    let expected = [
+        (Boundary::Wrap, false),
+        (Boundary::Clamp, true),
+        (Boundary::Mirror, true),
+        (Boundary::Fixed, false),
+    ];
+
+    for &(boundary, on_edge) in &expected {
+        let options = ResizeOptions::new(15, 8).boundary(boundary);
+
+        let output = rmr::resize(&input, options).unwrap();
+
+        assert_eq!(8, output.seams.len());
+        for point in output.seams {
+            let edge = point.x == 0 || point.x == 15;
+            assert_eq!(on_edge, edge, "{:?} removed {:?}", boundary, point);
+        }
+    }
+}
+
+#[test]
+fn boundary_fixed_keeps_edges_test() {
+    // Every pixel of a flat image has the same energy apart from the edges.
+    let input = DynamicImage::ImageRgba8(RgbaImage::from_pixel(10, 6, Rgba([90, 40, 200, 255])));
+
+    for &(width, height) in &[(3, 6), (10, 3), (4, 4)] {
+        let options = ResizeOptions::new(width, height).boundary(Boundary::Fixed);
+
+        let output = rmr::resize(&input, options).unwrap();
+
+        // Seams have to cross the edges at their ends, but never run along
+        // them.
+        for point in output.seams {
+            let along_edge = match point.direction {
+                Direction::Vertical => point.x == 0 || point.x == 9,
+                Direction::Horizontal => point.y == 0 || point.y == 5,
+            };
+            assert!(!along_edge, "{:?} is on the edge", point);
+        }
+    }
+}
+
+#[test]
+fn boundary_threads_match_single_thread_test() {
+    let input = load(CASTLE).resize(96, 96, FilterType::Triangle);
+    let (width, height) = input.dimensions();
+    let (width, height) = (width as usize - 12, height as usize + 6);
+
+    for &boundary in &BOUNDARIES {
+        let options = || ResizeOptions::new(width, height).boundary(boundary);
+        let single = rmr::resize(&input, options()).unwrap();
+        let threaded = rmr::resize(&input, options().threads(4)).unwrap();
+
+        assert_eq!(
+            single.image.to_rgba8(),
+            threaded.image.to_rgba8(),
+            "{:?}",
+            boundary
+        );
+        assert_eq!(single.seams, threaded.seams, "{:?}", boundary);
+    }
+}
+
+#[test]
+fn boundary_neighbors_test() {
+    assert_eq!((4, 1), Boundary::Wrap.neighbors(0, 5));
+    assert_eq!((3, 0), Boundary::Wrap.neighbors(4, 5));
+    assert_eq!((0, 1), Boundary::Clamp.neighbors(0, 5));
+    assert_eq!((3, 4), Boundary::Clamp.neighbors(4, 5));
+    assert_eq!((1, 1), Boundary::Mirror.neighbors(0, 5));
+    assert_eq!((3, 3), Boundary::Mirror.neighbors(4, 5));
+    assert_eq!((1, 3), Boundary::Mirror.neighbors(2, 5));
+
+    for &boundary in &BOUNDARIES {
+        assert_eq!((0, 0), boundary.neighbors(0, 1), "{:?}", boundary);
+    }
+}
+
+static CASTLE: &[u8] = include_bytes!("../images/castle.jpg");
+
+fn load(bytes: &[u8]) -> DynamicImage {
+    image::load_from_memory(bytes).expect("loaded test image")
+}
+
+/// Columns ramping from black on the left to white on the right.
+fn make_ramp(width: u32, height: u32) -> DynamicImage {
+    DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |x, _| {
+        let value = (x * 255 / (width - 1)) as u8;
+        Rgba([value, value, value, 255])
+    }))
+}
@@ -1,6 +1,7 @@
 use image::{DynamicImage, GenericImageView};
 
-use rmr::carve::Carver;
+use rmr::carve::{Carver, Direction};
+use rmr::progress::{Control, Progress};
 
 macro_rules! test_carve {
     ( $target:expr, $dw:expr, $dh:expr ) => {
@@ -10,8 +11,8 @@ macro_rules! test_carve {
         let target_width = (width as isize + $dw) as usize;
         let target_height = (height as isize + $dh) as usize;
 
-        let mut carver = Carver::new(&input);
-        let output = carver.resize(target_width, target_height);
+        let mut carver = Carver::new(&input).unwrap();
+        let output = carver.resize(target_width, target_height).unwrap();
 
         let target = load($target);
         if let Err(msg) = compare_images(&target, &output) {
@@ -54,6 +55,45 @@ fn carver_both_plus_five_test() {
     test_carve!(BOTH_PLUS_FIVE, 5, 5);
 }
 
+#[test]
+fn carver_horizontal_matches_transposed_vertical_test() {
+    let input = load(INPUT);
+    let transposed = transpose(&input);
+    let (width, height) = input.dimensions();
+
+    for &target in &[height as usize - 7, height as usize + 7] {
+        let mut carver = Carver::new(&input).unwrap();
+        carver
+            .carve(Direction::Horizontal, target, &mut |_: &Progress| {
+                Control::Continue
+            })
+            .unwrap();
+        let horizontal = carver.image();
+
+        let mut carver = Carver::new(&transposed).unwrap();
+        let vertical = carver.resize(target, width as usize).unwrap();
+
+        compare_images(&transpose(&vertical), &horizontal).unwrap();
+    }
+}
+
+#[test]
+fn carver_horizontal_seam_points_test() {
+    let input = load(INPUT);
+    let (width, height) = input.dimensions();
+
+    let mut carver = Carver::new(&input).unwrap();
+    carver.resize(width as usize, height as usize - 3).unwrap();
+    let points = carver.get_seam_points();
+
+    // One point per column for each seam, all within the input image.
+    assert_eq!(3 * width as usize, points.len());
+    for point in points {
+        assert_eq!(Direction::Horizontal, point.direction);
+        assert!(point.x < width as usize && point.y < height as usize);
+    }
+}
+
 static INPUT: &'static [u8; 7256] = include_bytes!("images/input.png");
 static WIDTH_MINUS_FIVE: &'static [u8; 6944] = include_bytes!("images/out-width-minus-five.png");
 static WIDTH_PLUS_FIVE: &'static [u8; 7550] = include_bytes!("images/out-width-plus-five.png");
@@ -78,3 +118,7 @@ fn compare_images(target: &DynamicImage, image: &DynamicImage) -> Result<(), &'s
 
     Ok(())
 }
+
+fn transpose(image: &DynamicImage) -> DynamicImage {
+    image.rotate90().fliph()
+}
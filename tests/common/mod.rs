@@ -0,0 +1,48 @@
+use image::imageops::FilterType;
+use image::GenericImageView;
+
+use rmr::carve::{SeamConstraint, Strategy};
+use rmr::ResizeOptions;
+
+static CASTLE: &[u8] = include_bytes!("../../images/castle.jpg");
+
+/// Checks that seams found with `strategy` cost close to the best seams, and
+/// far less than seams that ignore the content.
+pub fn assert_strategy_quality(strategy: Strategy) {
+    // A quarter of the full castle keeps the full resolution run quick.
+    let input = image::load_from_memory(CASTLE)
+        .expect("loaded test image")
+        .resize(512, 512, FilterType::Triangle);
+    let (width, height) = input.dimensions();
+    let (width, height) = (width as usize - 40, height as usize - 20);
+    let seam_energy = |options: ResizeOptions| -> u64 {
+        let output = rmr::resize(&input, options).unwrap();
+        assert_eq!((width as u32, height as u32), output.image.dimensions());
+        output
+            .stats
+            .passes
+            .iter()
+            .map(|pass| pass.seam_energy)
+            .sum()
+    };
+
+    let full = seam_energy(ResizeOptions::new(width, height));
+    let guided = seam_energy(ResizeOptions::new(width, height).strategy(strategy));
+    let straight =
+        seam_energy(ResizeOptions::new(width, height).seam_constraint(SeamConstraint::straight()));
+
+    assert!(
+        (guided as f64) < full as f64 * 1.4,
+        "{:?} seams removed {} energy, full resolution seams {}",
+        strategy,
+        guided,
+        full
+    );
+    assert!(
+        guided < straight,
+        "{:?} seams removed {} energy, straight seams {}",
+        strategy,
+        guided,
+        straight
+    );
+}
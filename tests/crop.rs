@@ -0,0 +1,108 @@
+use image::{DynamicImage, GenericImageView};
+
+use rmr::carve::{Direction, ResizeMode};
+use rmr::crop::{find_crop_window, CropWindow};
+use rmr::ResizeOptions;
+
+#[test]
+fn crop_find_window_test() {
+    let columns = [5, 1, 9, 9, 2, 7];
+
+    assert_eq!(
+        CropWindow {
+            start: 2,
+            len: 2,
+            removed_energy: 15,
+        },
+        find_crop_window(&columns, 2)
+    );
+    assert_eq!(
+        CropWindow {
+            start: 2,
+            len: 4,
+            removed_energy: 6,
+        },
+        find_crop_window(&columns, 4)
+    );
+    assert_eq!(0, find_crop_window(&columns, 6).removed_energy);
+}
+
+#[test]
+fn crop_find_window_tie_test() {
+    assert_eq!(0, find_crop_window(&[3, 3, 3], 2).start);
+}
+
+#[test]
+fn crop_mode_test() {
+    let input = load(INPUT);
+    let (width, height) = input.dimensions();
+    let (target_width, target_height) = (width - 4, height - 3);
+    let options =
+        ResizeOptions::new(target_width as usize, target_height as usize).mode(ResizeMode::Crop);
+
+    let output = rmr::resize(&input, options).unwrap();
+
+    assert!(output.stats.passes.iter().all(|pass| pass.cropped));
+    assert_eq!(
+        4 * height as usize + 3 * target_width as usize,
+        output.seams.len()
+    );
+
+    // The result is a window of the input somewhere.
+    let found = (0..=4).any(|x| {
+        (0..=3).any(|y| {
+            let window = input.crop_imm(x, y, target_width, target_height);
+            window
+                .pixels()
+                .all(|(px, py, pixel)| output.image.get_pixel(px, py) == pixel)
+        })
+    });
+    assert!(found);
+}
+
+#[test]
+fn crop_auto_mode_test() {
+    let input = load(INPUT);
+    let (width, height) = input.dimensions();
+    let resize = |mode| {
+        let options = ResizeOptions::new(width as usize - 6, height as usize).mode(mode);
+        rmr::resize(&input, options).unwrap()
+    };
+
+    let carved = resize(ResizeMode::Carve);
+    let cropped = resize(ResizeMode::Crop);
+    let auto = resize(ResizeMode::Auto);
+
+    let pass = &auto.stats.passes[0];
+    assert_eq!(Direction::Vertical, pass.direction);
+    assert_eq!(6, pass.seams_removed);
+    assert_eq!(
+        carved.stats.passes[0]
+            .seam_energy
+            .min(cropped.stats.passes[0].seam_energy),
+        pass.seam_energy
+    );
+    assert_eq!(
+        pass.seam_energy < carved.stats.passes[0].seam_energy,
+        pass.cropped
+    );
+}
+
+#[test]
+fn crop_mode_enlarges_by_carving_test() {
+    let input = load(INPUT);
+    let (width, height) = input.dimensions();
+    let options = ResizeOptions::new(width as usize + 2, height as usize).mode(ResizeMode::Crop);
+
+    let output = rmr::resize(&input, options).unwrap();
+
+    assert_eq!((width + 2, height), output.image.dimensions());
+    assert!(!output.stats.passes[0].cropped);
+    assert_eq!(2, output.stats.passes[0].seams_inserted);
+}
+
+static INPUT: &[u8; 7256] = include_bytes!("images/input.png");
+
+fn load(bytes: &[u8]) -> DynamicImage {
+    image::load_from_memory(bytes).expect("loaded test image")
+}
@@ -0,0 +1,100 @@
+use image::{DynamicImage, GenericImageView, Rgba};
+
+use rmr::carve::{Carver, Direction, SeamKind, SeamPoint};
+use rmr::debug::{create_debug_image, seam_color, DebugOptions};
+
+#[test]
+fn debug_seam_points_direction_and_kind_test() {
+    let input = load(INPUT);
+    let (width, height) = input.dimensions();
+
+    let mut carver = Carver::new(&input).unwrap();
+    carver
+        .resize(width as usize - 2, height as usize + 3)
+        .unwrap();
+    let points = carver.get_seam_points();
+
+    let count = |direction, kind| {
+        points
+            .iter()
+            .filter(|p| p.direction == direction && p.kind == kind)
+            .count()
+    };
+
+    assert_eq!(
+        2 * height as usize,
+        count(Direction::Vertical, SeamKind::Removed)
+    );
+    assert_eq!(0, count(Direction::Vertical, SeamKind::Inserted));
+    assert_eq!(0, count(Direction::Horizontal, SeamKind::Removed));
+    assert_eq!(
+        3 * (width as usize - 2),
+        count(Direction::Horizontal, SeamKind::Inserted)
+    );
+}
+
+#[test]
+fn debug_opaque_overlay_test() {
+    let input = load(INPUT);
+    let points = vec![
+        point(0, 0, Direction::Vertical, SeamKind::Removed),
+        point(1, 0, Direction::Horizontal, SeamKind::Inserted),
+    ];
+
+    let output = create_debug_image(&input, &points, &DebugOptions::default());
+
+    assert_eq!(input.dimensions(), output.dimensions());
+    assert_eq!(Rgba([255, 0, 0, 255]), output.get_pixel(0, 0));
+    assert_eq!(
+        seam_color(Direction::Horizontal, SeamKind::Inserted),
+        output.get_pixel(1, 0)
+    );
+    assert_eq!(input.get_pixel(2, 0), output.get_pixel(2, 0));
+}
+
+#[test]
+fn debug_alpha_overlay_test() {
+    let input = DynamicImage::new_rgba8(2, 2);
+    let points = vec![point(0, 0, Direction::Vertical, SeamKind::Removed)];
+    let options = DebugOptions {
+        alpha: 0.5,
+        legend: false,
+    };
+
+    let output = create_debug_image(&input, &points, &options);
+
+    assert_eq!(Rgba([128, 0, 0, 128]), output.get_pixel(0, 0));
+    assert_eq!(Rgba([0, 0, 0, 0]), output.get_pixel(1, 1));
+}
+
+#[test]
+fn debug_legend_test() {
+    let input = load(INPUT);
+    let options = DebugOptions {
+        alpha: 1.0,
+        legend: true,
+    };
+
+    let output = create_debug_image(&input, &[], &options);
+
+    assert!(output.height() > input.height());
+    assert!(output.width() >= input.width());
+    for (x, y, pixel) in input.pixels() {
+        assert_eq!(pixel, output.get_pixel(x, y));
+    }
+}
+
+static INPUT: &[u8; 7256] = include_bytes!("images/input.png");
+
+fn load(bytes: &[u8]) -> DynamicImage {
+    image::load_from_memory(bytes).expect("loaded test image")
+}
+
+fn point(x: usize, y: usize, direction: Direction, kind: SeamKind) -> SeamPoint {
+    SeamPoint {
+        x,
+        y,
+        direction,
+        kind,
+    }
+}
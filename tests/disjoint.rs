@@ -0,0 +1,64 @@
+use image::{DynamicImage, GenericImageView};
+
+use rmr::carve::{Direction, SeamKind, Strategy};
+use rmr::ResizeOptions;
+
+mod common;
+
+#[test]
+fn disjoint_single_seam_matches_full_resolution_test() {
+    let input = load(INPUT);
+    let (width, height) = input.dimensions();
+    let (width, height) = (width as usize - 4, height as usize - 3);
+
+    let full = rmr::resize(&input, ResizeOptions::new(width, height)).unwrap();
+    let disjoint = rmr::resize(
+        &input,
+        ResizeOptions::new(width, height).strategy(Strategy::Disjoint { seams: 1 }),
+    )
+    .unwrap();
+
+    assert_eq!(full.seams, disjoint.seams);
+}
+
+#[test]
+fn disjoint_seams_are_valid_test() {
+    let input = load(INPUT);
+    let (width, height) = input.dimensions();
+    let options = ResizeOptions::new(width as usize - 12, height as usize - 5)
+        .strategy(Strategy::Disjoint { seams: 5 });
+
+    let output = rmr::resize(&input, options).unwrap();
+
+    assert_eq!((width - 12, height - 5), output.image.dimensions());
+
+    // Every row loses exactly one pixel per vertical seam, and no pixel is
+    // removed twice.
+    let mut removed = vec![0; height as usize];
+    let mut points: Vec<_> = output
+        .seams
+        .iter()
+        .filter(|point| point.direction == Direction::Vertical)
+        .map(|point| {
+            assert_eq!(SeamKind::Removed, point.kind);
+            removed[point.y] += 1;
+            (point.x, point.y)
+        })
+        .collect();
+    assert!(removed.iter().all(|&count| count == 12));
+    let count = points.len();
+    points.sort();
+    points.dedup();
+    assert_eq!(count, points.len());
+}
+
+#[test]
+fn disjoint_quality_test() {
+    common::assert_strategy_quality(Strategy::Disjoint { seams: 8 });
+}
+
+static INPUT: &[u8; 7256] = include_bytes!("images/input.png");
+
+fn load(bytes: &[u8]) -> DynamicImage {
+    image::load_from_memory(bytes).expect("loaded test image")
+}
@@ -0,0 +1,90 @@
+use image::{DynamicImage, GenericImageView, ImageFormat};
+
+use rmr::encode::{write_image, EncodeOptions, PngCompression};
+
+#[test]
+fn encode_jpeg_quality_test() {
+    let input = load(INPUT);
+
+    let encode = |quality| {
+        let options = EncodeOptions {
+            quality,
+            ..EncodeOptions::default()
+        };
+        let mut bytes = vec![];
+        write_image(&input, &mut bytes, ImageFormat::Jpeg, &options).unwrap();
+        bytes
+    };
+
+    let low = encode(10);
+    let high = encode(95);
+
+    assert_eq!(
+        Ok(ImageFormat::Jpeg),
+        image::guess_format(&low).map_err(|_| ())
+    );
+    assert!(low.len() < high.len());
+}
+
+#[test]
+fn encode_png_compression_round_trip_test() {
+    let input = load(INPUT);
+
+    for &compression in &[
+        PngCompression::Fast,
+        PngCompression::Default,
+        PngCompression::Best,
+        PngCompression::Huffman,
+        PngCompression::Rle,
+    ] {
+        let options = EncodeOptions {
+            png_compression: compression,
+            ..EncodeOptions::default()
+        };
+        let mut bytes = vec![];
+        write_image(&input, &mut bytes, ImageFormat::Png, &options).unwrap();
+
+        let output = image::load_from_memory(&bytes).unwrap();
+        assert_eq!(input.dimensions(), output.dimensions());
+        for (x, y, pixel) in input.pixels() {
+            assert_eq!(pixel, output.get_pixel(x, y));
+        }
+    }
+}
+
+#[test]
+fn encode_other_format_test() {
+    let input = load(INPUT);
+    let mut bytes = vec![];
+
+    write_image(
+        &input,
+        &mut bytes,
+        ImageFormat::Bmp,
+        &EncodeOptions::default(),
+    )
+    .unwrap();
+
+    assert_eq!(b"BM", &bytes[..2]);
+}
+
+#[test]
+fn encode_webp_unsupported_test() {
+    let input = load(INPUT);
+    let mut bytes = vec![];
+
+    let result = write_image(
+        &input,
+        &mut bytes,
+        ImageFormat::WebP,
+        &EncodeOptions::default(),
+    );
+
+    assert!(result.is_err());
+}
+
+static INPUT: &[u8; 7256] = include_bytes!("images/input.png");
+
+fn load(bytes: &[u8]) -> DynamicImage {
+    image::load_from_memory(bytes).expect("loaded test image")
+}
@@ -0,0 +1,179 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use image::{DynamicImage, GenericImageView, GrayImage, Luma, Rgba, RgbaImage};
+
+use rmr::carve::SeamOrder;
+use rmr::ResizeOptions;
+
+/// The lowest peak signal-to-noise ratio, in decibels, an output may have
+/// against its golden image. Override with `RMR_GOLDEN_PSNR`.
+const DEFAULT_MIN_PSNR: f64 = 40.0;
+
+/// Set `RMR_BLESS=1` to write the current outputs as the golden images.
+macro_rules! test_golden {
+    ( $name:expr, $dw:expr, $dh:expr $(, $option:ident($($arg:expr),*))* ) => {
+        let input = load(INPUT);
+        let (width, height) = input.dimensions();
+        let target_width = (width as isize + $dw) as usize;
+        let target_height = (height as isize + $dh) as usize;
+
+        let options = ResizeOptions::new(target_width, target_height)$(.$option($($arg),*))*;
+        let output = rmr::resize(&input, options).unwrap();
+
+        check_golden($name, &output.image);
+    };
+}
+
+#[test]
+fn golden_width_minus_five_test() {
+    test_golden!("width-minus-five", -5, 0);
+}
+
+#[test]
+fn golden_width_plus_five_test() {
+    test_golden!("width-plus-five", 5, 0);
+}
+
+#[test]
+fn golden_height_minus_five_test() {
+    test_golden!("height-minus-five", 0, -5);
+}
+
+#[test]
+fn golden_height_plus_five_test() {
+    test_golden!("height-plus-five", 0, 5);
+}
+
+#[test]
+fn golden_both_minus_five_test() {
+    test_golden!("both-minus-five", -5, -5);
+}
+
+#[test]
+fn golden_both_plus_five_test() {
+    test_golden!("both-plus-five", 5, 5);
+}
+
+#[test]
+fn golden_height_first_test() {
+    test_golden!(
+        "height-first-minus-ten-plus-ten",
+        -10,
+        10,
+        seam_order(SeamOrder::HeightFirst)
+    );
+}
+
+#[test]
+fn golden_mask_shrink_test() {
+    test_golden!("mask-minus-ten", -10, -10, protect_mask(make_mask()));
+}
+
+#[test]
+fn golden_mask_grow_test() {
+    test_golden!("mask-plus-ten", 10, 10, protect_mask(make_mask()));
+}
+
+static INPUT: &[u8] = include_bytes!("images/input.png");
+
+fn load(bytes: &[u8]) -> DynamicImage {
+    image::load_from_memory(bytes).expect("loaded test image")
+}
+
+/// Protects a block in the middle of the input.
+fn make_mask() -> GrayImage {
+    let (width, height) = load(INPUT).dimensions();
+    GrayImage::from_fn(width, height, |x, y| {
+        let inside = x >= width / 3 && x < width / 2 && y >= height / 3 && y < height / 2;
+        Luma([if inside { 255 } else { 0 }])
+    })
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/images")
+        .join(format!("out-{}.png", name))
+}
+
+/// Compares `image` with the golden image called `name`, writing the output
+/// and a difference image to the target directory if it doesn't match.
+fn check_golden(name: &str, image: &DynamicImage) {
+    let path = golden_path(name);
+    if env::var_os("RMR_BLESS").is_some() {
+        image.save(&path).unwrap();
+        return;
+    }
+
+    let golden =
+        image::open(&path).unwrap_or_else(|e| panic!("Couldn't open {}: {}", path.display(), e));
+    let min_psnr = env::var("RMR_GOLDEN_PSNR")
+        .map(|psnr| psnr.parse().expect("RMR_GOLDEN_PSNR should be a number"))
+        .unwrap_or(DEFAULT_MIN_PSNR);
+
+    let failure = if golden.dimensions() != image.dimensions() {
+        Some(format!(
+            "{} is {:?}, expected {:?}.",
+            name,
+            image.dimensions(),
+            golden.dimensions()
+        ))
+    } else {
+        let psnr = psnr(&golden.to_rgba8(), &image.to_rgba8());
+        if psnr < min_psnr {
+            Some(format!(
+                "{} has a PSNR of {:.2} dB, expected at least {:.2} dB.",
+                name, psnr, min_psnr
+            ))
+        } else {
+            None
+        }
+    };
+
+    if let Some(message) = failure {
+        let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden");
+        fs::create_dir_all(&dir).unwrap();
+        let actual = dir.join(format!("{}-actual.png", name));
+        image.save(&actual).unwrap();
+        let mut saved = format!("Saved to: {}", actual.display());
+        if golden.dimensions() == image.dimensions() {
+            let diff = dir.join(format!("{}-diff.png", name));
+            diff_image(&golden.to_rgba8(), &image.to_rgba8())
+                .save(&diff)
+                .unwrap();
+            saved = format!("{} and {}", saved, diff.display());
+        }
+        panic!("{} {}", message, saved);
+    }
+}
+
+/// The peak signal-to-noise ratio of `image` against `golden` over every
+/// channel. Identical images have an infinite PSNR.
+fn psnr(golden: &RgbaImage, image: &RgbaImage) -> f64 {
+    let squared_error: f64 = golden
+        .pixels()
+        .zip(image.pixels())
+        .flat_map(|(a, b)| a.0.iter().zip(b.0.iter()).map(|(&a, &b)| (a, b)))
+        .map(|(a, b)| (f64::from(a) - f64::from(b)).powi(2))
+        .sum();
+    let mse = squared_error / (golden.len() as f64);
+    if mse == 0.0 {
+        return f64::INFINITY;
+    }
+    10.0 * (255.0 * 255.0 / mse).log10()
+}
+
+/// An opaque image of the absolute difference of each color channel,
+/// brightened so small differences are visible.
+fn diff_image(golden: &RgbaImage, image: &RgbaImage) -> RgbaImage {
+    RgbaImage::from_fn(golden.width(), golden.height(), |x, y| {
+        let (a, b) = (golden.get_pixel(x, y), image.get_pixel(x, y));
+        let mut pixel = [0, 0, 0, 255];
+        for channel in 0..3 {
+            let difference = (i16::from(a[channel]) - i16::from(b[channel])).unsigned_abs();
+            pixel[channel] = (difference * 4).min(255) as u8;
+        }
+        Rgba(pixel)
+    })
+}
@@ -1,3 +1,5 @@
+use rmr::carve::Direction;
+use rmr::energy::Boundary;
 use rmr::grid::Grid;
 
 // Test grid visualized:
@@ -181,6 +183,73 @@ fn grid_get_adjacent_test() {
     assert_eq!(&2, down);
 }
 
+#[test]
+fn grid_get_adjacent_within_test() {
+    let grid = make_test_grid();
+    let corners = [(0, 0), (2, 0), (1, 1), (0, 2), (2, 2)];
+    let expected = [
+        (
+            Boundary::Clamp,
+            [
+                (0, 1, 0, 3),
+                (1, 2, 2, 5),
+                (3, 5, 1, 7),
+                (6, 7, 3, 6),
+                (7, 8, 5, 8),
+            ],
+        ),
+        (
+            Boundary::Mirror,
+            [
+                (1, 1, 3, 3),
+                (1, 1, 5, 5),
+                (3, 5, 1, 7),
+                (7, 7, 3, 3),
+                (7, 7, 5, 5),
+            ],
+        ),
+        (
+            Boundary::Fixed,
+            [
+                (0, 1, 0, 3),
+                (1, 2, 2, 5),
+                (3, 5, 1, 7),
+                (6, 7, 3, 6),
+                (7, 8, 5, 8),
+            ],
+        ),
+    ];
+
+    for &(boundary, adjacent) in &expected {
+        for (&(x, y), &(left, right, up, down)) in corners.iter().zip(adjacent.iter()) {
+            assert_eq!(
+                (&left, &right, &up, &down),
+                grid.get_adjacent_within(x, y, boundary),
+                "{:?} at ({}, {})",
+                boundary,
+                x,
+                y
+            );
+        }
+    }
+    for &(x, y) in &corners {
+        assert_eq!(
+            grid.get_adjacent(x, y),
+            grid.get_adjacent_within(x, y, Boundary::Wrap)
+        );
+    }
+}
+
+#[test]
+fn grid_get_adjacent_within_single_column_test() {
+    let grid = Grid::new(vec![vec![0], vec![1]]);
+
+    for &boundary in &[Boundary::Wrap, Boundary::Clamp, Boundary::Mirror] {
+        let (left, right, _, _) = grid.get_adjacent_within(0, 1, boundary);
+        assert_eq!((&1, &1), (left, right), "{:?}", boundary);
+    }
+}
+
 #[test]
 fn grid_get_parents_test() {
     let grid = make_test_grid();
@@ -227,6 +296,26 @@ fn grid_iter_parents_test() {
     assert_eq!(vec![&4, &5], grid.iter_parents(2, 2).collect::<Vec<_>>());
 }
 
+#[test]
+fn grid_iter_parents_within_test() {
+    let grid = make_test_grid();
+
+    assert!(grid.iter_parents_within(1, 0, 2).next().is_none());
+    assert_eq!(
+        vec![&4],
+        grid.iter_parents_within(1, 2, 0).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        vec![&3, &4, &5],
+        grid.iter_parents_within(0, 2, 2).collect::<Vec<_>>()
+    );
+    assert_eq!(
+        vec![(0, 0, &0), (1, 0, &1), (2, 0, &2)],
+        grid.iter_parents_with_coords_within(2, 1, 5)
+            .collect::<Vec<_>>()
+    );
+}
+
 #[test]
 fn grid_get_parents_indexed_test() {
     let grid = make_test_grid();
@@ -384,6 +473,45 @@ fn grid_add_last_column_test() {
     assert_eq!(&8, grid.get(3, 2));
 }
 
+#[test]
+fn grid_remove_seam_test() {
+    let mut grid = make_test_grid();
+
+    let removed = grid.remove_seam(&[0, 1, 2]);
+
+    assert_eq!(vec![0, 4, 8], removed);
+    assert_eq!((2, 3), grid.dimensions());
+    assert_eq!(vec![&1, &2], grid.get_row(0));
+    assert_eq!(vec![&3, &5], grid.get_row(1));
+    assert_eq!(vec![&6, &7], grid.get_row(2));
+}
+
+#[test]
+fn grid_insert_seam_test() {
+    let mut grid = make_test_grid();
+
+    grid.insert_seam(&[0, 2, 3], vec![10, 11, 12]);
+
+    assert_eq!((4, 3), grid.dimensions());
+    assert_eq!(vec![&10, &0, &1, &2], grid.get_row(0));
+    assert_eq!(vec![&3, &4, &11, &5], grid.get_row(1));
+    assert_eq!(vec![&6, &7, &8, &12], grid.get_row(2));
+}
+
+#[test]
+fn grid_token_remove_and_insert_seam_test() {
+    let mut grid = make_test_grid();
+    let right = grid.make_token(2, 1);
+    let removed = grid.make_token(1, 1);
+
+    grid.remove_seam(&[1, 1, 1]);
+    assert_eq!(&5, grid.trade(right.clone()).unwrap());
+    assert!(grid.trade(removed).is_none());
+
+    grid.insert_seam(&[0, 0, 0], vec![9, 9, 9]);
+    assert_eq!(&5, grid.trade(right).unwrap());
+}
+
 #[test]
 fn grid_token_trade_test() {
     let mut grid = make_test_grid();
@@ -480,7 +608,7 @@ fn grid_token_shift_row_right_from_point_test() {
     assert!(iter.next().is_none());
 }
 
-// Rotated test grid visualized:
+// Test grid viewed horizontally, with x running down and y running across:
 //  -----------
 // | 0 | 3 | 6 |
 // | 1 | 4 | 7 |
@@ -488,22 +616,24 @@ fn grid_token_shift_row_right_from_point_test() {
 //  -----------
 
 #[test]
-fn grid_rotation_size_test() {
-    let mut grid = make_test_grid();
+fn grid_horizontal_size_test() {
+    let mut grid = Grid::new(vec![vec![0, 1, 2], vec![3, 4, 5]]);
 
-    grid.rotate();
-    assert_eq!(3, grid.width());
+    grid.set_direction(Direction::Horizontal);
+    assert_eq!(2, grid.width());
     assert_eq!(3, grid.height());
+    assert_eq!((3, 2), grid.dimensions());
 
-    grid.rotate();
+    grid.set_direction(Direction::Vertical);
     assert_eq!(3, grid.width());
-    assert_eq!(3, grid.height());
+    assert_eq!(2, grid.height());
+    assert_eq!((3, 2), grid.dimensions());
 }
 
 #[test]
-fn grid_rotation_get_test() {
+fn grid_horizontal_get_test() {
     let mut grid = make_test_grid();
-    grid.rotate();
+    grid.set_direction(Direction::Horizontal);
 
     // First row
     assert_eq!(&0, grid.get(0, 0));
@@ -522,9 +652,9 @@ fn grid_rotation_get_test() {
 }
 
 #[test]
-fn grid_rotation_get_mut_test() {
+fn grid_horizontal_get_mut_test() {
     let mut grid = make_test_grid();
-    grid.rotate();
+    grid.set_direction(Direction::Horizontal);
 
     // First row
     assert_eq!(&mut 0, grid.get_mut(0, 0));
@@ -543,78 +673,71 @@ fn grid_rotation_get_mut_test() {
 }
 
 #[test]
-fn grid_rotation_iter_test() {
+fn grid_horizontal_iter_test() {
     let mut grid = make_test_grid();
-    grid.rotate();
+    grid.set_direction(Direction::Horizontal);
 
+    // Iteration stays in image order.
     let collection: Vec<_> = grid.iter().collect();
-    assert_eq!(vec![&0, &3, &6, &1, &4, &7, &2, &5, &8], collection);
+    assert_eq!(vec![&0, &1, &2, &3, &4, &5, &6, &7, &8], collection);
 }
 
 #[test]
-fn grid_rotation_iter_mut_test() {
+fn grid_horizontal_iter_mut_test() {
     let mut grid = make_test_grid();
-    grid.rotate();
+    grid.set_direction(Direction::Horizontal);
 
     let collection: Vec<_> = grid.iter_mut().collect();
     assert_eq!(
-        vec![&mut 0, &mut 3, &mut 6, &mut 1, &mut 4, &mut 7, &mut 2, &mut 5, &mut 8,],
+        vec![&mut 0, &mut 1, &mut 2, &mut 3, &mut 4, &mut 5, &mut 6, &mut 7, &mut 8,],
         collection
     );
 }
 
 #[test]
-fn grid_rotation_coord_iter_test() {
+fn grid_horizontal_coord_iter_test() {
     let mut grid = make_test_grid();
-    grid.rotate();
+    grid.set_direction(Direction::Horizontal);
     let mut iter = grid.coord_iter();
 
-    // First column
+    // Coordinates and order stay in image space.
     assert_eq!((0, 0, &0), iter.next().unwrap());
-    assert_eq!((0, 1, &3), iter.next().unwrap());
-    assert_eq!((0, 2, &6), iter.next().unwrap());
-
-    // Second column
     assert_eq!((1, 0, &1), iter.next().unwrap());
-    assert_eq!((1, 1, &4), iter.next().unwrap());
-    assert_eq!((1, 2, &7), iter.next().unwrap());
-
-    // Third column
     assert_eq!((2, 0, &2), iter.next().unwrap());
+    assert_eq!((0, 1, &3), iter.next().unwrap());
+    assert_eq!((1, 1, &4), iter.next().unwrap());
     assert_eq!((2, 1, &5), iter.next().unwrap());
+    assert_eq!((0, 2, &6), iter.next().unwrap());
+    assert_eq!((1, 2, &7), iter.next().unwrap());
     assert_eq!((2, 2, &8), iter.next().unwrap());
 
     assert!(iter.next().is_none());
 }
 
 #[test]
-fn grid_rotation_coord_iter_mut_test() {
+fn grid_horizontal_coord_iter_mut_test() {
     let mut grid = make_test_grid();
-    grid.rotate();
+    grid.set_direction(Direction::Horizontal);
     let mut iter = grid.coord_iter_mut();
 
-    // First row
+    // Coordinates and order stay in image space.
     assert_eq!((0, 0, &mut 0), iter.next().unwrap());
-    assert_eq!((0, 1, &mut 3), iter.next().unwrap());
-    assert_eq!((0, 2, &mut 6), iter.next().unwrap());
-
-    // Second row
     assert_eq!((1, 0, &mut 1), iter.next().unwrap());
-    assert_eq!((1, 1, &mut 4), iter.next().unwrap());
-    assert_eq!((1, 2, &mut 7), iter.next().unwrap());
-
-    // Third row
     assert_eq!((2, 0, &mut 2), iter.next().unwrap());
+    assert_eq!((0, 1, &mut 3), iter.next().unwrap());
+    assert_eq!((1, 1, &mut 4), iter.next().unwrap());
     assert_eq!((2, 1, &mut 5), iter.next().unwrap());
+    assert_eq!((0, 2, &mut 6), iter.next().unwrap());
+    assert_eq!((1, 2, &mut 7), iter.next().unwrap());
     assert_eq!((2, 2, &mut 8), iter.next().unwrap());
 
     assert!(iter.next().is_none());
 }
 
 #[test]
-fn grid_rotation_shift_row_left_from_point_test() {
+fn grid_horizontal_shift_row_left_from_point_test() {
     let mut grid = make_test_grid();
-    grid.rotate();
+    grid.set_direction(Direction::Horizontal);
 
     // First row
     grid.shift_row_left_from_point(0, 0);
@@ -636,9 +759,9 @@ fn grid_rotation_shift_row_left_from_point_test() {
 }
 
 #[test]
-fn grid_rotation_shift_row_right_from_point_test() {
+fn grid_horizontal_shift_row_right_from_point_test() {
     let mut grid = make_test_grid();
-    grid.rotate();
+    grid.set_direction(Direction::Horizontal);
 
     // First row
     grid.shift_row_right_from_point(0, 0);
@@ -660,9 +783,9 @@ fn grid_rotation_shift_row_right_from_point_test() {
 }
 
 #[test]
-fn grid_rotation_remove_last_column_test() {
+fn grid_horizontal_remove_last_column_test() {
     let mut grid = make_test_grid();
-    grid.rotate();
+    grid.set_direction(Direction::Horizontal);
 
     grid.remove_last_column();
     assert_eq!(2, grid.width());
@@ -682,9 +805,9 @@ fn grid_rotation_remove_last_column_test() {
 }
 
 #[test]
-fn grid_rotation_add_last_column_test() {
+fn grid_horizontal_add_last_column_test() {
     let mut grid = make_test_grid();
-    grid.rotate();
+    grid.set_direction(Direction::Horizontal);
 
     grid.add_last_column();
     assert_eq!(4, grid.width());
@@ -710,9 +833,49 @@ fn grid_rotation_add_last_column_test() {
 }
 
 #[test]
-fn grid_rotation_before_token_trade_test() {
+fn grid_horizontal_remove_seam_test() {
+    let mut grid = make_test_grid();
+    grid.set_direction(Direction::Horizontal);
+
+    let removed = grid.remove_seam(&[2, 0, 1]);
+
+    assert_eq!(vec![6, 1, 5], removed);
+    assert_eq!((3, 2), grid.dimensions());
+    assert_eq!(vec![&0, &3], grid.get_row(0));
+    assert_eq!(vec![&4, &7], grid.get_row(1));
+    assert_eq!(vec![&2, &8], grid.get_row(2));
+}
+
+#[test]
+fn grid_horizontal_insert_seam_test() {
+    let mut grid = make_test_grid();
+    grid.set_direction(Direction::Horizontal);
+
+    grid.insert_seam(&[3, 0, 1], vec![10, 11, 12]);
+
+    assert_eq!((3, 4), grid.dimensions());
+    assert_eq!(vec![&0, &3, &6, &10], grid.get_row(0));
+    assert_eq!(vec![&11, &1, &4, &7], grid.get_row(1));
+    assert_eq!(vec![&2, &12, &5, &8], grid.get_row(2));
+}
+
+#[test]
+fn grid_horizontal_token_remove_and_insert_seam_test() {
+    let mut grid = make_test_grid();
+    grid.set_direction(Direction::Horizontal);
+    let below = grid.make_token(2, 1);
+
+    grid.remove_seam(&[0, 0, 0]);
+    assert_eq!(&7, grid.trade(below.clone()).unwrap());
+
+    grid.insert_seam(&[0, 1, 2], vec![9, 9, 9]);
+    assert_eq!(&7, grid.trade(below).unwrap());
+}
+
+#[test]
+fn grid_horizontal_before_token_trade_test() {
     let mut grid = make_test_grid();
-    grid.rotate();
+    grid.set_direction(Direction::Horizontal);
 
     let mut tokens = vec![];
     for y in 0..grid.height() {
@@ -735,7 +898,7 @@ fn grid_rotation_before_token_trade_test() {
 }
 
 #[test]
-fn grid_rotation_after_token_trade_test() {
+fn grid_horizontal_after_token_trade_test() {
     let mut grid = make_test_grid();
 
     let mut tokens = vec![];
@@ -745,7 +908,7 @@ fn grid_rotation_after_token_trade_test() {
         }
     }
 
-    grid.rotate();
+    grid.set_direction(Direction::Horizontal);
 
     let mut iter = tokens.into_iter();
     assert_eq!(&0, grid.trade(iter.next().unwrap()).unwrap());
@@ -761,7 +924,7 @@ fn grid_rotation_after_token_trade_test() {
 }
 
 #[test]
-fn grid_rotation_after_token_trade_mut_test() {
+fn grid_horizontal_after_token_trade_mut_test() {
     let mut grid = make_test_grid();
 
     let mut tokens = vec![];
@@ -771,7 +934,7 @@ fn grid_rotation_after_token_trade_mut_test() {
         }
     }
 
-    grid.rotate();
+    grid.set_direction(Direction::Horizontal);
 
     let mut iter = tokens.into_iter();
     assert_eq!(&mut 0, grid.trade_mut(iter.next().unwrap()).unwrap());
@@ -787,9 +950,9 @@ fn grid_rotation_after_token_trade_mut_test() {
 }
 
 #[test]
-fn grid_rotation_before_token_trade_mut_test() {
+fn grid_horizontal_before_token_trade_mut_test() {
     let mut grid = make_test_grid();
-    grid.rotate();
+    grid.set_direction(Direction::Horizontal);
 
     let mut tokens = vec![];
     for y in 0..grid.height() {
@@ -0,0 +1,147 @@
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, GrayImage, ImageBuffer, Luma};
+
+use rmr::carve::{Carver, CarverOptions, Interpolation, ResizeMode};
+use rmr::grid::Grid;
+use rmr::layer::{Average, Layer};
+use rmr::ResizeOptions;
+
+#[test]
+fn layer_matches_image_test() {
+    let input = load(CASTLE).resize(64, 64, FilterType::Triangle);
+    let (width, height) = input.dimensions();
+    let targets = [
+        (width - 8, height - 5),
+        (width + 7, height + 4),
+        (width - 6, height + 3),
+    ];
+
+    for &(target_width, target_height) in &targets {
+        let options = ResizeOptions::new(target_width as usize, target_height as usize).layer(
+            DynamicImage::ImageRgba8(input.to_rgba8()),
+            Interpolation::Average,
+        );
+
+        let output = rmr::resize(&input, options).unwrap();
+
+        assert_eq!(1, output.layers.len());
+        assert_eq!(output.image.to_rgba8(), output.layers[0].to_rgba8());
+    }
+}
+
+#[test]
+fn layer_keeps_pixel_type_test() {
+    let input = load(CASTLE).resize(32, 32, FilterType::Triangle);
+    let (width, height) = input.dimensions();
+    let depth = ImageBuffer::from_fn(width, height, |x, y| Luma([(x * y) as u16 * 64]));
+    let options = ResizeOptions::new(width as usize + 3, height as usize - 2)
+        .layer(DynamicImage::ImageLuma16(depth), Interpolation::Duplicate);
+
+    let output = rmr::resize(&input, options).unwrap();
+
+    match &output.layers[0] {
+        DynamicImage::ImageLuma16(layer) => {
+            assert_eq!(output.image.dimensions(), layer.dimensions())
+        }
+        _ => panic!("layer changed pixel type"),
+    }
+}
+
+#[test]
+fn layer_duplicate_labels_test() {
+    let input = load(CASTLE).resize(48, 32, FilterType::Triangle);
+    let (width, height) = input.dimensions();
+    let (width, height) = (width as usize, height as usize);
+    let labels: Vec<Vec<usize>> = (0..height)
+        .map(|y| (0..width).map(|x| y * width + x).collect())
+        .collect();
+
+    let mut carver = Carver::new(&input).unwrap();
+    let index = carver.add_layer(Layer::new(Grid::new(labels))).unwrap();
+    carver.resize(width - 5, height + 4).unwrap();
+
+    // Inserted labels repeat a neighbour, so every label still names a pixel
+    // that survived the shrink, and no label was blended into a new one.
+    let removed: Vec<usize> = carver
+        .clone()
+        .get_removed_points()
+        .into_iter()
+        .map(|(x, y)| y * width + x)
+        .collect();
+    let layer = carver.layer::<usize>(index).unwrap();
+    assert_eq!((width - 5, height + 4), layer.grid().dimensions());
+    for label in layer.grid().iter() {
+        assert!(*label < width * height);
+        assert!(!removed.contains(label));
+    }
+}
+
+#[test]
+fn layer_follows_crop_test() {
+    let input = load(CASTLE).resize(48, 32, FilterType::Triangle);
+    let (width, height) = input.dimensions();
+    let options = CarverOptions {
+        mode: ResizeMode::Crop,
+        ..CarverOptions::default()
+    };
+    let mut carver = Carver::with_options(&input, options).unwrap();
+    let index = carver
+        .add_image_layer(&input, Interpolation::Average)
+        .unwrap();
+
+    let image = carver
+        .resize(width as usize - 10, height as usize - 6)
+        .unwrap();
+
+    assert_eq!(
+        image.to_rgba8(),
+        carver.image_layer(index).unwrap().to_rgba8()
+    );
+}
+
+#[test]
+fn layer_wrong_type_test() {
+    let mut carver = Carver::new(&DynamicImage::new_rgb8(4, 3)).unwrap();
+    let index = carver
+        .add_layer(Layer::with_interpolation(
+            Grid::new(vec![vec![0.5f32; 4]; 3]),
+            Interpolation::Average,
+        ))
+        .unwrap();
+
+    assert!(carver.layer::<f32>(index).is_some());
+    assert!(carver.layer::<f64>(index).is_none());
+    assert!(carver.layer::<f32>(index + 1).is_none());
+    assert!(carver.image_layer(index).is_none());
+}
+
+#[test]
+fn layer_size_mismatch_test() {
+    let mut carver = Carver::new(&DynamicImage::new_rgb8(4, 3)).unwrap();
+    let layer = GrayImage::from_pixel(3, 4, Luma([0]));
+
+    assert!(carver
+        .add_image_layer(&DynamicImage::ImageLuma8(layer), Interpolation::Duplicate)
+        .is_err());
+    assert!(carver
+        .add_layer(Layer::new(Grid::new(vec![vec![0u8; 3]; 3])))
+        .is_err());
+}
+
+#[test]
+fn layer_average_test() {
+    assert_eq!(127u8, 255u8.average(&0));
+    assert_eq!(255u8, 255u8.average(&255));
+    assert_eq!(-2i32, (-3i32).average(&0));
+    assert_eq!(1.5f32, 1.0f32.average(&2.0));
+    assert_eq!(
+        Luma([u16::MAX]),
+        Luma([u16::MAX]).average(&Luma([u16::MAX]))
+    );
+}
+
+static CASTLE: &[u8] = include_bytes!("../images/castle.jpg");
+
+fn load(bytes: &[u8]) -> DynamicImage {
+    image::load_from_memory(bytes).expect("loaded test image")
+}
@@ -0,0 +1,121 @@
+use image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+
+use rmr::carve::{Direction, SeamKind, SeamPoint};
+use rmr::debug::{create_debug_image_with_lines, DebugOptions, LINE_COLOR};
+use rmr::lines::{detect_lines, LineOptions, LineSegment};
+use rmr::ResizeOptions;
+
+#[test]
+fn lines_detect_step_edge_test() {
+    // Dark above the line from (0, 10) to (79, 50), light below it.
+    let input = make_slope(80, 60, |x, y| 2 * y > x + 20);
+
+    let lines = detect_lines(&input, &LineOptions::default());
+
+    assert!(!lines.is_empty());
+    for line in &lines {
+        assert!(line.len() >= 32.0, "{:?} is too short", line);
+        for &(x, y) in &[line.start, line.end] {
+            let distance = (f64::from(x) - 2.0 * f64::from(y) + 20.0).abs() / 5f64.sqrt();
+            assert!(distance <= 2.0, "{:?} is off the edge", line);
+        }
+    }
+}
+
+#[test]
+fn lines_flat_image_test() {
+    let input = DynamicImage::ImageRgba8(RgbaImage::from_pixel(40, 30, Rgba([10, 20, 30, 255])));
+
+    assert!(detect_lines(&input, &LineOptions::default()).is_empty());
+}
+
+#[test]
+fn lines_segment_points_test() {
+    let segment = LineSegment {
+        start: (0, 0),
+        end: (5, 2),
+    };
+
+    assert_eq!(
+        vec![(0, 0), (1, 0), (2, 1), (3, 1), (4, 2), (5, 2)],
+        segment.points()
+    );
+    assert_eq!(29f64.sqrt(), segment.len());
+}
+
+#[test]
+fn lines_steer_seams_test() {
+    let input = make_striped();
+    let on_stripe = |point: &&SeamPoint| on_stripe(point.x as u32, point.y as u32);
+    let target = || ResizeOptions::new(80, 60);
+
+    let plain = rmr::resize(&input, target()).unwrap();
+    let preserved = rmr::resize(&input, target().lines(LineOptions::default())).unwrap();
+
+    assert!(plain.lines.is_empty());
+    assert!(!preserved.lines.is_empty());
+    // The stripe is the smoothest way down the image, so plain seams follow
+    // it and eat it away.
+    assert!(plain.seams.iter().filter(on_stripe).count() > 60);
+    assert_eq!(0, preserved.seams.iter().filter(on_stripe).count());
+}
+
+#[test]
+fn lines_debug_overlay_test() {
+    let input = DynamicImage::new_rgb8(8, 8);
+    let lines = [LineSegment {
+        start: (1, 1),
+        end: (6, 1),
+    }];
+    let points = [SeamPoint {
+        x: 3,
+        y: 1,
+        direction: Direction::Vertical,
+        kind: SeamKind::Removed,
+    }];
+    let options = DebugOptions {
+        legend: true,
+        ..DebugOptions::default()
+    };
+
+    let output = create_debug_image_with_lines(&input, &points, &lines, &options);
+
+    // Seams are drawn over lines.
+    assert_eq!(LINE_COLOR, output.get_pixel(1, 1));
+    assert_eq!(Rgba([255, 0, 0, 255]), output.get_pixel(3, 1));
+    assert_eq!(Rgba([0, 0, 0, 255]), output.get_pixel(1, 2));
+    // The legend gains a row for lines.
+    let without = create_debug_image_with_lines(&input, &points, &[], &options);
+    assert!(output.height() > without.height());
+}
+
+/// Whether `(x, y)` is on the stripe drawn by `make_striped`.
+fn on_stripe(x: u32, y: u32) -> bool {
+    let left = 30 + y / 3;
+    x >= left && x < left + 2
+}
+
+/// Diagonal bands of three colors with the same brightness, crossed by a dark
+/// slanted stripe. Only the stripe has edges a line can be found along, but
+/// it has less energy than the bands.
+fn make_striped() -> DynamicImage {
+    DynamicImage::ImageRgba8(RgbaImage::from_fn(90, 60, |x, y| {
+        if on_stripe(x, y) {
+            Rgba([40, 40, 40, 255])
+        } else {
+            let colors = [[200, 100, 0, 255], [0, 150, 94, 255], [100, 125, 48, 255]];
+            Rgba(colors[((x + y) % 3) as usize])
+        }
+    }))
+}
+
+/// Black where `dark` holds and white elsewhere.
+fn make_slope(width: u32, height: u32, dark: impl Fn(u32, u32) -> bool) -> DynamicImage {
+    DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |x, y| {
+        if dark(x, y) {
+            Rgba([0, 0, 0, 255])
+        } else {
+            Rgba([255, 255, 255, 255])
+        }
+    }))
+}
@@ -0,0 +1,124 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+use image::{DynamicImage, Rgba, RgbaImage};
+
+use rmr::carve::LOW_MEMORY_BYTES_PER_PIXEL;
+use rmr::energy::PixelEnergyPoint;
+use rmr::{ResizeOptions, ResizeOutput};
+
+/// Counts the bytes allocated by every thread of the test binary.
+struct Counting;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let pointer = System.alloc(layout);
+        if !pointer.is_null() {
+            let current = CURRENT.fetch_add(layout.size(), Ordering::SeqCst) + layout.size();
+            PEAK.fetch_max(current, Ordering::SeqCst);
+        }
+        pointer
+    }
+
+    unsafe fn dealloc(&self, pointer: *mut u8, layout: Layout) {
+        System.dealloc(pointer, layout);
+        CURRENT.fetch_sub(layout.size(), Ordering::SeqCst);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// Tests measuring allocations take turns, so they don't count each other's.
+static MEASURING: Mutex<()> = Mutex::new(());
+
+#[test]
+fn memory_low_memory_matches_copy_test() {
+    let _turn = take_turn();
+    let input = make_noise(60, 40);
+
+    for &(width, height) in &[(90, 40), (60, 55), (120, 40)] {
+        let options = || ResizeOptions::new(width, height);
+        let copied = rmr::resize(&input, options()).unwrap();
+        let in_place = rmr::resize(&input, options().low_memory(true)).unwrap();
+
+        assert_eq!(copied.image.to_rgba8(), in_place.image.to_rgba8());
+        assert_eq!(copied.seams, in_place.seams);
+        assert_eq!(copied.stats.seams, in_place.stats.seams);
+    }
+}
+
+#[test]
+fn memory_low_memory_ceiling_test() {
+    let input = make_noise(200, 150);
+
+    for &(width, height) in &[(150, 150), (300, 150), (400, 150), (200, 225), (260, 120)] {
+        let options = ResizeOptions::new(width, height)
+            .low_memory(true)
+            .seam_log(false);
+        let (peak, output) = measure(|| rmr::resize(&input, options).unwrap());
+
+        assert_eq!(
+            (width as u32, height as u32),
+            output.image.to_rgba8().dimensions()
+        );
+        assert!(output.seams.is_empty());
+        let largest = 200.max(width) * 150.max(height);
+        assert!(
+            peak <= largest * LOW_MEMORY_BYTES_PER_PIXEL,
+            "{}x{} took {} bytes per pixel",
+            width,
+            height,
+            peak / largest
+        );
+    }
+}
+
+#[test]
+fn memory_point_size_test() {
+    // A packed pixel and three u32s.
+    assert_eq!(16, std::mem::size_of::<PixelEnergyPoint>());
+}
+
+#[test]
+fn memory_low_memory_saves_copy_test() {
+    let input = make_noise(200, 150);
+    let options = || ResizeOptions::new(400, 150);
+
+    let (copied, _) = measure(|| rmr::resize(&input, options()).unwrap());
+    let (in_place, _) = measure(|| rmr::resize(&input, options().low_memory(true)).unwrap());
+
+    assert!(in_place < copied, "{} isn't less than {}", in_place, copied);
+}
+
+/// The most bytes allocated at once while running `resize`, beyond what was
+/// allocated before.
+fn measure(resize: impl FnOnce() -> ResizeOutput) -> (usize, ResizeOutput) {
+    let _turn = take_turn();
+    let before = CURRENT.load(Ordering::SeqCst);
+    PEAK.store(before, Ordering::SeqCst);
+    let output = resize();
+    (PEAK.load(Ordering::SeqCst) - before, output)
+}
+
+fn take_turn() -> MutexGuard<'static, ()> {
+    MEASURING
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Pseudo-random noise, so seams wander all over the image.
+fn make_noise(width: u32, height: u32) -> DynamicImage {
+    let mut state = 0x2545_f491u32;
+    DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |_, _| {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        let [r, g, b, _] = state.to_le_bytes();
+        Rgba([r, g, b, 255])
+    }))
+}
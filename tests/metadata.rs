@@ -0,0 +1,179 @@
+use image::{DynamicImage, GenericImageView, ImageFormat, Rgba};
+use img_parts::{Bytes, DynImage, ImageEXIF, ImageICC};
+
+use rmr::encode::{write_image, EncodeOptions};
+use rmr::metadata::{apply_orientation, Metadata, ORIENTATION_NORMAL};
+
+#[test]
+fn metadata_read_jpeg_test() {
+    let jpeg = make_jpeg_with_metadata(6);
+
+    let metadata = Metadata::read(&jpeg);
+
+    assert_eq!(6, metadata.orientation);
+    assert_eq!(Some(make_exif(6, 4, 3)), metadata.exif);
+    assert_eq!(Some(ICC_PROFILE.to_vec()), metadata.icc_profile);
+}
+
+#[test]
+fn metadata_read_without_metadata_test() {
+    let mut png = vec![];
+    write_image(
+        &DynamicImage::new_rgb8(2, 2),
+        &mut png,
+        ImageFormat::Png,
+        &EncodeOptions::default(),
+    )
+    .unwrap();
+
+    assert_eq!(Metadata::empty(), Metadata::read(&png));
+    assert_eq!(Metadata::empty(), Metadata::read(b"not an image"));
+}
+
+#[test]
+fn metadata_updated_for_test() {
+    let metadata = Metadata::read(&make_jpeg_with_metadata(6));
+
+    let updated = metadata.updated_for(300, 70000);
+
+    assert_eq!(ORIENTATION_NORMAL, updated.orientation);
+    // The height is stored as a SHORT, which can't hold 70000, so it's left
+    // alone rather than truncated.
+    assert_eq!(Some(make_exif(1, 300, 3)), updated.exif);
+    assert_eq!(metadata.icc_profile, updated.icc_profile);
+}
+
+#[test]
+fn metadata_embed_png_test() {
+    let metadata = Metadata::read(&make_jpeg_with_metadata(3)).updated_for(4, 3);
+    let mut png = vec![];
+    write_image(
+        &DynamicImage::new_rgb8(4, 3),
+        &mut png,
+        ImageFormat::Png,
+        &EncodeOptions::default(),
+    )
+    .unwrap();
+
+    let png = metadata.embed(png).unwrap();
+
+    let reread = Metadata::read(&png);
+    assert_eq!(metadata, reread);
+    assert_eq!((4, 3), image::load_from_memory(&png).unwrap().dimensions());
+}
+
+#[test]
+fn metadata_embed_tiff_test() {
+    let metadata = Metadata::read(&make_jpeg_with_metadata(6)).updated_for(4, 3);
+    let mut tiff = vec![];
+    write_image(
+        &DynamicImage::new_rgb8(4, 3),
+        &mut tiff,
+        ImageFormat::Tiff,
+        &EncodeOptions::default(),
+    )
+    .unwrap();
+    assert_eq!(Metadata::empty(), Metadata::read(&tiff));
+
+    let tiff = metadata.embed(tiff).unwrap();
+
+    let reread = Metadata::read(&tiff);
+    assert_eq!(metadata, reread);
+    assert_eq!(Some(make_exif(1, 4, 3)), reread.exif);
+    assert_eq!(Some(ICC_PROFILE.to_vec()), reread.icc_profile);
+    assert_eq!((4, 3), image::load_from_memory(&tiff).unwrap().dimensions());
+}
+
+#[test]
+fn metadata_embed_unsupported_format_test() {
+    let metadata = Metadata::read(&make_jpeg_with_metadata(1));
+    let mut bmp = vec![];
+    write_image(
+        &DynamicImage::new_rgb8(2, 2),
+        &mut bmp,
+        ImageFormat::Bmp,
+        &EncodeOptions::default(),
+    )
+    .unwrap();
+
+    assert_eq!(bmp.clone(), metadata.embed(bmp).unwrap());
+}
+
+#[test]
+fn metadata_apply_orientation_test() {
+    // Marks the top left corner of a 3x2 image.
+    let mut image = DynamicImage::new_rgba8(3, 2);
+    let red = Rgba([255, 0, 0, 255]);
+    image.as_mut_rgba8().unwrap().put_pixel(0, 0, red);
+
+    let expected = [
+        (1, (3, 2), (0, 0)),
+        (2, (3, 2), (2, 0)),
+        (3, (3, 2), (2, 1)),
+        (4, (3, 2), (0, 1)),
+        (5, (2, 3), (0, 0)),
+        (6, (2, 3), (1, 0)),
+        (7, (2, 3), (1, 2)),
+        (8, (2, 3), (0, 2)),
+    ];
+
+    for &(orientation, dimensions, (x, y)) in &expected {
+        let oriented = apply_orientation(image.clone(), orientation);
+        assert_eq!(
+            dimensions,
+            oriented.dimensions(),
+            "orientation {}",
+            orientation
+        );
+        assert_eq!(red, oriented.get_pixel(x, y), "orientation {}", orientation);
+    }
+}
+
+static ICC_PROFILE: &[u8] = b"not a real icc profile";
+
+fn make_jpeg_with_metadata(orientation: u16) -> Vec<u8> {
+    let mut jpeg = vec![];
+    write_image(
+        &DynamicImage::new_rgb8(4, 3),
+        &mut jpeg,
+        ImageFormat::Jpeg,
+        &EncodeOptions::default(),
+    )
+    .unwrap();
+
+    let mut image = DynImage::from_bytes(Bytes::from(jpeg)).unwrap().unwrap();
+    image.set_exif(Some(Bytes::from(make_exif(orientation, 4, 3))));
+    image.set_icc_profile(Some(Bytes::from_static(ICC_PROFILE)));
+
+    let mut output = vec![];
+    image.encoder().write_to(&mut output).unwrap();
+    output
+}
+
+// A little endian EXIF block with an orientation in the first IFD and the
+// pixel dimensions in the EXIF IFD.
+fn make_exif(orientation: u16, width: u32, height: u16) -> Vec<u8> {
+    let mut exif = b"II*\0".to_vec();
+    exif.extend_from_slice(&8u32.to_le_bytes());
+
+    exif.extend_from_slice(&2u16.to_le_bytes());
+    push_entry(&mut exif, 0x0112, 3, &orientation.to_le_bytes());
+    push_entry(&mut exif, 0x8769, 4, &38u32.to_le_bytes());
+    exif.extend_from_slice(&0u32.to_le_bytes());
+
+    exif.extend_from_slice(&2u16.to_le_bytes());
+    push_entry(&mut exif, 0xA002, 4, &width.to_le_bytes());
+    push_entry(&mut exif, 0xA003, 3, &height.to_le_bytes());
+    exif.extend_from_slice(&0u32.to_le_bytes());
+
+    exif
+}
+
+fn push_entry(exif: &mut Vec<u8>, tag: u16, kind: u16, value: &[u8]) {
+    exif.extend_from_slice(&tag.to_le_bytes());
+    exif.extend_from_slice(&kind.to_le_bytes());
+    exif.extend_from_slice(&1u32.to_le_bytes());
+    let mut field = [0; 4];
+    field[..value.len()].copy_from_slice(value);
+    exif.extend_from_slice(&field);
+}
@@ -0,0 +1,111 @@
+use image::{DynamicImage, GenericImageView};
+
+use rmr::carve::{CarveError, Carver, Direction};
+use rmr::progress::{Control, Phase, Progress};
+
+#[test]
+fn progress_reports_every_seam_test() {
+    let input = load(INPUT);
+    let (width, height) = input.dimensions();
+
+    let mut reports = vec![];
+    let mut observer = |progress: &Progress| {
+        reports.push(*progress);
+        Control::Continue
+    };
+
+    let mut carver = Carver::new(&input).unwrap();
+    carver
+        .resize_with_observer(width as usize - 3, height as usize + 2, &mut observer)
+        .unwrap();
+
+    let carve_reports: Vec<_> = reports
+        .iter()
+        .filter(|p| p.phase == Phase::Carve && p.direction == Direction::Vertical)
+        .map(|p| (p.completed, p.total))
+        .collect();
+    assert_eq!(vec![(0, 3), (1, 3), (2, 3), (3, 3)], carve_reports);
+
+    // Inserted seams are reported one at a time too.
+    let grow_reports: Vec<_> = reports
+        .iter()
+        .filter(|p| p.phase == Phase::Carve && p.direction == Direction::Horizontal)
+        .map(|p| (p.completed, p.total))
+        .collect();
+    assert_eq!(vec![(0, 2), (1, 2), (2, 2)], grow_reports);
+
+    let precompute_reports: Vec<_> = reports
+        .iter()
+        .filter(|p| p.phase == Phase::GrowPrecompute)
+        .collect();
+    assert_eq!(3, precompute_reports.len());
+    assert!(precompute_reports
+        .iter()
+        .all(|p| p.direction == Direction::Horizontal && p.total == 2));
+
+    let last = reports.last().unwrap();
+    assert_eq!(Phase::Carve, last.phase);
+    assert_eq!(Direction::Horizontal, last.direction);
+    assert_eq!(last.total, last.completed);
+}
+
+#[test]
+fn progress_cancel_test() {
+    let input = load(INPUT);
+    let (width, height) = input.dimensions();
+
+    let mut calls = 0;
+    let mut observer = |progress: &Progress| {
+        calls += 1;
+        if progress.phase == Phase::Carve && progress.completed == 2 {
+            Control::Cancel
+        } else {
+            Control::Continue
+        }
+    };
+
+    let mut carver = Carver::new(&input).unwrap();
+    let result = carver.resize_with_observer(width as usize - 5, height as usize, &mut observer);
+
+    assert_eq!(Some(CarveError::Cancelled), result.err());
+    assert_eq!(5, calls);
+}
+
+#[test]
+fn progress_cancel_leaves_carver_usable_test() {
+    let input = load(INPUT);
+    let (input_width, input_height) = input.dimensions();
+    let (width, height) = (input_width as usize - 3, input_height as usize - 4);
+
+    // Cancels two seams into the width, then two seams into the height.
+    for &(cancel_height, partial_width, partial_height) in &[
+        (false, input_width - 2, input_height),
+        (true, width as u32, input_height + 2),
+    ] {
+        let mut observer = |progress: &Progress| {
+            let cancelling = progress.phase == Phase::Carve
+                && progress.completed == 2
+                && (progress.direction == Direction::Horizontal) == cancel_height;
+            if cancelling {
+                Control::Cancel
+            } else {
+                Control::Continue
+            }
+        };
+        let mut carver = Carver::new(&input).unwrap();
+        let result = carver.resize_with_observer(width, height + 8, &mut observer);
+        assert_eq!(Some(CarveError::Cancelled), result.err());
+
+        // The carver is left partway through, the right way up, and can
+        // carry on.
+        assert_eq!((partial_width, partial_height), carver.image().dimensions());
+        let output = carver.resize(width, height).unwrap();
+        assert_eq!((width as u32, height as u32), output.dimensions());
+    }
+}
+
+static INPUT: &[u8; 7256] = include_bytes!("images/input.png");
+
+fn load(bytes: &[u8]) -> DynamicImage {
+    image::load_from_memory(bytes).expect("loaded test image")
+}
@@ -0,0 +1,181 @@
+use image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+use proptest::prelude::*;
+
+use rmr::carve::{Carver, Direction, SeamKind};
+use rmr::grid::Grid;
+use rmr::layer::Layer;
+use rmr::progress::{Control, Progress};
+
+/// A random image between 2 and `max` pixels along each side.
+fn image(max: u32) -> impl Strategy<Value = RgbaImage> {
+    (2..=max, 2..=max)
+        .prop_flat_map(|(width, height)| {
+            proptest::collection::vec(any::<[u8; 3]>(), (width * height) as usize)
+                .prop_map(move |pixels| (width, height, pixels))
+        })
+        .prop_map(|(width, height, pixels)| {
+            RgbaImage::from_fn(width, height, |x, y| {
+                let [r, g, b] = pixels[(y * width + x) as usize];
+                Rgba([r, g, b, 255])
+            })
+        })
+}
+
+/// A random image with a target size anywhere from a single pixel up to
+/// nearly twice its size along each side.
+fn image_and_target(max: u32) -> impl Strategy<Value = (RgbaImage, usize, usize)> {
+    image(max).prop_flat_map(|image| {
+        let (width, height) = image.dimensions();
+        let (width, height) = (width as usize, height as usize);
+        (Just(image), 1..2 * width, 1..2 * height)
+    })
+}
+
+/// A random image with a smaller target width.
+fn image_and_smaller_width(max: u32) -> impl Strategy<Value = (RgbaImage, usize)> {
+    image(max).prop_flat_map(|image| {
+        let width = image.width() as usize;
+        (Just(image), 1..width)
+    })
+}
+
+/// A random image with a larger target width.
+fn image_and_larger_width(max: u32) -> impl Strategy<Value = (RgbaImage, usize)> {
+    image(max).prop_flat_map(|image| {
+        let width = image.width() as usize;
+        (Just(image), width + 1..2 * width)
+    })
+}
+
+/// A layer labelling every pixel with its index in the input.
+fn labels(image: &DynamicImage) -> Layer<usize> {
+    let (width, height) = image.dimensions();
+    let (width, height) = (width as usize, height as usize);
+    let rows = (0..height)
+        .map(|y| (0..width).map(|x| y * width + x).collect())
+        .collect();
+    Layer::new(Grid::new(rows))
+}
+
+fn carve(carver: &mut Carver, direction: Direction, target: usize) {
+    carver
+        .carve(direction, target, &mut |_: &Progress| Control::Continue)
+        .unwrap();
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(48))]
+
+    #[test]
+    fn property_exact_dimensions_test((input, width, height) in image_and_target(24)) {
+        let input = DynamicImage::ImageRgba8(input);
+        let mut carver = Carver::new(&input).unwrap();
+
+        let output = carver.resize(width, height).unwrap();
+
+        prop_assert_eq!((width as u32, height as u32), output.dimensions());
+    }
+
+    #[test]
+    fn property_seam_point_count_test((input, width, height) in image_and_target(24)) {
+        let input = DynamicImage::ImageRgba8(input);
+        let (input_width, input_height) = input.dimensions();
+        let (input_width, input_height) = (input_width as usize, input_height as usize);
+        let mut carver = Carver::new(&input).unwrap();
+
+        carver.resize(width, height).unwrap();
+
+        // Vertical seams run the full input height, then horizontal seams
+        // run the full width they leave behind.
+        let counts = carver.seam_counts();
+        let vertical = counts.vertical_removed + counts.vertical_inserted;
+        let horizontal = counts.horizontal_removed + counts.horizontal_inserted;
+        prop_assert_eq!(input_width.max(width) - input_width.min(width), vertical);
+        prop_assert_eq!(input_height.max(height) - input_height.min(height), horizontal);
+
+        let points = carver.get_seam_points();
+        prop_assert_eq!(vertical * input_height + horizontal * width, points.len());
+        for point in &points {
+            prop_assert!(point.x < input_width && point.y < input_height);
+        }
+    }
+
+    #[test]
+    fn property_shrink_keeps_pixels_once_test((input, width) in image_and_smaller_width(24)) {
+        let input = DynamicImage::ImageRgba8(input);
+        let height = input.height() as usize;
+        let mut carver = Carver::new(&input).unwrap();
+        let index = carver.add_layer(labels(&input)).unwrap();
+
+        carver.resize(width, height).unwrap();
+
+        let mut seen = vec![false; input.width() as usize * height];
+        for &label in carver.layer::<usize>(index).unwrap().grid().iter() {
+            prop_assert!(!seen[label], "pixel {} appears twice", label);
+            seen[label] = true;
+        }
+        let removed = carver.get_removed_points();
+        prop_assert_eq!((input.width() as usize - width) * height, removed.len());
+        for (x, y) in removed {
+            let label = y * input.width() as usize + x;
+            prop_assert!(!seen[label], "removed pixel {} was kept", label);
+            seen[label] = true;
+        }
+        prop_assert!(seen.into_iter().all(|seen| seen));
+    }
+
+    #[test]
+    fn property_seams_connected_test(
+        (input, width) in image_and_smaller_width(16),
+        horizontal in any::<bool>(),
+    ) {
+        let input = DynamicImage::ImageRgba8(input);
+        let (input, direction) = if horizontal {
+            (input.rotate90(), Direction::Horizontal)
+        } else {
+            (input, Direction::Vertical)
+        };
+        let mut carver = Carver::new(&input).unwrap();
+        let index = carver.add_layer(labels(&input)).unwrap();
+
+        // Carving one seam at a time shows where each seam ran in the image
+        // it was carved from.
+        let mut before = carver.layer::<usize>(index).unwrap().grid().clone();
+        before.set_direction(direction);
+        for target in (width..before.width()).rev() {
+            carve(&mut carver, direction, target);
+            let mut after = carver.layer::<usize>(index).unwrap().grid().clone();
+            after.set_direction(direction);
+
+            let seam: Vec<usize> = (0..before.height())
+                .map(|y| {
+                    (0..after.width())
+                        .find(|&x| before.get(x, y) != after.get(x, y))
+                        .unwrap_or(after.width())
+                })
+                .collect();
+            for pair in seam.windows(2) {
+                prop_assert!(
+                    pair[0].abs_diff(pair[1]) <= 1,
+                    "seam {:?} isn't 8-connected",
+                    seam
+                );
+            }
+            before = after;
+        }
+    }
+
+    #[test]
+    fn property_inserted_seams_test((input, width) in image_and_larger_width(24)) {
+        let input = DynamicImage::ImageRgba8(input);
+        let height = input.height() as usize;
+        let mut carver = Carver::new(&input).unwrap();
+
+        carver.resize(width, height).unwrap();
+
+        let points = carver.get_seam_points();
+        let inserted = width - input.width() as usize;
+        prop_assert_eq!(inserted * height, points.len());
+        prop_assert!(points.iter().all(|point| point.kind == SeamKind::Inserted));
+    }
+}
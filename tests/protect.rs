@@ -0,0 +1,208 @@
+use image::{DynamicImage, GenericImageView, GrayImage, Luma, Rgba, RgbaImage};
+
+use rmr::cascade::{Cascade, DetectOptions, Rect};
+use rmr::protect::{face_mask, merge_masks, skin_mask};
+use rmr::ResizeOptions;
+
+const SKIN: Rgba<u8> = Rgba([224, 172, 140, 255]);
+const BACKGROUND: Rgba<u8> = Rgba([60, 110, 170, 255]);
+
+#[test]
+fn protect_skin_mask_test() {
+    let input = make_face(96, 96);
+
+    let mask = skin_mask(&input);
+
+    assert_eq!(input.dimensions(), mask.dimensions());
+    assert_eq!(Luma([255]), *mask.get_pixel(48, 50));
+    assert_eq!(Luma([0]), *mask.get_pixel(4, 4));
+    // The dark eyes aren't skin.
+    assert_eq!(Luma([0]), *mask.get_pixel(36, 38));
+}
+
+#[test]
+fn protect_skin_ignores_specks_test() {
+    let mut input = RgbaImage::from_pixel(9, 9, BACKGROUND);
+    input.put_pixel(4, 4, SKIN);
+
+    let mask = skin_mask(&DynamicImage::ImageRgba8(input));
+
+    assert!(mask.pixels().all(|&pixel| pixel == Luma([0])));
+}
+
+#[test]
+fn protect_bundled_cascade_finds_face_test() {
+    let input = make_face(96, 96);
+
+    let faces = Cascade::frontal_face().detect(&input, &DetectOptions::default());
+
+    assert!(!faces.is_empty());
+    for face in &faces {
+        // Every detection sits over the face.
+        let (center_x, center_y) = (face.x + face.width / 2, face.y + face.height / 2);
+        assert!((30..66).contains(&center_x), "{:?}", face);
+        assert!((30..66).contains(&center_y), "{:?}", face);
+    }
+}
+
+#[test]
+fn protect_bundled_cascade_ignores_plain_images_test() {
+    let cascade = Cascade::frontal_face();
+    let flat = DynamicImage::ImageRgba8(RgbaImage::from_pixel(64, 64, BACKGROUND));
+    let ramp = DynamicImage::ImageRgba8(RgbaImage::from_fn(64, 64, |x, _| {
+        Rgba([x as u8 * 4, x as u8 * 4, x as u8 * 4, 255])
+    }));
+
+    assert!(cascade.detect(&flat, &DetectOptions::default()).is_empty());
+    assert!(cascade.detect(&ramp, &DetectOptions::default()).is_empty());
+}
+
+#[test]
+fn protect_cascade_parse_errors_test() {
+    let tilted = CASCADE.replace("<rects>", "<tilted>1</tilted><rects>");
+    let deep = CASCADE.replace("0 -1 0 0.5", "1 -1 0 0.5 0 -2 0 0.5");
+    let outside = CASCADE.replace("0 0 6 3 -1.", "2 0 6 3 -1.");
+
+    assert!(Cascade::parse(CASCADE).is_ok());
+    assert!(Cascade::parse(&tilted).is_err());
+    assert!(Cascade::parse(&deep).is_err());
+    assert!(Cascade::parse(&outside).is_err());
+    assert!(Cascade::parse("<opencv_storage>").is_err());
+    assert!(Cascade::parse("<opencv_storage></opencv_storage>").is_err());
+}
+
+#[test]
+fn protect_parsed_cascade_detects_test() {
+    // A dark left half and a light right half.
+    let cascade = Cascade::parse(CASCADE).unwrap();
+    let input = DynamicImage::ImageRgba8(RgbaImage::from_fn(32, 16, |x, _| {
+        if x < 16 {
+            Rgba([0, 0, 0, 255])
+        } else {
+            Rgba([255, 255, 255, 255])
+        }
+    }));
+    let options = DetectOptions {
+        min_neighbors: 0,
+        ..DetectOptions::default()
+    };
+
+    let faces = cascade.detect(&input, &options);
+
+    assert!(!faces.is_empty());
+    for face in faces {
+        assert!(face.x < 16 && face.x + face.width > 16, "{:?}", face);
+    }
+}
+
+#[test]
+fn protect_face_mask_test() {
+    let faces = [
+        Rect {
+            x: 1,
+            y: 1,
+            width: 2,
+            height: 2,
+        },
+        Rect {
+            x: 4,
+            y: 3,
+            width: 5,
+            height: 5,
+        },
+    ];
+
+    let mask = face_mask(6, 5, &faces);
+
+    let protected: Vec<(u32, u32)> = mask
+        .enumerate_pixels()
+        .filter(|(_, _, pixel)| pixel[0] == 255)
+        .map(|(x, y, _)| (x, y))
+        .collect();
+    assert_eq!(
+        vec![
+            (1, 1),
+            (2, 1),
+            (1, 2),
+            (2, 2),
+            (4, 3),
+            (5, 3),
+            (4, 4),
+            (5, 4)
+        ],
+        protected
+    );
+}
+
+#[test]
+fn protect_merge_masks_test() {
+    let a = GrayImage::from_fn(3, 1, |x, _| Luma([x as u8 * 100]));
+    let b = GrayImage::from_fn(3, 1, |x, _| Luma([200 - x as u8 * 100]));
+
+    let merged = merge_masks(&a, &b).unwrap();
+
+    assert_eq!(vec![200, 100, 200], merged.into_raw());
+    assert!(merge_masks(&a, &GrayImage::new(2, 1)).is_err());
+}
+
+#[test]
+fn protect_skin_survives_resize_test() {
+    let input = make_face(96, 96);
+    let mask = skin_mask(&input);
+    let skin = mask.pixels().filter(|pixel| pixel[0] == 255).count();
+
+    let options = ResizeOptions::new(70, 96).protect_mask(mask);
+    let output = rmr::resize(&input, options).unwrap();
+
+    let kept = output
+        .image
+        .pixels()
+        .filter(|&(_, _, pixel)| pixel == SKIN)
+        .count();
+    assert!(kept * 100 >= skin * 95, "kept {} of {}", kept, skin);
+}
+
+/// A cascade with a 6 by 3 window and one stage that fires when the left
+/// half is darker than the right.
+const CASCADE: &str = r#"<?xml version="1.0"?>
+<opencv_storage>
+<cascade type_id="opencv-cascade-classifier">
+  <stageType>BOOST</stageType>
+  <featureType>HAAR</featureType>
+  <height>3</height>
+  <width>6</width>
+  <stages>
+    <_>
+      <stageThreshold>0.</stageThreshold>
+      <weakClassifiers>
+        <_>
+          <internalNodes>0 -1 0 0.5</internalNodes>
+          <leafValues>-1. 1.</leafValues></_></weakClassifiers></_></stages>
+  <features>
+    <_>
+      <rects>
+        <_>0 0 6 3 -1.</_>
+        <_>3 0 3 3 2.</_></rects></_></features></cascade>
+</opencv_storage>
+"#;
+
+/// A cartoon face: a skin colored oval with dark eyes and mouth on a blue
+/// background.
+fn make_face(width: u32, height: u32) -> DynamicImage {
+    let inside = |x: u32, y: u32, cx: f64, cy: f64, rx: f64, ry: f64| {
+        let dx = (f64::from(x) - cx) / rx;
+        let dy = (f64::from(y) - cy) / ry;
+        dx * dx + dy * dy <= 1.0
+    };
+    DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |x, y| {
+        let eye = inside(x, y, 36.0, 38.0, 6.0, 4.0) || inside(x, y, 60.0, 38.0, 6.0, 4.0);
+        let mouth = inside(x, y, 48.0, 66.0, 11.0, 3.0);
+        if eye || mouth {
+            Rgba([50, 30, 30, 255])
+        } else if inside(x, y, 48.0, 48.0, 28.0, 36.0) {
+            SKIN
+        } else {
+            BACKGROUND
+        }
+    }))
+}
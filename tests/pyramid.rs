@@ -0,0 +1,41 @@
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, GrayImage, Luma};
+
+use rmr::carve::Strategy;
+use rmr::ResizeOptions;
+
+mod common;
+
+#[test]
+fn pyramid_quality_test() {
+    common::assert_strategy_quality(Strategy::pyramid(4));
+}
+
+#[test]
+fn pyramid_protect_mask_test() {
+    let input = load(CASTLE).resize(128, 128, FilterType::Triangle);
+    let (width, height) = input.dimensions();
+    let mask = GrayImage::from_fn(width, height, |x, _| {
+        Luma([if x >= width / 4 && x < width / 2 {
+            255
+        } else {
+            0
+        }])
+    });
+    let options = ResizeOptions::new(width as usize - 10, height as usize)
+        .strategy(Strategy::pyramid(4))
+        .protect_mask(mask.clone());
+
+    let output = rmr::resize(&input, options).unwrap();
+
+    assert_eq!(10 * height as usize, output.seams.len());
+    for point in output.seams {
+        assert_eq!(0, mask.get_pixel(point.x as u32, point.y as u32)[0]);
+    }
+}
+
+static CASTLE: &[u8] = include_bytes!("../images/castle.jpg");
+
+fn load(bytes: &[u8]) -> DynamicImage {
+    image::load_from_memory(bytes).expect("loaded test image")
+}
@@ -0,0 +1,270 @@
+use image::{DynamicImage, GenericImageView, GrayImage, Luma, Rgba, RgbaImage};
+
+use rmr::carve::{
+    CarveError, Carver, CarverOptions, Direction, ResizeMode, SeamConstraint, SeamCounts, SeamKind,
+    SeamOrder, Strategy,
+};
+use rmr::energy::EnergyFunction;
+use rmr::progress::{Control, Progress};
+use rmr::ResizeOptions;
+
+#[test]
+fn resize_matches_carver_test() {
+    let input = load(INPUT);
+    let (width, height) = input.dimensions();
+    let (width, height) = (width as usize - 5, height as usize + 5);
+
+    let expected = Carver::new(&input).unwrap().resize(width, height).unwrap();
+    let output = rmr::resize(&input, ResizeOptions::new(width, height)).unwrap();
+
+    assert_images_eq(&expected, &output.image);
+}
+
+#[test]
+fn resize_stats_seam_counts_test() {
+    let input = load(INPUT);
+    let (width, height) = input.dimensions();
+    let options = ResizeOptions::new(width as usize + 2, height as usize - 3)
+        .seam_order(SeamOrder::HeightFirst);
+
+    let output = rmr::resize(&input, options).unwrap();
+
+    assert_eq!(
+        (width + 2, height - 3),
+        (output.image.width(), output.image.height())
+    );
+    assert_eq!(
+        SeamCounts {
+            vertical_removed: 0,
+            vertical_inserted: 2,
+            horizontal_removed: 3,
+            horizontal_inserted: 0,
+        },
+        output.stats.seams
+    );
+
+    // Height first means the vertical seams are as tall as the target height.
+    let vertical_points = output
+        .seams
+        .iter()
+        .filter(|p| p.direction == Direction::Vertical && p.kind == SeamKind::Inserted)
+        .count();
+    assert_eq!(2 * (height as usize - 3), vertical_points);
+}
+
+#[test]
+fn resize_threads_match_single_thread_test() {
+    let input = load(INPUT);
+    let (width, height) = input.dimensions();
+    let (width, height) = (width as usize - 4, height as usize - 4);
+
+    let single = rmr::resize(&input, ResizeOptions::new(width, height)).unwrap();
+    let threaded = rmr::resize(&input, ResizeOptions::new(width, height).threads(3)).unwrap();
+
+    assert_images_eq(&single.image, &threaded.image);
+}
+
+#[test]
+fn resize_absolute_energy_test() {
+    let input = load(INPUT);
+    let (width, height) = input.dimensions();
+    let options = ResizeOptions::new(width as usize - 5, height as usize)
+        .energy_function(EnergyFunction::AbsoluteGradient);
+
+    let output = rmr::resize(&input, options).unwrap();
+
+    assert_eq!((width - 5, height), output.image.dimensions());
+}
+
+#[test]
+fn resize_protect_mask_test() {
+    let input = load(INPUT);
+    let (width, height) = input.dimensions();
+
+    // Protect everything except the last two columns.
+    let mask = GrayImage::from_fn(width, height, |x, _| {
+        if x < width - 2 {
+            Luma([255])
+        } else {
+            Luma([0])
+        }
+    });
+    let options = ResizeOptions::new(width as usize - 2, height as usize).protect_mask(mask);
+
+    let output = rmr::resize(&input, options).unwrap();
+
+    assert!(output.seams.iter().all(|p| p.x >= width as usize - 2));
+    for (x, y, pixel) in output.image.pixels() {
+        assert_eq!(input.get_pixel(x, y), pixel);
+    }
+}
+
+#[test]
+fn resize_protect_mask_size_mismatch_test() {
+    let input = load(INPUT);
+    let (width, height) = input.dimensions();
+    let options = ResizeOptions::new(width as usize - 1, height as usize)
+        .protect_mask(GrayImage::new(width - 1, height));
+
+    assert!(rmr::resize(&input, options).is_err());
+}
+
+#[test]
+fn resize_zero_target_test() {
+    let input = load(INPUT);
+    let (_, height) = input.dimensions();
+
+    assert!(rmr::resize(&input, ResizeOptions::new(0, height as usize)).is_err());
+
+    let mut carver = Carver::new(&input).unwrap();
+    let mut observer = |_: &Progress| Control::Continue;
+    assert_eq!(
+        Err(CarveError::EmptyTarget),
+        carver.resize_with_observer(0, height as usize, &mut observer)
+    );
+}
+
+#[test]
+fn resize_empty_image_test() {
+    for &(width, height) in &[(0, 0), (0, 3), (3, 0)] {
+        let input = DynamicImage::new_rgba8(width, height);
+
+        assert!(rmr::resize(&input, ResizeOptions::new(2, 2)).is_err());
+        assert!(Carver::with_options(&input, CarverOptions::default()).is_err());
+    }
+}
+
+#[test]
+fn resize_tiny_image_test() {
+    let modes = [ResizeMode::Carve, ResizeMode::Crop, ResizeMode::Auto];
+    let strategies = [
+        Strategy::FullResolution,
+        Strategy::pyramid(2),
+        Strategy::Disjoint { seams: 3 },
+    ];
+    for &(width, height) in &[(1, 1), (1, 4), (4, 1), (2, 3)] {
+        let input = DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |x, y| {
+            Rgba([(x * 90) as u8, (y * 60) as u8, 30, 255])
+        }));
+        for &(target_width, target_height) in &[(1, 1), (1, 9), (9, 1), (9, 9)] {
+            for &mode in &modes {
+                for &strategy in &strategies {
+                    let options = ResizeOptions::new(target_width, target_height)
+                        .mode(mode)
+                        .strategy(strategy);
+
+                    let output = rmr::resize(&input, options).unwrap();
+
+                    assert_eq!(
+                        (target_width as u32, target_height as u32),
+                        output.image.dimensions()
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn resize_grow_past_double_test() {
+    let input = load(INPUT);
+    let (width, height) = input.dimensions();
+    let (width, height) = (width as usize * 3, height as usize);
+
+    let output = rmr::resize(&input, ResizeOptions::new(width, height)).unwrap();
+
+    assert_eq!((width as u32, height as u32), output.image.dimensions());
+    assert_eq!(
+        width - input.width() as usize,
+        output.stats.seams.vertical_inserted
+    );
+    assert_eq!(1, output.stats.passes.len());
+}
+
+#[test]
+fn resize_observer_cancel_test() {
+    let input = load(INPUT);
+    let (width, height) = input.dimensions();
+    let options = ResizeOptions::new(width as usize - 5, height as usize)
+        .observer(|_: &Progress| Control::Cancel);
+
+    assert!(rmr::resize(&input, options).is_err());
+}
+
+#[test]
+fn resize_straight_seams_test() {
+    let input = load(INPUT);
+    let (width, height) = input.dimensions();
+    let options = ResizeOptions::new(width as usize - 3, height as usize)
+        .seam_constraint(SeamConstraint::straight());
+
+    let output = rmr::resize(&input, options).unwrap();
+
+    for seam in output.seams.chunks(height as usize) {
+        assert!(seam.iter().all(|point| point.x == seam[0].x));
+    }
+}
+
+#[test]
+fn resize_seam_window_test() {
+    let input = load(INPUT);
+    let (width, height) = input.dimensions();
+
+    for &window in &[1, 3] {
+        let constraint = SeamConstraint {
+            window,
+            ..SeamConstraint::default()
+        };
+        let options =
+            ResizeOptions::new(width as usize - 1, height as usize).seam_constraint(constraint);
+
+        let output = rmr::resize(&input, options).unwrap();
+
+        let max_step = output
+            .seams
+            .windows(2)
+            .map(|pair| pair[0].x.abs_diff(pair[1].x))
+            .max()
+            .unwrap();
+        assert!(max_step <= window, "window {}", window);
+        // The wider window is actually used.
+        assert!(max_step > 1 || window == 1, "window {}", window);
+    }
+}
+
+#[test]
+fn resize_diagonal_penalty_test() {
+    let input = load(INPUT);
+    let (width, height) = input.dimensions();
+    let (width, height) = (width as usize - 3, height as usize);
+
+    let straight = rmr::resize(
+        &input,
+        ResizeOptions::new(width, height).seam_constraint(SeamConstraint::straight()),
+    )
+    .unwrap();
+    let penalized = rmr::resize(
+        &input,
+        ResizeOptions::new(width, height).seam_constraint(SeamConstraint {
+            window: 1,
+            diagonal_penalty: u32::MAX,
+        }),
+    )
+    .unwrap();
+
+    // A prohibitive penalty leaves only straight seams.
+    assert_images_eq(&straight.image, &penalized.image);
+}
+
+static INPUT: &[u8; 7256] = include_bytes!("images/input.png");
+
+fn load(bytes: &[u8]) -> DynamicImage {
+    image::load_from_memory(bytes).expect("loaded test image")
+}
+
+fn assert_images_eq(expected: &DynamicImage, image: &DynamicImage) {
+    assert_eq!(expected.dimensions(), image.dimensions());
+    for (x, y, pixel) in expected.pixels() {
+        assert_eq!(pixel, image.get_pixel(x, y), "pixel ({}, {})", x, y);
+    }
+}
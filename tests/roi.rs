@@ -0,0 +1,229 @@
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, GrayImage, Luma, Rgba, RgbaImage};
+
+use rmr::carve::{
+    CarveError, Carver, CarverOptions, Direction, ResizeMode, Roi, SeamKind, Strategy,
+};
+use rmr::progress::{Control, Progress};
+use rmr::ResizeOptions;
+
+/// Columns 20 to 39 of the full height.
+const BAND: Roi = Roi::Rect {
+    x: 20,
+    y: 0,
+    width: 20,
+    height: 48,
+};
+
+#[test]
+fn roi_rect_keeps_outside_pixels_test() {
+    let input = load_castle();
+    let (width, height) = input.dimensions();
+    let strategies = [
+        Strategy::FullResolution,
+        Strategy::pyramid(4),
+        Strategy::Disjoint { seams: 4 },
+    ];
+
+    for &strategy in &strategies {
+        let options = ResizeOptions::new(width as usize - 8, height as usize)
+            .roi(BAND)
+            .strategy(strategy);
+
+        let output = rmr::resize(&input, options).unwrap();
+
+        assert_eq!(8 * height as usize, output.seams.len());
+        for point in &output.seams {
+            assert!(
+                (20..40).contains(&point.x),
+                "{:?} removed {:?}",
+                strategy,
+                point
+            );
+        }
+        // Everything left of the region stays put, and everything right of it
+        // moves over by the seams removed.
+        let input = input.to_rgba8();
+        let output = output.image.to_rgba8();
+        for y in 0..height {
+            for x in (0..20).chain(40..width) {
+                let shifted = if x < 20 { x } else { x - 8 };
+                assert_eq!(input.get_pixel(x, y), output.get_pixel(shifted, y));
+            }
+        }
+    }
+}
+
+#[test]
+fn roi_mask_shrinks_band_test() {
+    let input = load_castle();
+    let (width, height) = input.dimensions();
+    // Only the top third, like a band of sky.
+    let mask = GrayImage::from_fn(width, height, |_, y| {
+        Luma([if y < height / 3 { 255 } else { 0 }])
+    });
+    let options = ResizeOptions::new(width as usize, height as usize - 6).roi(Roi::Mask(mask));
+
+    let output = rmr::resize(&input, options).unwrap();
+
+    assert_eq!((width, height - 6), output.image.dimensions());
+    for point in &output.seams {
+        assert!(point.y < height as usize / 3, "removed {:?}", point);
+    }
+    let input = input.to_rgba8();
+    let output = output.image.to_rgba8();
+    for y in height / 3..height {
+        for x in 0..width {
+            assert_eq!(input.get_pixel(x, y), output.get_pixel(x, y - 6));
+        }
+    }
+}
+
+#[test]
+fn roi_grow_inserts_inside_test() {
+    let input = load_castle();
+    let (width, height) = input.dimensions();
+    // Growing by more than the region is wide takes several rounds.
+    let options = ResizeOptions::new(width as usize + 30, height as usize).roi(BAND);
+
+    let output = rmr::resize(&input, options).unwrap();
+
+    assert_eq!((width + 30, height), output.image.dimensions());
+    assert!(output
+        .seams
+        .iter()
+        .all(|point| point.kind == SeamKind::Inserted && (20..40).contains(&point.x)));
+}
+
+#[test]
+fn roi_carves_in_crop_mode_test() {
+    let input = load_castle();
+    let (width, height) = input.dimensions();
+    let options = ResizeOptions::new(width as usize - 5, height as usize)
+        .roi(BAND)
+        .mode(ResizeMode::Crop);
+
+    let output = rmr::resize(&input, options).unwrap();
+
+    assert!(output.seams.iter().all(|point| (20..40).contains(&point.x)));
+    assert!(output.stats.passes.iter().all(|pass| !pass.cropped));
+}
+
+#[test]
+fn roi_too_narrow_test() {
+    let input = load_castle();
+    let (width, height) = input.dimensions();
+    let options = ResizeOptions::new(width as usize - 21, height as usize).roi(BAND);
+
+    let error = rmr::resize(&input, options).err().unwrap();
+
+    assert_eq!(
+        "The region of interest is 20 pixels across at its narrowest, so it can't change \
+         the width from 64 to 43",
+        error.to_string()
+    );
+}
+
+#[test]
+fn roi_not_spanning_seams_test() {
+    let input = load_castle();
+    let (width, height) = input.dimensions();
+
+    // Horizontal seams have to cross every column of the image.
+    let options = ResizeOptions::new(width as usize, height as usize - 1).roi(BAND);
+    assert!(rmr::resize(&input, options).is_err());
+
+    // Two quarters of the image with a column between their corners.
+    let mask = GrayImage::from_fn(width, height, |x, y| {
+        let above = x < width / 2 && y < height / 2;
+        let below = x > width / 2 && y >= height / 2;
+        Luma([if above || below { 255 } else { 0 }])
+    });
+    let options = ResizeOptions::new(width as usize - 1, height as usize).roi(Roi::Mask(mask));
+    let error = rmr::resize(&input, options).err().unwrap();
+    assert_eq!(
+        "No seam runs through the region of interest",
+        error.to_string()
+    );
+}
+
+#[test]
+fn roi_narrowing_mask_test() {
+    let input = DynamicImage::ImageRgba8(RgbaImage::from_fn(10, 2, |x, y| {
+        Rgba([(x * 25) as u8, (y * 100) as u8, 50, 255])
+    }));
+    // Only one seam joins the two rows, so the second one has nowhere to go.
+    let mask = GrayImage::from_fn(10, 2, |x, y| {
+        let inside = match y {
+            0 => x == 0 || x == 5,
+            _ => x == 1 || x == 9,
+        };
+        Luma([if inside { 255 } else { 0 }])
+    });
+    let strategies = [
+        Strategy::FullResolution,
+        Strategy::pyramid(2),
+        Strategy::Disjoint { seams: 2 },
+    ];
+
+    for &strategy in &strategies {
+        for &(width, low_memory) in &[(8, false), (12, false), (12, true)] {
+            let options = ResizeOptions::new(width, 2)
+                .roi(Roi::Mask(mask.clone()))
+                .strategy(strategy)
+                .low_memory(low_memory);
+
+            let error = rmr::resize(&input, options).err().unwrap();
+
+            assert_eq!(
+                "No seam runs through the region of interest",
+                error.to_string(),
+                "{:?} to {}",
+                strategy,
+                width
+            );
+        }
+    }
+
+    let options = CarverOptions {
+        roi: Some(Roi::Mask(mask)),
+        ..CarverOptions::default()
+    };
+    let mut carver = Carver::with_options(&input, options).unwrap();
+    let mut observer = |_: &Progress| Control::Continue;
+    assert_eq!(
+        Err(CarveError::NoSeamInRoi),
+        carver.carve(Direction::Vertical, 8, &mut observer)
+    );
+}
+
+#[test]
+fn roi_invalid_test() {
+    let input = load_castle();
+    let (width, height) = input.dimensions();
+    let rects = [(0, 0, 0, height), (60, 0, 5, height), (0, 1, width, height)];
+
+    for &(x, y, roi_width, roi_height) in &rects {
+        let roi = Roi::Rect {
+            x,
+            y,
+            width: roi_width,
+            height: roi_height,
+        };
+        let options = ResizeOptions::new(width as usize - 1, height as usize).roi(roi);
+        assert!(rmr::resize(&input, options).is_err(), "{:?}", (x, y));
+    }
+
+    let options = ResizeOptions::new(width as usize - 1, height as usize)
+        .roi(Roi::Mask(GrayImage::new(width, height + 1)));
+    assert!(rmr::resize(&input, options).is_err());
+}
+
+static CASTLE: &[u8] = include_bytes!("../images/castle.jpg");
+
+/// The castle scaled down to 64 by 48 pixels.
+fn load_castle() -> DynamicImage {
+    image::load_from_memory(CASTLE)
+        .expect("loaded test image")
+        .resize_exact(64, 48, FilterType::Triangle)
+}
@@ -0,0 +1,84 @@
+use std::time::Duration;
+
+use image::{DynamicImage, GenericImageView};
+
+use rmr::carve::Direction;
+use rmr::stats::Report;
+use rmr::ResizeOptions;
+
+#[test]
+fn stats_pass_test() {
+    let input = load(INPUT);
+    let (width, height) = input.dimensions();
+    let options = ResizeOptions::new(width as usize - 3, height as usize + 2);
+
+    let output = rmr::resize(&input, options).unwrap();
+    let passes = &output.stats.passes;
+
+    assert_eq!(2, passes.len());
+
+    assert_eq!(Direction::Vertical, passes[0].direction);
+    assert_eq!(3, passes[0].seams_removed);
+    assert_eq!(0, passes[0].seams_inserted);
+    assert_eq!(Duration::default(), passes[0].grow_precompute);
+    assert!(passes[0].seam_energy > 0);
+
+    assert_eq!(Direction::Horizontal, passes[1].direction);
+    assert_eq!(0, passes[1].seams_removed);
+    assert_eq!(2, passes[1].seams_inserted);
+    assert!(passes[1].seam_energy > 0);
+}
+
+#[test]
+fn stats_peak_memory_test() {
+    let input = load(INPUT);
+    let (width, height) = input.dimensions();
+
+    let shrink = rmr::resize(
+        &input,
+        ResizeOptions::new(width as usize - 3, height as usize),
+    )
+    .unwrap();
+    let grow = rmr::resize(
+        &input,
+        ResizeOptions::new(width as usize + 3, height as usize),
+    )
+    .unwrap();
+
+    let pixels = (width * height) as usize;
+    assert!(shrink.stats.peak_memory_bytes > pixels);
+    // Enlarging holds a second copy of the grid while planning seams.
+    assert!(grow.stats.peak_memory_bytes > shrink.stats.peak_memory_bytes);
+}
+
+#[test]
+fn stats_report_json_test() {
+    let input = load(INPUT);
+    let (width, height) = input.dimensions();
+    let options = ResizeOptions::new(width as usize - 1, height as usize);
+    let output = rmr::resize(&input, options).unwrap();
+
+    let report = Report {
+        decode: Duration::from_millis(1500),
+        encode: Duration::from_micros(1500),
+        resize: &output.stats,
+    };
+    let json = report.to_json();
+
+    assert!(json.starts_with('{'));
+    assert!(json.trim_end().ends_with('}'));
+    assert!(json.contains("\"decode\": 1.5,"));
+    assert!(json.contains("\"encode\": 0.0015\n"));
+    assert!(json.contains("\"direction\": \"vertical\""));
+    assert!(json.contains("\"cropped\": false,"));
+    assert!(json.contains("\"vertical_removed\": 1,"));
+    assert!(json.contains("\"peak_memory_bytes\": "));
+    assert_eq!(json.matches('{').count(), json.matches('}').count());
+    assert_eq!(json.matches('[').count(), json.matches(']').count());
+}
+
+static INPUT: &[u8; 7256] = include_bytes!("images/input.png");
+
+fn load(bytes: &[u8]) -> DynamicImage {
+    image::load_from_memory(bytes).expect("loaded test image")
+}
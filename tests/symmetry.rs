@@ -0,0 +1,105 @@
+use image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+
+use rmr::carve::{Direction, Symmetry};
+use rmr::ResizeOptions;
+
+#[test]
+fn symmetry_detected_axis_test() {
+    // Both an axis between columns and an axis on a column.
+    for &width in &[40, 41] {
+        let input = make_symmetric(width, 24, width - 1);
+
+        for &distance in &[6, 7] {
+            let options = ResizeOptions::new((width - distance) as usize, 24)
+                .symmetry(Symmetry::detect(Direction::Vertical));
+
+            let output = rmr::resize(&input, options).unwrap();
+
+            let (output_width, _) = output.image.dimensions();
+            assert_eq!(width - distance, output_width);
+            if distance % 2 == 0 || width % 2 == 1 {
+                assert_mirrored(&output.image, output_width - 1);
+            }
+        }
+    }
+}
+
+#[test]
+fn symmetry_breaks_without_pairs_test() {
+    let input = make_symmetric(40, 24, 39);
+    let options = ResizeOptions::new(34, 24);
+
+    let output = rmr::resize(&input, options).unwrap();
+
+    assert!(!is_mirrored(&output.image, 33));
+}
+
+#[test]
+fn symmetry_off_center_axis_test() {
+    // Only the columns from 9 to 47 mirror each other, about 28.5.
+    let input = make_symmetric(48, 24, 56);
+    for &symmetry in &[
+        Symmetry::detect(Direction::Vertical),
+        Symmetry::about(Direction::Vertical, 28.5),
+    ] {
+        let options = ResizeOptions::new(40, 24).symmetry(symmetry);
+
+        let output = rmr::resize(&input, options).unwrap();
+
+        assert_mirrored(&output.image, 48);
+    }
+}
+
+#[test]
+fn symmetry_horizontal_test() {
+    let input = make_symmetric(30, 20, 29).rotate90();
+    let options = ResizeOptions::new(20, 22).symmetry(Symmetry::detect(Direction::Horizontal));
+
+    let output = rmr::resize(&input, options).unwrap();
+
+    assert_eq!((20, 22), output.image.dimensions());
+    assert_mirrored(&output.image.rotate270(), 21);
+}
+
+#[test]
+fn symmetry_invalid_axis_test() {
+    let input = make_symmetric(10, 10, 9);
+    for &axis in &[0.0, 10.0, -3.0, f64::NAN] {
+        let options =
+            ResizeOptions::new(8, 10).symmetry(Symmetry::about(Direction::Vertical, axis));
+        assert!(rmr::resize(&input, options).is_err(), "{}", axis);
+    }
+}
+
+/// Noise mirrored so the pixels at `x` and `sum - x` match.
+fn make_symmetric(width: u32, height: u32, sum: u32) -> DynamicImage {
+    let mut state = 0x9e37_79b9u32;
+    let noise: Vec<u8> = (0..width * height)
+        .map(|_| {
+            // xorshift32
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect();
+    DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |x, y| {
+        let x = if x <= sum && sum - x < x { sum - x } else { x };
+        let value = noise[(y * width + x) as usize];
+        Rgba([value, value / 2, 255 - value, 255])
+    }))
+}
+
+/// Whether the pixels at `x` and `sum - x` match wherever both are in the
+/// image.
+fn is_mirrored(image: &DynamicImage, sum: u32) -> bool {
+    let (width, height) = image.dimensions();
+    let first = sum.saturating_sub(width - 1);
+    (0..height).all(|y| {
+        (first..=sum - first).all(|x| image.get_pixel(x, y) == image.get_pixel(sum - x, y))
+    })
+}
+
+fn assert_mirrored(image: &DynamicImage, sum: u32) {
+    assert!(is_mirrored(image, sum), "not mirrored about {}", sum);
+}
@@ -0,0 +1,161 @@
+use image::{DynamicImage, GenericImageView, GrayImage, Rgba, RgbaImage};
+
+use rmr::energy::{text_mask, TextOptions};
+use rmr::ResizeOptions;
+
+const INK: Rgba<u8> = Rgba([30, 30, 30, 255]);
+const PAPER: Rgba<u8> = Rgba([250, 250, 245, 255]);
+
+#[test]
+fn text_dark_on_light_test() {
+    let input = make_text("HELLO", INK, PAPER);
+
+    let mask = text_mask(&input, &TextOptions::default());
+
+    assert_covers_ink(&input, &mask, INK);
+    assert_eq!(0, mask.get_pixel(0, 0)[0]);
+    assert_eq!(0, mask.get_pixel(input.width() - 1, input.height() - 1)[0]);
+}
+
+#[test]
+fn text_light_on_dark_test() {
+    let input = make_text("HOLE", PAPER, INK);
+
+    let mask = text_mask(&input, &TextOptions::default());
+
+    assert_covers_ink(&input, &mask, PAPER);
+    assert_eq!(0, mask.get_pixel(0, 0)[0]);
+}
+
+#[test]
+fn text_single_letter_test() {
+    let input = make_text("H", INK, PAPER);
+
+    let mask = text_mask(&input, &TextOptions::default());
+
+    assert!(mask.pixels().all(|pixel| pixel[0] == 0));
+}
+
+#[test]
+fn text_ignores_shapes_test() {
+    let flat = DynamicImage::ImageRgba8(RgbaImage::from_pixel(60, 40, PAPER));
+    let ramp = DynamicImage::ImageRgba8(RgbaImage::from_fn(60, 40, |x, _| {
+        Rgba([x as u8 * 4, x as u8 * 4, x as u8 * 4, 255])
+    }));
+    let checkers = DynamicImage::ImageRgba8(RgbaImage::from_fn(96, 48, |x, y| {
+        if (x / 24 + y / 24) % 2 == 0 {
+            INK
+        } else {
+            PAPER
+        }
+    }));
+
+    for input in &[flat, ramp, checkers] {
+        let mask = text_mask(input, &TextOptions::default());
+        assert!(mask.pixels().all(|pixel| pixel[0] == 0));
+    }
+}
+
+#[test]
+fn text_survives_resize_test() {
+    let input = frame(&make_text("HELLO", INK, PAPER));
+    let (width, height) = input.dimensions();
+    let target = || ResizeOptions::new(width as usize - 16, height as usize);
+
+    let plain = rmr::resize(&input, target()).unwrap();
+    let protected = rmr::resize(&input, target().text(TextOptions::default())).unwrap();
+
+    // The paper between the letters is the cheapest way down the image, so
+    // plain seams squeeze the letters together.
+    let (left, right) = ink_span(&input);
+    let (plain_left, plain_right) = ink_span(&plain.image);
+    assert!(plain_right - plain_left < right - left);
+    // The row of letters is kept whole, gaps and all, though seams may
+    // still cut through the frame above and below it.
+    let (top, bottom) = (12, 32); // The rows the letters are drawn on.
+    let (output_left, output_right) = ink_span(&protected.image);
+    assert_eq!(right - left, output_right - output_left);
+    for y in top..=bottom {
+        for x in 0..=right - left {
+            assert_eq!(
+                input.get_pixel(left + x, y),
+                protected.image.get_pixel(output_left + x, y)
+            );
+        }
+    }
+}
+
+/// Asserts that every `ink` pixel of `input` is in the mask.
+fn assert_covers_ink(input: &DynamicImage, mask: &GrayImage, ink: Rgba<u8>) {
+    for (x, y, pixel) in input.pixels() {
+        if pixel == ink {
+            assert_eq!(255, mask.get_pixel(x, y)[0], "({}, {}) isn't masked", x, y);
+        }
+    }
+}
+
+/// The first and last columns with ink on them.
+fn ink_span(image: &DynamicImage) -> (u32, u32) {
+    let columns: Vec<u32> = (0..image.width())
+        .filter(|&x| (0..image.height()).any(|y| image.get_pixel(x, y) == INK))
+        .collect();
+    (columns[0], columns[columns.len() - 1])
+}
+
+/// Fills the margins of `make_text` with colorful bands of the same
+/// brightness, which have plenty of energy but no edges to find text along.
+fn frame(text: &DynamicImage) -> DynamicImage {
+    let colors = [[200, 100, 0, 255], [0, 150, 94, 255], [100, 125, 48, 255]];
+    let (width, height) = text.dimensions();
+    DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |x, y| {
+        if x < 9 || y < 9 || x >= width - 9 || y >= height - 9 {
+            Rgba(colors[((x + y) % 3) as usize])
+        } else {
+            text.get_pixel(x, y)
+        }
+    }))
+}
+
+/// Block letters from a 5 by 7 font, scaled up three times, one cell apart
+/// with a margin of four cells.
+fn make_text(text: &str, ink: Rgba<u8>, paper: Rgba<u8>) -> DynamicImage {
+    const SCALE: u32 = 3;
+    const MARGIN: u32 = 4;
+    let glyphs: Vec<[&str; 7]> = text.chars().map(glyph).collect();
+    let width = (glyphs.len() as u32 * 6 - 1 + MARGIN * 2) * SCALE;
+    let height = (7 + MARGIN * 2) * SCALE;
+    DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |x, y| {
+        let (column, row) = (x / SCALE, y / SCALE);
+        if column < MARGIN || !(MARGIN..MARGIN + 7).contains(&row) {
+            return paper;
+        }
+        let (letter, column) = (((column - MARGIN) / 6) as usize, (column - MARGIN) % 6);
+        match glyphs.get(letter) {
+            Some(rows)
+                if column < 5
+                    && rows[(row - MARGIN) as usize].as_bytes()[column as usize] == b'#' =>
+            {
+                ink
+            }
+            _ => paper,
+        }
+    }))
+}
+
+fn glyph(letter: char) -> [&'static str; 7] {
+    match letter {
+        'H' => [
+            "#...#", "#...#", "#...#", "#####", "#...#", "#...#", "#...#",
+        ],
+        'E' => [
+            "#####", "#....", "#....", "####.", "#....", "#....", "#####",
+        ],
+        'L' => [
+            "#....", "#....", "#....", "#....", "#....", "#....", "#####",
+        ],
+        'O' => [
+            ".###.", "#...#", "#...#", "#...#", "#...#", "#...#", ".###.",
+        ],
+        _ => panic!("No glyph for {}", letter),
+    }
+}
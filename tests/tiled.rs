@@ -0,0 +1,280 @@
+use std::io::Cursor;
+
+use image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+
+use rmr::carve::{Interpolation, Roi};
+use rmr::tiff::{TiledTiffReader, TiledTiffWriter};
+use rmr::tiled::StripSource;
+use rmr::{BoxResult, ResizeOptions, TileOptions};
+
+#[test]
+fn tiled_tiff_roundtrip_test() {
+    let input = make_noise(300, 270);
+    let mut writer = TiledTiffWriter::new(Cursor::new(vec![]), 300, 270).unwrap();
+    for &(x, width) in &[(0, 100), (100, 1), (101, 199)] {
+        writer
+            .write_columns(&input.crop_imm(x, 0, width, 270))
+            .unwrap();
+    }
+    let bytes = writer.finish().unwrap().into_inner();
+
+    assert_eq!(b"II*\0", &bytes[..4]);
+    let mut reader = TiledTiffReader::new(Cursor::new(bytes)).unwrap();
+    assert_eq!((300, 270), reader.size());
+    assert_eq!(
+        input.to_rgba8(),
+        reader.read_strip(0, 300).unwrap().to_rgba8()
+    );
+    assert_eq!(
+        input.crop_imm(250, 0, 40, 270).to_rgba8(),
+        reader.read_strip(250, 40).unwrap().to_rgba8()
+    );
+}
+
+#[test]
+fn tiled_tiff_writer_counts_columns_test() {
+    let mut writer = TiledTiffWriter::new(Cursor::new(vec![]), 10, 4).unwrap();
+    writer.write_columns(&make_noise(6, 4)).unwrap();
+
+    assert!(writer.write_columns(&make_noise(5, 4)).is_err());
+    assert!(writer.write_columns(&make_noise(2, 3)).is_err());
+    assert!(writer.finish().is_err());
+}
+
+#[test]
+fn tiled_single_strip_matches_resize_test() {
+    let input = make_noise(60, 40);
+    let tiles = TileOptions {
+        strip_width: 60,
+        overlap: 16,
+    };
+
+    for &width in &[45, 60, 80] {
+        let whole = rmr::resize(&input, ResizeOptions::new(width, 40)).unwrap();
+        let tiled = resize_tiled(&mut input.clone(), ResizeOptions::new(width, 40), &tiles);
+
+        assert_eq!(whole.image.to_rgba8(), tiled.unwrap().to_rgba8());
+    }
+}
+
+#[test]
+fn tiled_seams_stay_in_strips_test() {
+    let input = make_numbered(100, 30);
+    let tiles = TileOptions {
+        strip_width: 25,
+        overlap: 4,
+    };
+
+    for &width in &[80, 132] {
+        // Averaged pixels would blur the column numbers.
+        let options = ResizeOptions::new(width, 30).interpolation(Interpolation::Duplicate);
+        let output = resize_tiled(&mut input.clone(), options, &tiles);
+
+        // Four strips of 25 columns. Each keeps the same number of columns
+        // in every row, whatever its share of the seams.
+        let output = output.unwrap();
+        assert_eq!((width as u32, 30), output.dimensions());
+        let mut shares = None;
+        for y in 0..30 {
+            let columns: Vec<u32> = (0..output.width())
+                .map(|x| u32::from(output.get_pixel(x, y)[0]))
+                .collect();
+            assert!(columns.windows(2).all(|pair| pair[0] <= pair[1]));
+            let mut row_shares = [0; 4];
+            for &column in &columns {
+                row_shares[column as usize / 25] += 1;
+            }
+            assert_eq!(*shares.get_or_insert(row_shares), row_shares, "row {}", y);
+        }
+    }
+}
+
+#[test]
+fn tiled_seams_follow_energy_test() {
+    // Smooth on the left, noise on the right.
+    let noise = make_noise(100, 30);
+    let input = DynamicImage::ImageRgba8(RgbaImage::from_fn(100, 30, |x, y| {
+        if x < 50 {
+            Rgba([x as u8, 0, 0, 255])
+        } else {
+            let [_, g, b, a] = noise.get_pixel(x, y).0;
+            Rgba([x as u8, g, b, a])
+        }
+    }));
+    let tiles = TileOptions {
+        strip_width: 50,
+        overlap: 4,
+    };
+
+    let output = resize_tiled(&mut input.clone(), ResizeOptions::new(70, 30), &tiles).unwrap();
+
+    // Equal shares would take 15 columns from each half.
+    let smooth = (0..70).filter(|&x| output.get_pixel(x, 0)[0] < 50).count();
+    assert!(smooth < 30, "{} smooth columns left", smooth);
+}
+
+#[test]
+fn tiled_reads_strips_test() {
+    let input = make_noise(100, 30);
+    let tiles = TileOptions {
+        strip_width: 30,
+        overlap: 5,
+    };
+    let mut source = Recording {
+        image: input.clone(),
+        widths: vec![],
+    };
+
+    let output = resize_tiled(&mut source, ResizeOptions::new(70, 30), &tiles).unwrap();
+
+    // Every strip is read once for the estimate, then again with overlap.
+    assert_eq!(vec![25, 25, 25, 25, 30, 35, 35, 30], source.widths);
+    let mut bytes = Cursor::new(vec![]);
+    let mut writer = TiledTiffWriter::new(&mut bytes, 100, 30).unwrap();
+    writer.write_columns(&input).unwrap();
+    writer.finish().unwrap();
+    bytes.set_position(0);
+    let mut reader = TiledTiffReader::new(bytes).unwrap();
+    let from_tiff = resize_tiled(&mut reader, ResizeOptions::new(70, 30), &tiles).unwrap();
+    assert_eq!(output.to_rgba8(), from_tiff.to_rgba8());
+}
+
+#[test]
+fn tiled_errors_test() {
+    let input = make_noise(40, 20);
+    let tiles = TileOptions {
+        strip_width: 10,
+        overlap: 2,
+    };
+    let no_overlap = TileOptions {
+        strip_width: 10,
+        overlap: 0,
+    };
+    let roi = Roi::Rect {
+        x: 0,
+        y: 0,
+        width: 20,
+        height: 20,
+    };
+
+    let tiled = |options, tiles| resize_tiled(&mut input.clone(), options, tiles).is_err();
+    assert!(tiled(ResizeOptions::new(30, 15), &tiles));
+    assert!(tiled(ResizeOptions::new(3, 20), &tiles));
+    assert!(tiled(ResizeOptions::new(30, 20), &no_overlap));
+    assert!(tiled(ResizeOptions::new(30, 20).roi(roi), &tiles));
+    assert!(!tiled(ResizeOptions::new(4, 20), &tiles));
+}
+
+#[test]
+fn tiled_tiff_reader_rejects_bad_headers_test() {
+    let mut reader = TiledTiffReader::new(Cursor::new(make_tiff(1, 1, 3))).unwrap();
+    assert_eq!(
+        Rgba([10, 20, 30, 255]),
+        reader.read_strip(0, 1).unwrap().get_pixel(0, 0)
+    );
+
+    let read = |bytes: Vec<u8>| TiledTiffReader::new(Cursor::new(bytes)).map(|_| ());
+
+    // Tiles of no size, and tiles running past the end of the file.
+    assert!(read(make_tiff(0, 1, 3)).is_err());
+    assert!(read(make_tiff(1, 0, 3)).is_err());
+    assert!(read(make_tiff(4096, 4096, 3)).is_err());
+
+    // Counts far larger than the file.
+    assert!(read(make_tiff(1, 1, 0x4000_0000)).is_err());
+    let mut bytes = make_tiff(1, 1, 3);
+    bytes[8..10].copy_from_slice(&u16::MAX.to_le_bytes());
+    assert!(read(bytes).is_err());
+}
+
+/// Resizes `source` into an in-memory TIFF and reads it back.
+fn resize_tiled<S: StripSource>(
+    source: &mut S,
+    options: ResizeOptions,
+    tiles: &TileOptions,
+) -> BoxResult<DynamicImage> {
+    let mut bytes = Cursor::new(vec![]);
+    rmr::resize_tiled(source, options, tiles, &mut bytes)?;
+    bytes.set_position(0);
+    let mut reader = TiledTiffReader::new(bytes)?;
+    let (width, _) = reader.size();
+    reader.read_strip(0, width)
+}
+
+/// Keeps the width of every strip read from `image`.
+struct Recording {
+    image: DynamicImage,
+    widths: Vec<u32>,
+}
+
+impl StripSource for Recording {
+    fn size(&self) -> (u32, u32) {
+        self.image.dimensions()
+    }
+
+    fn read_strip(&mut self, x: u32, width: u32) -> BoxResult<DynamicImage> {
+        self.widths.push(width);
+        self.image.read_strip(x, width)
+    }
+}
+
+/// Noise with each pixel's column number in its red channel, so the columns
+/// of a carved image can be traced back.
+fn make_numbered(width: u32, height: u32) -> DynamicImage {
+    let noise = make_noise(width, height);
+    DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |x, y| {
+        let [_, g, b, a] = noise.get_pixel(x, y).0;
+        Rgba([x as u8, g, b, a])
+    }))
+}
+
+/// A one pixel RGB tiled TIFF with the given tile size and number of
+/// `BitsPerSample` values.
+fn make_tiff(tile_width: u32, tile_length: u32, bits_count: u32) -> Vec<u8> {
+    const SHORT: u16 = 3;
+    const LONG: u16 = 4;
+    // The header, then a directory of ten entries, then the bits per sample
+    // and the pixel.
+    let bits_offset = 8 + 2 + 10 * 12 + 4;
+    let pixel_offset = bits_offset + 6;
+    let entries = [
+        (256, LONG, 1, 1),
+        (257, LONG, 1, 1),
+        (258, SHORT, bits_count, bits_offset),
+        (259, SHORT, 1, 1),
+        (262, SHORT, 1, 2),
+        (277, SHORT, 1, 3),
+        (284, SHORT, 1, 1),
+        (322, LONG, 1, tile_width),
+        (323, LONG, 1, tile_length),
+        (324, LONG, 1, pixel_offset),
+    ];
+
+    let mut bytes = b"II*\0".to_vec();
+    bytes.extend_from_slice(&8u32.to_le_bytes());
+    bytes.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    for &(tag, field_type, count, value) in &entries {
+        bytes.extend_from_slice(&(tag as u16).to_le_bytes());
+        bytes.extend_from_slice(&field_type.to_le_bytes());
+        bytes.extend_from_slice(&count.to_le_bytes());
+        bytes.extend_from_slice(&value.to_le_bytes());
+    }
+    bytes.extend_from_slice(&0u32.to_le_bytes());
+    for _ in 0..3 {
+        bytes.extend_from_slice(&8u16.to_le_bytes());
+    }
+    bytes.extend_from_slice(&[10, 20, 30]);
+    bytes
+}
+
+/// Pseudo-random noise, so seams wander all over the image.
+fn make_noise(width: u32, height: u32) -> DynamicImage {
+    let mut state = 0x2545_f491u32;
+    DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |_, _| {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        let [r, g, b, _] = state.to_le_bytes();
+        Rgba([r, g, b, 255])
+    }))
+}